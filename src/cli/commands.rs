@@ -0,0 +1,551 @@
+//! Implementation of the subcommands.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{info, warn};
+
+use space_packets::decom::{Database, Decommutator};
+use space_packets::export::{ExportConfig, Exporter};
+use space_packets::io::net;
+use space_packets::io::pipeline::{self, Pipeline};
+use space_packets::io::reader::HEADER_SIZE;
+use space_packets::io::recording::{RecordingReader, RecordingWriter, Replayer, Speed};
+use space_packets::protocol::{Encoding, PrimaryHeader};
+use space_packets::pus::SEQUENCE_COUNTER_MASK;
+use space_packets::{Filter, Packet, Reader};
+
+use super::input::{self, RawPacket, RawPackets, Source, Transport};
+use super::output::{self, Format, Printer};
+use super::{InputArgs, OutputArgs};
+
+/// Packets in flight in the multi-threaded pipeline
+const PIPELINE_CAPACITY: usize = 1024;
+
+fn parse(raw: &RawPacket) -> Result<Packet> {
+    Packet::try_from_buffers(&raw.buf[..HEADER_SIZE], &raw.buf[HEADER_SIZE..])
+}
+
+/// Valid packets of the inputs: invalid ones are reported and skipped.
+fn valid_packets(input: &InputArgs) -> impl Iterator<Item = (RawPacket, Packet)> {
+    RawPackets::new(input).filter_map(|raw| {
+        let raw = match raw {
+            Ok(raw) => raw,
+            Err(e) => {
+                warn!("{:#}", e);
+                return None;
+            }
+        };
+        match parse(&raw) {
+            Ok(pkt) => Some((raw, pkt)),
+            Err(e) => {
+                warn!(
+                    "Skipping packet of `{}` at offset `{}`: {:#}",
+                    raw.input, raw.offset, e
+                );
+                None
+            }
+        }
+    })
+}
+
+fn filter_expr(expr: Option<&str>) -> Result<Option<Filter>> {
+    expr.map(|expr| Filter::parse(expr).context("Invalid filter expression"))
+        .transpose()
+}
+
+pub fn decode(
+    input: &InputArgs,
+    output: &OutputArgs,
+    format: Format,
+    encoding: Encoding,
+    filter: Option<&str>,
+    workers: Option<usize>,
+) -> Result<()> {
+    let filter = filter_expr(filter)?;
+    let matches = |pkt: &Packet| filter.as_ref().is_none_or(|filter| filter.matches(pkt));
+    let mut printer = Printer::new(output::open(output)?, format).with_encoding(encoding);
+
+    match workers {
+        None => {
+            for (_, pkt) in valid_packets(input) {
+                if matches(&pkt) {
+                    printer.print(pkt)?;
+                }
+            }
+        }
+        Some(workers) => {
+            let paths = if input.inputs.is_empty() {
+                vec![None]
+            } else {
+                input
+                    .inputs
+                    .iter()
+                    .map(|path| Some(path.as_path()))
+                    .collect()
+            };
+            let mut stream: Box<dyn Read + Send> = Box::new(io::empty());
+            for path in paths {
+                stream = Box::new(stream.chain(input::open(path, input.source, input.port)?));
+            }
+
+            let (pipeline, receiver) =
+                Pipeline::new(stream, workers, PIPELINE_CAPACITY, pipeline::decode);
+            let pipeline_thread = thread::spawn(move || pipeline.run());
+            for res in receiver {
+                match res {
+                    Ok(decoded) if matches(&decoded.packet) => printer.print(decoded.packet)?,
+                    Ok(_) => {}
+                    Err(e) => warn!("Skipping packet: {:#}", e),
+                }
+            }
+            pipeline_thread
+                .join()
+                .map_err(|_| anyhow!("Decoding panicked"))??;
+        }
+    }
+
+    printer.flush()
+}
+
+pub fn encode(input: Option<&Path>, output: &OutputArgs, yaml: bool) -> Result<()> {
+    let mut description = String::new();
+    match input {
+        Some(path) => File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut description))
+            .with_context(|| format!("Could not read `{}`", path.display()))?,
+        None => io::stdin()
+            .read_to_string(&mut description)
+            .context("Could not read stdin")?,
+    };
+
+    let is_yaml = yaml
+        || input
+            .and_then(Path::extension)
+            .is_some_and(|ext| ext == "yaml" || ext == "yml");
+    let mut printer = Printer::new(output::open(output)?, Format::Raw);
+    for pkt in described_packets(&description, is_yaml)? {
+        printer.print(pkt)?;
+    }
+    printer.flush()
+}
+
+/// Packets of a description: a list, a single packet, or (in JSON) one packet per line.
+fn described_packets(description: &str, is_yaml: bool) -> Result<Vec<Packet>> {
+    let values: Vec<serde_json::Value> = if is_yaml {
+        vec![serde_yaml::from_str(description).context("Invalid YAML description")?]
+    } else {
+        serde_json::Deserializer::from_str(description)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .context("Invalid JSON description")?
+    };
+
+    values
+        .into_iter()
+        .flat_map(|value| match value {
+            serde_json::Value::Array(values) => values,
+            value => vec![value],
+        })
+        .enumerate()
+        .map(|(idx, value)| {
+            serde_json::from_value(value).with_context(|| format!("Invalid packet `{}`", idx))
+        })
+        .collect()
+}
+
+pub fn filter(expr: &str, input: &InputArgs, output: &OutputArgs) -> Result<()> {
+    let filter = Filter::parse(expr).context("Invalid filter expression")?;
+    let mut dst = output::open(output)?;
+
+    for (raw, pkt) in valid_packets(input) {
+        if filter.matches(&pkt) {
+            dst.write_all(&raw.buf)?;
+        }
+    }
+    dst.flush().context("Could not flush the output")
+}
+
+#[derive(Default)]
+struct ApidStats {
+    packets: u64,
+    bytes: u64,
+    invalid: u64,
+    gaps: u64,
+    last_counter: Option<u16>,
+    first_time: Option<Duration>,
+    last_time: Option<Duration>,
+}
+
+pub fn stats(input: &InputArgs, output: &OutputArgs) -> Result<()> {
+    let mut stats: BTreeMap<u16, ApidStats> = BTreeMap::new();
+    let mut read_errors = 0;
+
+    for raw in RawPackets::new(input) {
+        let raw = match raw {
+            Ok(raw) => raw,
+            Err(e) => {
+                warn!("{:#}", e);
+                read_errors += 1;
+                continue;
+            }
+        };
+
+        // The header is readable, even when the checksum is invalid
+        let header = PrimaryHeader::from_buffer(&raw.buf[..HEADER_SIZE]);
+        let counter = header.sequence_counter;
+        let entry = stats.entry(header.apid).or_default();
+        entry.packets += 1;
+        entry.bytes += raw.buf.len() as u64;
+        if let Some(last) = entry.last_counter {
+            if counter != (last + 1) & SEQUENCE_COUNTER_MASK {
+                entry.gaps += 1;
+            }
+        }
+        entry.last_counter = Some(counter);
+
+        match parse(&raw) {
+            Ok(pkt) => {
+                if let Some(time) = pkt.sec_header.as_ref().map(|header| header.time()) {
+                    entry.first_time.get_or_insert(time);
+                    entry.last_time = Some(time);
+                }
+            }
+            Err(_) => entry.invalid += 1,
+        }
+    }
+
+    let time = |time: Option<Duration>| {
+        time.map_or("-".to_string(), |t| format!("{:.3}", t.as_secs_f64()))
+    };
+    let mut dst = output::open(output)?;
+    writeln!(
+        dst,
+        "{:>6} {:>10} {:>12} {:>8} {:>6} {:>16} {:>16}",
+        "APID", "packets", "bytes", "invalid", "gaps", "first time", "last time"
+    )?;
+    for (apid, stats) in &stats {
+        writeln!(
+            dst,
+            "{:>#6x} {:>10} {:>12} {:>8} {:>6} {:>16} {:>16}",
+            apid,
+            stats.packets,
+            stats.bytes,
+            stats.invalid,
+            stats.gaps,
+            time(stats.first_time),
+            time(stats.last_time)
+        )?;
+    }
+    let total = |f: fn(&ApidStats) -> u64| stats.values().map(f).sum::<u64>();
+    writeln!(
+        dst,
+        "{:>6} {:>10} {:>12} {:>8} {:>6}",
+        "total",
+        total(|s| s.packets),
+        total(|s| s.bytes),
+        total(|s| s.invalid),
+        total(|s| s.gaps)
+    )?;
+    if read_errors > 0 {
+        writeln!(
+            dst,
+            "{} input(s) ended with an unreadable packet",
+            read_errors
+        )?;
+    }
+    dst.flush().context("Could not flush the output")
+}
+
+/// Path of the file of the APID, in the directory.
+fn apid_path(dir: &Path, apid: u16) -> PathBuf {
+    dir.join(format!("apid-{:#05x}.bin", apid))
+}
+
+pub fn split(input: &InputArgs, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Could not create the directory `{}`", dir.display()))?;
+
+    let mut files = HashMap::new();
+    for (raw, pkt) in valid_packets(input) {
+        let apid = pkt.pri_header.apid;
+        let file = match files.get_mut(&apid) {
+            Some(file) => file,
+            None => {
+                let path = apid_path(dir, apid);
+                let file = File::create(&path)
+                    .with_context(|| format!("Could not create `{}`", path.display()))?;
+                files.entry(apid).or_insert(BufWriter::new(file))
+            }
+        };
+        file.write_all(&raw.buf)?;
+    }
+
+    for file in files.values_mut() {
+        file.flush()?;
+    }
+    info!("Split in {} file(s)", files.len());
+    Ok(())
+}
+
+/// Next packet of an input to merge, timed by its secondary header. Packets without
+/// one keep the time of the previous packet of their input.
+fn next_timed(
+    packets: &mut dyn Iterator<Item = (RawPacket, Packet)>,
+    last_time: Duration,
+) -> Option<(Duration, Vec<u8>)> {
+    let (raw, pkt) = packets.next()?;
+    let time = pkt
+        .sec_header
+        .as_ref()
+        .map_or(last_time, |header| header.time());
+    Some((time, raw.buf))
+}
+
+pub fn merge(inputs: &[PathBuf], output: &OutputArgs) -> Result<()> {
+    let mut inputs = inputs
+        .iter()
+        .map(|path| {
+            let args = InputArgs {
+                inputs: vec![path.clone()],
+                source: Source::Raw,
+                port: None,
+            };
+            Box::new(valid_packets(&args)) as Box<dyn Iterator<Item = (RawPacket, Packet)>>
+        })
+        .collect::<Vec<_>>();
+    let mut heads = inputs
+        .iter_mut()
+        .map(|packets| next_timed(packets, Duration::ZERO))
+        .collect::<Vec<_>>();
+
+    let mut dst = output::open(output)?;
+    loop {
+        // Earliest packet, the first input winning ties
+        let next = heads
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, head)| head.as_ref().map(|(time, _)| (*time, idx)))
+            .min();
+        let idx = match next {
+            Some((_, idx)) => idx,
+            None => break,
+        };
+
+        let (time, buf) = heads[idx].take().unwrap();
+        dst.write_all(&buf)?;
+        heads[idx] = next_timed(&mut inputs[idx], time);
+    }
+    dst.flush().context("Could not flush the output")
+}
+
+pub fn export(
+    definitions: &Path,
+    input: &InputArgs,
+    dir: &Path,
+    config: ExportConfig,
+) -> Result<()> {
+    let database = Database::load(definitions)?;
+    let mut exporter = Exporter::new(dir, config, database.clone())?;
+    let decommutator = Decommutator::new(database);
+
+    let mut undefined = 0;
+    for (raw, pkt) in valid_packets(input) {
+        match decommutator.decommutate(&pkt) {
+            Ok(Some(samples)) => exporter.write(&pkt, &samples)?,
+            Ok(None) => undefined += 1,
+            Err(e) => warn!(
+                "Skipping packet of `{}` at offset `{}`: {:#}",
+                raw.input, raw.offset, e
+            ),
+        }
+    }
+    if undefined > 0 {
+        info!("Skipped {} packet(s) of APIDs not defined", undefined);
+    }
+
+    for path in exporter.finish()? {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+pub fn validate(input: &InputArgs) -> Result<()> {
+    let (mut packets, mut invalid) = (0, 0);
+
+    for raw in RawPackets::new(input) {
+        let error = match raw {
+            Ok(raw) => {
+                packets += 1;
+                match parse(&raw) {
+                    Ok(_) => continue,
+                    Err(e) => format!("`{}` at offset `{}`: {:#}", raw.input, raw.offset, e),
+                }
+            }
+            Err(e) => format!("{:#}", e),
+        };
+        println!("{}", error);
+        invalid += 1;
+    }
+
+    if invalid > 0 {
+        bail!("{} invalid packet(s) out of {}", invalid, packets);
+    }
+    println!("{} valid packet(s)", packets);
+    Ok(())
+}
+
+/// Prints the packets of the channel while the job produces them.
+fn print_all<F>(job: F, receiver: Receiver<Packet>, printer: &mut Printer<impl Write>) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    let job_thread = thread::spawn(job);
+    for pkt in receiver {
+        printer.print(pkt)?;
+        printer.flush()?;
+    }
+    job_thread.join().map_err(|_| anyhow!("Reading panicked"))?
+}
+
+pub fn listen(transport: Transport, addr: &str, output: &OutputArgs, format: Format) -> Result<()> {
+    let mut printer = Printer::new(output::open(output)?, format);
+
+    match transport {
+        Transport::Udp => {
+            let (mut reader, receiver) = net::UdpReader::bind(addr)?;
+            info!("Listening on udp://{}", reader.local_addr()?);
+            print_all(move || reader.run(), receiver, &mut printer)
+        }
+        Transport::TcpServer => {
+            let server = net::TcpServer::bind(addr)?;
+            info!("Listening on tcp://{}", server.local_addr()?);
+            // One front-end at a time, until the listener fails
+            loop {
+                let (mut reader, receiver) = server.accept()?;
+                if let Err(e) = print_all(move || reader.run(), receiver, &mut printer) {
+                    warn!("Connection closed: {:#}", e);
+                }
+            }
+        }
+        Transport::TcpClient => {
+            let (mut reader, receiver) = net::tcp_client(addr)?;
+            print_all(move || reader.run(), receiver, &mut printer)
+        }
+    }
+}
+
+pub fn record(path: &Path) -> Result<()> {
+    let mut metadata = BTreeMap::new();
+    metadata.insert("source".to_string(), "stdin".to_string());
+    let dst =
+        File::create(path).with_context(|| format!("Could not create `{}`", path.display()))?;
+    let mut writer = RecordingWriter::new(dst, &metadata)?;
+
+    let (mut reader, receiver) = Reader::new(io::stdin());
+    let reader_thread = thread::spawn(move || reader.run());
+    for pkt in receiver {
+        writer.write_now(pkt)?;
+    }
+    writer.flush()?;
+    reader_thread
+        .join()
+        .map_err(|_| anyhow!("Reading panicked"))?
+}
+
+pub fn replay(path: &Path, speed: &str, output: &OutputArgs, format: Format) -> Result<()> {
+    let speed = match speed {
+        "max" => Speed::AsFastAsPossible,
+        factor => {
+            let factor: f64 = factor.parse().context("Invalid speed")?;
+            if factor == 1.0 {
+                Speed::Original
+            } else {
+                Speed::scaled(factor)?
+            }
+        }
+    };
+    let src = File::open(path).with_context(|| format!("Could not open `{}`", path.display()))?;
+    let (mut replayer, receiver) = Replayer::new(RecordingReader::open(src)?, speed);
+
+    let mut printer = Printer::new(output::open(output)?, format);
+    print_all(move || replayer.run(), receiver, &mut printer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn descriptions() -> Result<()> {
+        let json = r#"{"type": "tm", "apid": 1}
+{"type": "tc", "apid": 2, "user_data": "0102"}"#;
+        let packets = described_packets(json, false)?;
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].pri_header.data_length, 3);
+
+        let yaml = "- type: tc\n  apid: 0x10\n  user_data: '0102'\n- {type: tm, apid: 1}\n";
+        let packets = described_packets(yaml, true)?;
+        assert_eq!(packets[0].pri_header.apid, 0x10);
+        assert!(packets[1].user_data.is_none());
+
+        let err =
+            described_packets(r#"[{"type": "tm", "apid": 1}, {"apid": 1}]"#, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid packet `1`"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn time_ordered_merge() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("merge-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        // Packets of the given milliseconds (in the week), and sequence counters
+        let write = |name: &str, times: &[(u32, u16)]| -> Result<PathBuf> {
+            let mut buf = Vec::new();
+            for (ms, seq) in times {
+                let description = serde_json::json!({
+                    "type": "tm",
+                    "apid": 1,
+                    "sequence_counter": seq,
+                    "secondary_header": {"time_week": 0, "time_ms": ms},
+                });
+                for pkt in described_packets(&description.to_string(), false)? {
+                    buf.extend_from_slice(&pkt.into_buffer());
+                }
+            }
+            let path = dir.join(name);
+            fs::write(&path, buf)?;
+            Ok(path)
+        };
+        let inputs = [
+            write("a.bin", &[(10, 1), (30, 3), (50, 5)])?,
+            write("b.bin", &[(20, 2), (30, 4), (60, 6)])?,
+        ];
+
+        let merged = dir.join("merged.bin");
+        merge(
+            &inputs,
+            &OutputArgs {
+                output: Some(merged.clone()),
+            },
+        )?;
+
+        let merged = fs::read(merged)?;
+        let seqs = merged
+            .chunks(merged.len() / 6)
+            .map(|pkt| pkt[3])
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, [1, 2, 3, 4, 5, 6]);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
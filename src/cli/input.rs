@@ -0,0 +1,186 @@
+//! Inputs of the commands: packet streams read from files or stdin, in various formats.
+
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+
+use space_packets::io::framing::{Cobs, DeframedReader, Hdlc, Kiss, Slip};
+use space_packets::io::pcap::{PcapConfig, PcapReader};
+use space_packets::io::reader::read_raw;
+use space_packets::io::RecordingReader;
+
+use super::InputArgs;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Source {
+    /// Packets one after the other.
+    Raw,
+    /// pcap or pcapng capture of UDP datagrams.
+    Pcap,
+    /// Recording, with arrival times.
+    Recording,
+    Kiss,
+    Slip,
+    Hdlc,
+    Cobs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Transport {
+    /// One packet per datagram.
+    Udp,
+    /// Packet streams pushed by front-ends, one at a time.
+    TcpServer,
+    /// Packet stream served by a front-end.
+    TcpClient,
+}
+
+/// Packet stream of the file, or of stdin.
+pub fn open(
+    path: Option<&Path>,
+    source: Source,
+    port: Option<u16>,
+) -> Result<Box<dyn Read + Send>> {
+    let src: Box<dyn Read + Send> = match path {
+        Some(path) => Box::new(
+            File::open(path).with_context(|| format!("Could not open `{}`", path.display()))?,
+        ),
+        None => Box::new(io::stdin()),
+    };
+
+    let stream: Box<dyn Read + Send> = match source {
+        Source::Raw => src,
+        Source::Pcap => {
+            let config = PcapConfig {
+                ports: port.into_iter().collect(),
+                ..Default::default()
+            };
+            Box::new(PcapReader::new(src, config)?)
+        }
+        Source::Recording => match path {
+            Some(path) => Box::new(RecordStream::open(path)?),
+            None => bail!("Recordings cannot be read from stdin"),
+        },
+        Source::Kiss => Box::new(DeframedReader::new(src, Kiss::default())),
+        Source::Slip => Box::new(DeframedReader::new(src, Slip::new())),
+        Source::Hdlc => Box::new(DeframedReader::new(src, Hdlc::new())),
+        Source::Cobs => Box::new(DeframedReader::new(src, Cobs::new())),
+    };
+    Ok(stream)
+}
+
+/// Packets of a recording, as a stream.
+struct RecordStream {
+    recording: RecordingReader<File>,
+    next: usize,
+    record: Cursor<Vec<u8>>,
+}
+
+impl RecordStream {
+    fn open(path: &Path) -> Result<RecordStream> {
+        let src =
+            File::open(path).with_context(|| format!("Could not open `{}`", path.display()))?;
+        Ok(RecordStream {
+            recording: RecordingReader::open(src)?,
+            next: 0,
+            record: Cursor::new(Vec::new()),
+        })
+    }
+}
+
+impl Read for RecordStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let len = self.record.read(buf)?;
+            if len > 0 || buf.is_empty() || self.next == self.recording.len() {
+                return Ok(len);
+            }
+
+            let record = self
+                .recording
+                .get(self.next)
+                .map_err(|e| io::Error::other(format!("{:#}", e)))?;
+            self.record = Cursor::new(record.data);
+            self.next += 1;
+        }
+    }
+}
+
+/// Packet read from an input, not yet checked.
+pub struct RawPacket {
+    /// Name of the input: its path, or `-` for stdin.
+    pub input: String,
+    /// Offset of the packet in the (decoded) stream of the input.
+    pub offset: u64,
+    pub buf: Vec<u8>,
+}
+
+/// Packets of every input, one input after the other.
+pub struct RawPackets {
+    inputs: Vec<Option<PathBuf>>,
+    source: Source,
+    port: Option<u16>,
+    current: Option<(String, Box<dyn Read + Send>, u64)>,
+}
+
+impl RawPackets {
+    pub fn new(args: &InputArgs) -> RawPackets {
+        let inputs = if args.inputs.is_empty() {
+            vec![None]
+        } else {
+            args.inputs.iter().cloned().map(Some).collect()
+        };
+
+        RawPackets {
+            inputs: inputs.into_iter().rev().collect(),
+            source: args.source,
+            port: args.port,
+            current: None,
+        }
+    }
+}
+
+impl Iterator for RawPackets {
+    type Item = Result<RawPacket>;
+
+    fn next(&mut self) -> Option<Result<RawPacket>> {
+        loop {
+            let (input, stream, offset) = match &mut self.current {
+                Some(current) => current,
+                None => {
+                    let path = self.inputs.pop()?;
+                    let name = path
+                        .as_ref()
+                        .map_or("-".to_string(), |path| path.display().to_string());
+                    match open(path.as_deref(), self.source, self.port) {
+                        Ok(stream) => self.current = Some((name, stream, 0)),
+                        Err(e) => return Some(Err(e)),
+                    }
+                    continue;
+                }
+            };
+
+            // An error ends the input: the stream cannot be resynchronized
+            match read_raw(stream) {
+                Ok(Some(buf)) => {
+                    let pkt = RawPacket {
+                        input: input.clone(),
+                        offset: *offset,
+                        buf,
+                    };
+                    *offset += pkt.buf.len() as u64;
+                    return Some(Ok(pkt));
+                }
+                Ok(None) => self.current = None,
+                Err(e) => {
+                    let e = e.context(format!("`{}` at offset `{}`", input, offset));
+                    self.current = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Command line interface: one subcommand per tool, reading files or stdin and writing
+//! files or stdout.
+
+// Reachable modules
+mod commands;
+mod input;
+mod output;
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use space_packets::export::{ExportConfig, Format as TableFormat, Layout};
+use space_packets::protocol::Encoding;
+
+use input::Source;
+use output::Format;
+
+#[derive(Debug, Parser)]
+#[command(name = "space_packets", version, about = "CCSDS space packet tools")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Decodes packets, printing them.
+    Decode {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[arg(short, long, value_enum, default_value_t = Format::Pretty)]
+        format: Format,
+        /// Only the packets matching the filter expression.
+        #[arg(long)]
+        filter: Option<String>,
+        /// Decodes on a pool of workers, keeping the order of the packets.
+        #[arg(long)]
+        workers: Option<usize>,
+        /// Base64 user data in JSON (`user_data_base64`), instead of hex.
+        #[arg(long)]
+        base64: bool,
+    },
+    /// Encodes packets from their JSON (or NDJSON) or YAML description.
+    Encode {
+        /// Description of the packets: a list, or a single packet [default: stdin].
+        input: Option<PathBuf>,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// YAML description, instead of JSON (default for `.yaml` and `.yml` files).
+        #[arg(long)]
+        yaml: bool,
+    },
+    /// Keeps the packets matching the filter expression.
+    Filter {
+        /// E.g. "apid in 0x70..0x7F and type == tm and len > 100".
+        expr: String,
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Counts packets, bytes, invalid packets and sequence gaps per APID.
+    Stats {
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Splits the packets in one file per APID.
+    Split {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Directory of the APID files.
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Merges files ordered by secondary header time into one ordered stream.
+    Merge {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Exports the parameters of the packets, as defined, to one table per APID.
+    Export {
+        /// Parameter definitions: a YAML file, or a `.toml` file.
+        #[arg(short = 'D', long)]
+        definitions: PathBuf,
+        #[command(flatten)]
+        input: InputArgs,
+        /// Directory of the APID files.
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+        #[arg(long, value_enum, default_value_t = TableLayout::Packet)]
+        layout: TableLayout,
+        /// Apache Parquet files (if built with the `parquet` feature), instead of CSV.
+        #[arg(long)]
+        parquet: bool,
+    },
+    /// Reports truncated packets and invalid checksums, failing if there are any.
+    Validate {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Decodes packets received from the network.
+    Listen {
+        #[arg(value_enum)]
+        transport: input::Transport,
+        /// Address to bind or connect to, e.g. 127.0.0.1:4000.
+        addr: String,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[arg(short, long, value_enum, default_value_t = Format::Pretty)]
+        format: Format,
+    },
+    /// Records the packet stream of stdin, with arrival times.
+    Record { path: PathBuf },
+    /// Replays a recording, paced as when received.
+    Replay {
+        path: PathBuf,
+        /// Speed factor, or `max` for as fast as possible.
+        #[arg(long, default_value = "1")]
+        speed: String,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[arg(short, long, value_enum, default_value_t = Format::Raw)]
+        format: Format,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum TableLayout {
+    /// One row per packet, one column per parameter.
+    Packet,
+    /// One row per parameter sample.
+    Sample,
+}
+
+#[derive(Debug, Args)]
+struct InputArgs {
+    /// Input files, read one after the other [default: stdin].
+    inputs: Vec<PathBuf>,
+    /// Format of the input files.
+    #[arg(long = "from", value_enum, default_value_t = Source::Raw)]
+    source: Source,
+    /// UDP port carrying the packets in captures (all ports by default).
+    #[arg(long)]
+    port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+struct OutputArgs {
+    /// Output file [default: stdout].
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Command::Decode {
+            input,
+            output,
+            format,
+            filter,
+            workers,
+            base64,
+        } => {
+            let encoding = if base64 {
+                Encoding::Base64
+            } else {
+                Encoding::Hex
+            };
+            commands::decode(
+                &input,
+                &output,
+                format,
+                encoding,
+                filter.as_deref(),
+                workers,
+            )
+        }
+        Command::Encode {
+            input,
+            output,
+            yaml,
+        } => commands::encode(input.as_deref(), &output, yaml),
+        Command::Filter {
+            expr,
+            input,
+            output,
+        } => commands::filter(&expr, &input, &output),
+        Command::Stats { input, output } => commands::stats(&input, &output),
+        Command::Split { input, dir } => commands::split(&input, &dir),
+        Command::Merge { inputs, output } => commands::merge(&inputs, &output),
+        Command::Export {
+            definitions,
+            input,
+            dir,
+            layout,
+            parquet,
+        } => {
+            let config = ExportConfig {
+                layout: match layout {
+                    TableLayout::Packet => Layout::Packet,
+                    TableLayout::Sample => Layout::Sample,
+                },
+                format: if parquet {
+                    TableFormat::Parquet
+                } else {
+                    TableFormat::Csv
+                },
+                ..Default::default()
+            };
+            commands::export(&definitions, &input, &dir, config)
+        }
+        Command::Validate { input } => commands::validate(&input),
+        Command::Listen {
+            transport,
+            addr,
+            output,
+            format,
+        } => commands::listen(transport, &addr, &output, format),
+        Command::Record { path } => commands::record(&path),
+        Command::Replay {
+            path,
+            speed,
+            output,
+            format,
+        } => commands::replay(&path, &speed, &output, format),
+    }
+}
@@ -0,0 +1,106 @@
+//! Outputs of the commands: files or stdout, with decoded packets in various formats.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+
+use space_packets::io::NdjsonWriter;
+use space_packets::protocol::{Encoding, PktType};
+use space_packets::Packet;
+
+use super::OutputArgs;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Debug dump of every packet.
+    Pretty,
+    /// One JSON object per line (NDJSON).
+    Json,
+    /// One row per packet, with a header row.
+    Csv,
+    /// Packets one after the other.
+    Raw,
+}
+
+/// Output file, or stdout.
+pub fn open(args: &OutputArgs) -> Result<Box<dyn Write + Send>> {
+    let dst: Box<dyn Write + Send> = match &args.output {
+        Some(path) => Box::new(
+            File::create(path).with_context(|| format!("Could not create `{}`", path.display()))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+    Ok(Box::new(BufWriter::new(dst)))
+}
+
+const CSV_HEADER: &str = "version,type,apid,sequence_flags,sequence_counter,data_length,\
+                          time_week,time_ms,user_data,checksum";
+
+/// Writes the packets in the given format.
+pub struct Printer<W: Write> {
+    dst: W,
+    format: Format,
+    encoding: Encoding,
+    count: u64,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(dst: W, format: Format) -> Printer<W> {
+        Printer {
+            dst,
+            format,
+            encoding: Encoding::Hex,
+            count: 0,
+        }
+    }
+
+    /// Encoding of the user data field in JSON, hex by default.
+    pub fn with_encoding(self, encoding: Encoding) -> Printer<W> {
+        Printer { encoding, ..self }
+    }
+
+    pub fn print(&mut self, pkt: Packet) -> Result<()> {
+        match self.format {
+            Format::Pretty => writeln!(self.dst, "{:#?}", pkt)?,
+            Format::Json => NdjsonWriter::new(&mut self.dst, self.encoding).write(&pkt)?,
+            Format::Csv => {
+                if self.count == 0 {
+                    writeln!(self.dst, "{}", CSV_HEADER)?;
+                }
+                let header = &pkt.pri_header;
+                let (week, ms) = match &pkt.sec_header {
+                    Some(time) => (time.time_week.to_string(), time.time_ms.to_string()),
+                    None => (String::new(), String::new()),
+                };
+                let user_data = pkt.user_data.as_ref().map_or(&[][..], |field| &field.data);
+                writeln!(
+                    self.dst,
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    header.version_number,
+                    match header.packet_type {
+                        PktType::Telemetry => "tm",
+                        PktType::Telecommand => "tc",
+                    },
+                    header.apid,
+                    header.sequence_flags,
+                    header.sequence_counter,
+                    header.data_length,
+                    week,
+                    ms,
+                    Encoding::Hex.encode(user_data),
+                    pkt.checksum
+                )?;
+            }
+            Format::Raw => self.dst.write_all(&pkt.into_buffer())?,
+        }
+
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.dst.flush().context("Could not flush the output")
+    }
+}
@@ -0,0 +1,429 @@
+//! Definitions of the parameters in the user data field of the packets, per APID.
+//!
+//! Loaded from YAML or TOML files (`serde` feature), e.g.:
+//!
+//! ```yaml
+//! packets:
+//!   - apid: 0x73
+//!     name: housekeeping
+//!     parameters:
+//!       - {name: mode, offset: 32, length: 8, type: enum, labels: {OFF: 0, SAFE: 2}}
+//!       - {name: count, length: 8}
+//!       - {name: volts, length: 16, type: signed, endianness: little, repeat: count}
+//!       - {name: temp, length: 32, type: float, when: {parameter: mode, equals: 2}}
+//! ```
+//!
+//! Offsets and lengths are in bits. A parameter without offset follows the previous one.
+//! The type is unsigned by default, and multi-byte values are big-endian by default.
+
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+#[cfg(feature = "serde")]
+use std::fs;
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use anyhow::Context;
+use anyhow::{bail, Result};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+/// Type of a parameter, and its allowed lengths.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamType {
+    /// 1 to 64 bits.
+    Unsigned,
+    /// 2 to 64 bits, two's complement.
+    Signed,
+    /// 32 or 64 bits, IEEE 754.
+    Float,
+    /// 1 to 64 bits, true when non-zero.
+    Bool,
+    /// 1 to 64 bits, with the raw values of the labels.
+    Enum { labels: BTreeMap<String, u64> },
+    /// Whole bytes, ASCII or UTF-8, padded with NUL characters.
+    String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Endianness {
+    #[default]
+    Big,
+    /// Whole bytes only.
+    Little,
+}
+
+/// Number of values of a repeated parameter.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(untagged))]
+pub enum Repeat {
+    Count(usize),
+    /// Value of a previous (unsigned) parameter.
+    Parameter(String),
+}
+
+/// Presence of a parameter, depending on the raw value of a previous parameter.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(deny_unknown_fields))]
+pub struct Condition {
+    pub parameter: String,
+    pub equals: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(try_from = "ParameterRepr")
+)]
+pub struct Parameter {
+    pub name: String,
+    /// Offset in the user data field, in bits: after the previous parameter when not given.
+    pub offset: Option<usize>,
+    /// Length of a value, in bits.
+    pub length: usize,
+    pub param_type: ParamType,
+    pub endianness: Endianness,
+    /// Values one after the other, named `name[0]`, `name[1]`, ...
+    pub repeat: Option<Repeat>,
+    pub when: Option<Condition>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TypeName {
+    #[default]
+    Unsigned,
+    Signed,
+    Float,
+    Bool,
+    Enum,
+    String,
+}
+
+/// Parameter as written in the files: the type is a name, with the labels of enumerations.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParameterRepr {
+    name: String,
+    #[serde(default)]
+    offset: Option<usize>,
+    length: usize,
+    #[serde(default, rename = "type")]
+    type_name: TypeName,
+    #[serde(default)]
+    labels: Option<BTreeMap<String, u64>>,
+    #[serde(default)]
+    endianness: Endianness,
+    #[serde(default)]
+    repeat: Option<Repeat>,
+    #[serde(default)]
+    when: Option<Condition>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ParameterRepr> for Parameter {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: ParameterRepr) -> Result<Parameter> {
+        let param_type = match (repr.type_name, repr.labels) {
+            (TypeName::Enum, Some(labels)) => ParamType::Enum { labels },
+            (TypeName::Enum, None) => bail!("Enumeration `{}` without labels", repr.name),
+            (_, Some(_)) => bail!("Labels of `{}`, which is not an enumeration", repr.name),
+            (TypeName::Unsigned, None) => ParamType::Unsigned,
+            (TypeName::Signed, None) => ParamType::Signed,
+            (TypeName::Float, None) => ParamType::Float,
+            (TypeName::Bool, None) => ParamType::Bool,
+            (TypeName::String, None) => ParamType::String,
+        };
+        Ok(Parameter {
+            name: repr.name,
+            offset: repr.offset,
+            length: repr.length,
+            param_type,
+            endianness: repr.endianness,
+            repeat: repr.repeat,
+            when: repr.when,
+        })
+    }
+}
+
+/// Parameters of the packets of an APID, in order.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(deny_unknown_fields))]
+pub struct PacketDefinition {
+    pub apid: u16,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+}
+
+/// Packet definitions, keyed by APID.
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    packets: BTreeMap<u16, PacketDefinition>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DatabaseFile {
+    packets: Vec<PacketDefinition>,
+}
+
+impl Database {
+    /// Checks the definitions: unique APIDs and names, valid lengths, references to
+    /// previous parameters.
+    pub fn new(packets: Vec<PacketDefinition>) -> Result<Database> {
+        let mut database = Database::default();
+        for packet in packets {
+            let apid = packet.apid;
+            if let Err(e) = check(&packet) {
+                bail!("Invalid definition of APID `{:#05x}`: {:#}", apid, e);
+            }
+            if database.packets.insert(apid, packet).is_some() {
+                bail!("APID `{:#05x}` defined twice", apid);
+            }
+        }
+        Ok(database)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_yaml(yaml: &str) -> Result<Database> {
+        let file: DatabaseFile = serde_yaml::from_str(yaml).context("Invalid YAML definitions")?;
+        Database::new(file.packets)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_toml(toml: &str) -> Result<Database> {
+        let file: DatabaseFile = toml::from_str(toml).context("Invalid TOML definitions")?;
+        Database::new(file.packets)
+    }
+
+    /// Loads a `.toml` file, or a YAML file otherwise.
+    #[cfg(feature = "serde")]
+    pub fn load(path: &Path) -> Result<Database> {
+        let definitions = fs::read_to_string(path)
+            .with_context(|| format!("Could not read `{}`", path.display()))?;
+        let database = if path.extension().is_some_and(|ext| ext == "toml") {
+            Database::from_toml(&definitions)
+        } else {
+            Database::from_yaml(&definitions)
+        };
+        database.with_context(|| format!("Invalid definitions file `{}`", path.display()))
+    }
+
+    pub fn get(&self, apid: u16) -> Option<&PacketDefinition> {
+        self.packets.get(&apid)
+    }
+
+    pub fn packets(&self) -> impl Iterator<Item = &PacketDefinition> {
+        self.packets.values()
+    }
+}
+
+fn check(packet: &PacketDefinition) -> Result<()> {
+    let mut defined: HashMap<&str, &Parameter> = HashMap::new();
+    for param in &packet.parameters {
+        let length = param.length;
+        let valid = match param.param_type {
+            ParamType::Unsigned | ParamType::Bool | ParamType::Enum { .. } => {
+                (1..=64).contains(&length)
+            }
+            ParamType::Signed => (2..=64).contains(&length),
+            ParamType::Float => length == 32 || length == 64,
+            ParamType::String => length > 0 && length.is_multiple_of(8),
+        };
+        if !valid {
+            bail!("Parameter `{}` of invalid length `{}`", param.name, length);
+        }
+        if param.endianness == Endianness::Little && !length.is_multiple_of(8) {
+            bail!("Little-endian parameter `{}` of partial bytes", param.name);
+        }
+
+        let references = [
+            match &param.repeat {
+                Some(Repeat::Parameter(name)) => Some(name),
+                _ => None,
+            },
+            param.when.as_ref().map(|condition| &condition.parameter),
+        ];
+        for name in references.iter().flatten() {
+            if !defined.contains_key(name.as_str()) {
+                bail!(
+                    "Parameter `{}` refers to `{}`, not defined before",
+                    param.name,
+                    name
+                );
+            }
+        }
+        // The repeat count is the raw value of a single unsigned parameter
+        if let Some(Repeat::Parameter(name)) = &param.repeat {
+            let count = defined[name.as_str()];
+            if count.param_type != ParamType::Unsigned || count.repeat.is_some() {
+                bail!(
+                    "Parameter `{}` repeated by `{}`, which is not a single unsigned value",
+                    param.name,
+                    name
+                );
+            }
+        }
+
+        // The condition is on the raw value of a single parameter, which is not a string
+        if let Some(condition) = &param.when {
+            let tested = defined[condition.parameter.as_str()];
+            if tested.param_type == ParamType::String || tested.repeat.is_some() {
+                bail!(
+                    "Parameter `{}` present depending on `{}`, which has no single raw value",
+                    param.name,
+                    condition.parameter
+                );
+            }
+        }
+
+        if defined.insert(&param.name, param).is_some() {
+            bail!("Parameter `{}` defined twice", param.name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "serde")]
+    use crate::test_utils::TestResult;
+
+    fn parameter(name: &str, length: usize, param_type: ParamType) -> Parameter {
+        Parameter {
+            name: name.to_string(),
+            offset: None,
+            length,
+            param_type,
+            endianness: Endianness::Big,
+            repeat: None,
+            when: None,
+        }
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let packet = |parameters| PacketDefinition {
+            apid: 1,
+            name: String::new(),
+            parameters,
+        };
+        assert!(Database::new(vec![packet(vec![parameter("a", 8, ParamType::Float)])]).is_err());
+        assert!(Database::new(vec![packet(vec![parameter("a", 1, ParamType::Signed)])]).is_err());
+        assert!(Database::new(vec![packet(vec![parameter("a", 4, ParamType::String)])]).is_err());
+
+        let twice = vec![
+            parameter("a", 8, ParamType::Unsigned),
+            parameter("a", 8, ParamType::Unsigned),
+        ];
+        assert!(Database::new(vec![packet(twice)]).is_err());
+
+        let mut repeated = parameter("a", 8, ParamType::Unsigned);
+        repeated.repeat = Some(Repeat::Parameter("count".to_string()));
+        assert!(Database::new(vec![packet(vec![repeated.clone()])]).is_err());
+
+        // Repeat counts of a signed or repeated parameter
+        let signed = parameter("count", 8, ParamType::Signed);
+        assert!(Database::new(vec![packet(vec![signed, repeated.clone()])]).is_err());
+        let mut counts = parameter("count", 8, ParamType::Unsigned);
+        counts.repeat = Some(Repeat::Count(2));
+        assert!(Database::new(vec![packet(vec![counts, repeated.clone()])]).is_err());
+        let count = parameter("count", 8, ParamType::Unsigned);
+        assert!(Database::new(vec![packet(vec![count, repeated])]).is_ok());
+
+        // Conditions on a string or repeated parameter
+        let mut conditional = parameter("a", 8, ParamType::Unsigned);
+        conditional.when = Some(Condition {
+            parameter: "b".to_string(),
+            equals: 1,
+        });
+        let string = parameter("b", 8, ParamType::String);
+        assert!(Database::new(vec![packet(vec![string, conditional.clone()])]).is_err());
+        let mut values = parameter("b", 8, ParamType::Unsigned);
+        values.repeat = Some(Repeat::Count(2));
+        assert!(Database::new(vec![packet(vec![values, conditional.clone()])]).is_err());
+        let flag = parameter("b", 1, ParamType::Bool);
+        assert!(Database::new(vec![packet(vec![flag, conditional])]).is_ok());
+
+        let mut little = parameter("a", 12, ParamType::Unsigned);
+        little.endianness = Endianness::Little;
+        assert!(Database::new(vec![packet(vec![little])]).is_err());
+
+        assert!(Database::new(vec![packet(vec![]), packet(vec![])]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn definitions_files() -> TestResult {
+        let yaml = "
+packets:
+  - apid: 0x73
+    parameters:
+      - {name: mode, offset: 32, length: 8, type: enum, labels: {OFF: 0, SAFE: 2}}
+      - {name: count, length: 8}
+      - {name: volts, length: 16, type: signed, endianness: little, repeat: count}
+      - {name: temp, length: 32, type: float, when: {parameter: mode, equals: 2}}
+";
+        let toml = r#"
+[[packets]]
+apid = 0x73
+
+[[packets.parameters]]
+name = "mode"
+offset = 32
+length = 8
+type = "enum"
+labels = { OFF = 0, SAFE = 2 }
+
+[[packets.parameters]]
+name = "count"
+length = 8
+
+[[packets.parameters]]
+name = "volts"
+length = 16
+type = "signed"
+endianness = "little"
+repeat = "count"
+
+[[packets.parameters]]
+name = "temp"
+length = 32
+type = "float"
+when = { parameter = "mode", equals = 2 }
+"#;
+        let from_yaml = Database::from_yaml(yaml)?;
+        let from_toml = Database::from_toml(toml)?;
+        assert_eq!(from_yaml.get(0x73), from_toml.get(0x73));
+
+        let params = &from_yaml.get(0x73).unwrap().parameters;
+        assert_eq!(params[0].offset, Some(32));
+        assert_eq!(params[1].param_type, ParamType::Unsigned);
+        assert_eq!(
+            params[2].repeat,
+            Some(Repeat::Parameter("count".to_string()))
+        );
+        assert_eq!(params[2].endianness, Endianness::Little);
+
+        assert!(Database::from_yaml("packets: [{apid: 1, parameters: [{name: a}]}]").is_err());
+        let labels = "packets: [{apid: 1, parameters: [{name: a, length: 1, labels: {}}]}]";
+        assert!(Database::from_yaml(labels).is_err());
+
+        Ok(())
+    }
+}
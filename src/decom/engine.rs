@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+
+use crate::protocol::Packet;
+
+use super::database::{Database, Endianness, ParamType, Parameter, Repeat};
+use super::sample::{Sample, Value};
+
+/// Turns the user data field of packets into samples, as defined in the database.
+pub struct Decommutator {
+    database: Database,
+}
+
+impl Decommutator {
+    pub fn new(database: Database) -> Decommutator {
+        Decommutator { database }
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    /// Samples of the packet, in order of definition, or `None` if its APID is not defined.
+    pub fn decommutate(&self, pkt: &Packet) -> Result<Option<Vec<Sample>>> {
+        let apid = pkt.pri_header.apid;
+        let definition = match self.database.get(apid) {
+            Some(definition) => definition,
+            None => return Ok(None),
+        };
+        let data = pkt.user_data.as_ref().map_or(&[][..], |field| &field.data);
+
+        let mut samples = Vec::new();
+        // Raw values of the parameters decoded, for conditions and repeat counts
+        let mut raws: HashMap<&str, u64> = HashMap::new();
+        let mut position = 0;
+        for param in &definition.parameters {
+            if let Some(condition) = &param.when {
+                if raws.get(condition.parameter.as_str()) != Some(&condition.equals) {
+                    continue;
+                }
+            }
+
+            let offset = param.offset.unwrap_or(position);
+            let count = match &param.repeat {
+                None => 1,
+                Some(Repeat::Count(count)) => *count,
+                Some(Repeat::Parameter(name)) => match raws.get(name.as_str()) {
+                    Some(count) => *count as usize,
+                    None => bail!("Repeat count `{}` of `{}` not decoded", name, param.name),
+                },
+            };
+
+            for idx in 0..count {
+                let start = offset + idx * param.length;
+                let (value, raw) = decode(data, start, param).with_context(|| {
+                    format!("Invalid parameter `{}` of APID `{:#05x}`", param.name, apid)
+                })?;
+                let name = match param.repeat {
+                    Some(_) => format!("{}[{}]", param.name, idx),
+                    None => {
+                        if let Some(raw) = raw {
+                            raws.insert(&param.name, raw);
+                        }
+                        param.name.clone()
+                    }
+                };
+                samples.push(Sample { name, value });
+            }
+            position = offset + count * param.length;
+        }
+        Ok(Some(samples))
+    }
+}
+
+/// Value of the parameter at the given bit, with its raw value if it is not a string.
+fn decode(data: &[u8], start: usize, param: &Parameter) -> Result<(Value, Option<u64>)> {
+    let length = param.length;
+    if let ParamType::String = param.param_type {
+        let mut bytes = (0..length / 8)
+            .map(|idx| bits(data, start + idx * 8, 8).map(|byte| byte as u8))
+            .collect::<Result<Vec<_>>>()?;
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        let value = String::from_utf8_lossy(&bytes).into_owned();
+        return Ok((Value::String(value), None));
+    }
+
+    let mut raw = bits(data, start, length)?;
+    if param.endianness == Endianness::Little {
+        raw = raw.swap_bytes() >> (64 - length);
+    }
+    let value = match &param.param_type {
+        ParamType::Unsigned => Value::Unsigned(raw),
+        ParamType::Signed => Value::Signed(((raw << (64 - length)) as i64) >> (64 - length)),
+        ParamType::Float if length == 32 => Value::Float(f32::from_bits(raw as u32) as f64),
+        ParamType::Float => Value::Float(f64::from_bits(raw)),
+        ParamType::Bool => Value::Bool(raw != 0),
+        ParamType::Enum { labels } => Value::Enum {
+            raw,
+            label: labels
+                .iter()
+                .find(|(_, value)| **value == raw)
+                .map(|(label, _)| label.clone()),
+        },
+        ParamType::String => unreachable!(),
+    };
+    Ok((value, Some(raw)))
+}
+
+/// Big-endian value of the bits `start..start + length` (at most 64) of the data.
+fn bits(data: &[u8], start: usize, length: usize) -> Result<u64> {
+    let end = start + length;
+    if end > data.len() * 8 {
+        bail!(
+            "Bits `{}..{}` beyond the user data field of `{}` bits",
+            start,
+            end,
+            data.len() * 8
+        );
+    }
+
+    let bytes = &data[start / 8..end.div_ceil(8)];
+    let acc = bytes
+        .iter()
+        .fold(0u128, |acc, byte| acc << 8 | *byte as u128);
+    let trailing = bytes.len() * 8 - (start % 8) - length;
+    Ok(((acc >> trailing) & ((1u128 << length) - 1)) as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::protocol::PktType;
+    use crate::test_utils::{PacketBuilder, TestResult};
+
+    use super::super::database::{Condition, PacketDefinition};
+
+    fn packet(apid: u16, data: &[u8]) -> Packet {
+        PacketBuilder::new(PktType::Telemetry, apid)
+            .data(data)
+            .build()
+    }
+
+    fn parameter(name: &str, length: usize, param_type: ParamType) -> Parameter {
+        Parameter {
+            name: name.to_string(),
+            offset: None,
+            length,
+            param_type,
+            endianness: Endianness::Big,
+            repeat: None,
+            when: None,
+        }
+    }
+
+    #[test]
+    fn bit_fields() -> TestResult {
+        let data = [0b1010_1100, 0b0101_0011, 0xFF];
+        assert_eq!(bits(&data, 0, 1)?, 1);
+        assert_eq!(bits(&data, 2, 4)?, 0b1011);
+        assert_eq!(bits(&data, 6, 6)?, 0b00_0101);
+        assert_eq!(bits(&data, 0, 24)?, 0xAC53FF);
+        assert_eq!(bits(&[0xFF; 9], 4, 64)?, u64::MAX);
+        assert!(bits(&data, 20, 5).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn decommutation() -> TestResult {
+        let mut labels = std::collections::BTreeMap::new();
+        labels.insert("OFF".to_string(), 0);
+        labels.insert("SAFE".to_string(), 2);
+
+        let mut mode = parameter("mode", 4, ParamType::Enum { labels });
+        mode.offset = Some(8);
+        let flag = parameter("flag", 1, ParamType::Bool);
+        let delta = parameter("delta", 3, ParamType::Signed);
+        let count = parameter("count", 8, ParamType::Unsigned);
+        let mut volts = parameter("volts", 16, ParamType::Unsigned);
+        volts.endianness = Endianness::Little;
+        volts.repeat = Some(Repeat::Parameter("count".to_string()));
+        let mut temp = parameter("temp", 32, ParamType::Float);
+        temp.when = Some(Condition {
+            parameter: "mode".to_string(),
+            equals: 2,
+        });
+        let name = parameter("name", 32, ParamType::String);
+
+        let database = Database::new(vec![PacketDefinition {
+            apid: 0x73,
+            name: "housekeeping".to_string(),
+            parameters: vec![mode, flag, delta, count, volts, temp, name],
+        }])?;
+        let decommutator = Decommutator::new(database);
+
+        let sample = |name: &str, value| Sample::new(name, value);
+        let safe = Value::Enum {
+            raw: 2,
+            label: Some("SAFE".to_string()),
+        };
+        let mut data = vec![0xEE, 0x2E, 0x02, 0x34, 0x12, 0x78, 0x56];
+        data.extend_from_slice(&1.5f32.to_be_bytes());
+        data.extend_from_slice(b"HK\0\0");
+        let samples = decommutator.decommutate(&packet(0x73, &data))?.unwrap();
+        assert_eq!(
+            samples,
+            [
+                sample("mode", safe),
+                sample("flag", Value::Bool(true)),
+                sample("delta", Value::Signed(-2)),
+                sample("count", Value::Unsigned(2)),
+                sample("volts[0]", Value::Unsigned(0x1234)),
+                sample("volts[1]", Value::Unsigned(0x5678)),
+                sample("temp", Value::Float(1.5)),
+                sample("name", Value::String("HK".to_string())),
+            ]
+        );
+
+        // Absent parameter, and unknown label
+        let data = [0xEE, 0x50, 0x00, b'H', b'K', 0, 0];
+        let samples = decommutator.decommutate(&packet(0x73, &data))?.unwrap();
+        let off = Value::Enum {
+            raw: 5,
+            label: None,
+        };
+        assert_eq!(samples[0], sample("mode", off));
+        assert_eq!(samples.len(), 5);
+        assert_eq!(samples[4], sample("name", Value::String("HK".to_string())));
+
+        // Truncated packet, and unknown APID
+        assert!(decommutator.decommutate(&packet(0x73, &data[..5])).is_err());
+        assert!(decommutator.decommutate(&packet(0x10, &data))?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+//! Decommutation of the user data field of packets into named parameter samples.
+
+// Reachable modules
+pub mod database;
+mod engine;
+mod sample;
+
+// Re-exporting
+pub use database::{Database, PacketDefinition, Parameter};
+pub use engine::Decommutator;
+pub use sample::{Sample, Value};
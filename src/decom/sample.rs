@@ -0,0 +1,48 @@
+use std::fmt;
+
+/// Value of a parameter, typed as defined.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    /// Raw value of an enumeration, with its label if it has one.
+    Enum {
+        raw: u64,
+        label: Option<String>,
+    },
+    String(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Unsigned(value) => write!(f, "{}", value),
+            Value::Signed(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Enum {
+                label: Some(label), ..
+            } => write!(f, "{}", label),
+            Value::Enum { raw, label: None } => write!(f, "{}", raw),
+            Value::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Value of a parameter in a packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub value: Value,
+}
+
+impl Sample {
+    pub fn new(name: &str, value: Value) -> Sample {
+        Sample {
+            name: name.to_string(),
+            value,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+use super::table::{Cell, Sink, Table};
+
+/// Writes the rows as CSV (RFC 4180), with a header row. Timestamps are ISO 8601, in UTC.
+pub struct CsvSink<W> {
+    writer: W,
+    header: bool,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(dst: W) -> CsvSink<W> {
+        CsvSink {
+            writer: dst,
+            header: false,
+        }
+    }
+}
+
+impl<W: Write> Sink for CsvSink<W> {
+    fn write(&mut self, table: &Table) -> Result<()> {
+        if !self.header {
+            let names: Vec<_> = table.columns.iter().map(|c| escape(&c.name)).collect();
+            writeln!(self.writer, "{}", names.join(",")).context("Could not write the header")?;
+            self.header = true;
+        }
+
+        for row in 0..table.rows() {
+            let cells: Vec<_> = table
+                .columns
+                .iter()
+                .map(|column| column.cells[row].as_ref().map_or(String::new(), format))
+                .collect();
+            writeln!(self.writer, "{}", cells.join(",")).context("Could not write a row")?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush().context("Could not flush the rows")
+    }
+}
+
+fn format(cell: &Cell) -> String {
+    match cell {
+        Cell::Bool(value) => value.to_string(),
+        Cell::Unsigned(value) => value.to_string(),
+        Cell::Signed(value) => value.to_string(),
+        Cell::Float(value) => value.to_string(),
+        Cell::Text(value) => escape(value),
+        Cell::Timestamp(micros) => iso_8601(*micros),
+    }
+}
+
+/// Quotes the field if it contains a separator, a quote or a line break.
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Date and time of the microseconds since the Unix epoch, e.g. `2024-02-29T12:00:00.000000Z`.
+fn iso_8601(micros: i64) -> String {
+    let secs = micros.div_euclid(1_000_000);
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+
+    // Civil date of the days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        micros.rem_euclid(1_000_000)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::export::table::ColumnType;
+    use crate::test_utils::TestResult;
+
+    #[test]
+    fn csv_rows() -> TestResult {
+        let mut table = Table::new();
+        table.set("time", ColumnType::Timestamp, Some(Cell::Timestamp(0)))?;
+        table.set(
+            "mode",
+            ColumnType::Text,
+            Some(Cell::Text("a, \"b\"".into())),
+        )?;
+        table.end_row();
+        let leap_day = 1_709_208_000_123_456; // 2024-02-29T12:00:00.123456Z
+        table.set(
+            "time",
+            ColumnType::Timestamp,
+            Some(Cell::Timestamp(leap_day)),
+        )?;
+        table.set("volts", ColumnType::Float, Some(Cell::Float(1.5)))?;
+        table.end_row();
+
+        let mut sink = CsvSink::new(Vec::new());
+        sink.write(&table)?;
+        assert_eq!(
+            String::from_utf8(sink.writer)?,
+            "time,mode,volts\n\
+             1970-01-01T00:00:00.000000Z,\"a, \"\"b\"\"\",\n\
+             2024-02-29T12:00:00.123456Z,,1.5\n"
+        );
+        assert_eq!(iso_8601(-1), "1969-12-31T23:59:59.999999Z");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use log::info;
+
+use crate::decom::database::{ParamType, Repeat};
+use crate::decom::{Database, PacketDefinition, Sample, Value};
+use crate::io::reader::DATA_MAX_SIZE;
+use crate::protocol::Packet;
+
+use super::csv::CsvSink;
+#[cfg(feature = "parquet")]
+use super::parquet::ParquetSink;
+use super::table::{Cell, ColumnType, Sink, Table};
+
+/// Epoch of GPS time (1980-01-06), since the Unix epoch. Leap seconds are not counted.
+pub const GPS_EPOCH: Duration = Duration::from_secs(315_964_800);
+
+/// Rows buffered before being written.
+const BATCH_ROWS: usize = 65_536;
+
+/// Columns of the parameters of an APID in the packet layout, at most.
+const MAX_PARAMETER_COLUMNS: usize = 1024;
+
+/// Columns of the packet, which parameters cannot be named after in the packet layout.
+pub const PACKET_COLUMNS: [&str; 5] = [
+    "apid",
+    "sequence_counter",
+    "time_week",
+    "time_ms",
+    "timestamp",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// One row per packet, with one column per parameter of the definition of the APID
+    /// (empty when absent from the packet). A parameter repeated by another one has as many
+    /// columns as the largest count.
+    Packet,
+    /// One row per sample, with the parameter name, its numeric value (`value`, booleans
+    /// as 0 or 1) and its text (`text`, for strings and enumeration labels).
+    Sample,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    /// Apache Parquet (`parquet` feature).
+    Parquet,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportConfig {
+    pub layout: Layout,
+    pub format: Format,
+    /// Epoch of the time of the secondary headers, since the Unix epoch.
+    pub epoch: Duration,
+}
+
+impl Default for ExportConfig {
+    fn default() -> ExportConfig {
+        ExportConfig {
+            layout: Layout::Packet,
+            format: Format::Csv,
+            epoch: GPS_EPOCH,
+        }
+    }
+}
+
+struct Output {
+    table: Table,
+    sink: Box<dyn Sink>,
+}
+
+/// Exports the samples of packets to one file per APID, e.g. `apid-0x073.csv`.
+///
+/// Every row starts with the `apid`, `sequence_counter`, `time_week` and `time_ms` columns
+/// and the `timestamp` of the secondary header (empty without secondary header).
+pub struct Exporter {
+    dir: PathBuf,
+    config: ExportConfig,
+    /// Definitions of the packets, giving the columns of the packet layout.
+    database: Database,
+    outputs: BTreeMap<u16, Output>,
+}
+
+impl Exporter {
+    /// Creates the directory of the files, if needed.
+    pub fn new(dir: &Path, config: ExportConfig, database: Database) -> Result<Exporter> {
+        if config.format == Format::Parquet && cfg!(not(feature = "parquet")) {
+            bail!("Parquet exports require the `parquet` feature");
+        }
+        fs::create_dir_all(dir).with_context(|| format!("Could not create `{}`", dir.display()))?;
+
+        Ok(Exporter {
+            dir: dir.to_path_buf(),
+            config,
+            database,
+            outputs: BTreeMap::new(),
+        })
+    }
+
+    pub fn path(&self, apid: u16) -> PathBuf {
+        let extension = match self.config.format {
+            Format::Csv => "csv",
+            Format::Parquet => "parquet",
+        };
+        self.dir.join(format!("apid-{:#05x}.{}", apid, extension))
+    }
+
+    pub fn write(&mut self, pkt: &Packet, samples: &[Sample]) -> Result<()> {
+        let apid = pkt.pri_header.apid;
+        if !self.outputs.contains_key(&apid) {
+            let table = match self.config.layout {
+                Layout::Packet => {
+                    let definition = self
+                        .database
+                        .get(apid)
+                        .with_context(|| format!("APID `{:#05x}` not defined", apid))?;
+                    let columns = packet_columns(definition)
+                        .with_context(|| format!("Invalid columns of APID `{:#05x}`", apid))?;
+                    Table::with_columns(columns)?
+                }
+                Layout::Sample => Table::new(),
+            };
+            let path = self.path(apid);
+            let dst = BufWriter::new(
+                File::create(&path)
+                    .with_context(|| format!("Could not create `{}`", path.display()))?,
+            );
+            let sink: Box<dyn Sink> = match self.config.format {
+                Format::Csv => Box::new(CsvSink::new(dst)),
+                #[cfg(feature = "parquet")]
+                Format::Parquet => Box::new(ParquetSink::new(dst)),
+                #[cfg(not(feature = "parquet"))]
+                Format::Parquet => unreachable!(),
+            };
+            self.outputs.insert(apid, Output { table, sink });
+        }
+
+        let epoch = self.config.epoch;
+        let output = self.outputs.get_mut(&apid).unwrap();
+        let table = &mut output.table;
+        match self.config.layout {
+            Layout::Packet => {
+                table
+                    .row(|table| {
+                        set_packet(table, pkt, epoch)?;
+                        for sample in samples {
+                            let (column_type, cell) = cell(&sample.value);
+                            table.set(&sample.name, column_type, Some(cell))?;
+                        }
+                        Ok(())
+                    })
+                    .with_context(|| format!("Invalid sample for APID `{:#05x}`", apid))?;
+            }
+            Layout::Sample => {
+                for sample in samples {
+                    let to_text = |text: &str| Some(Cell::Text(text.to_string()));
+                    let (value, text) = match &sample.value {
+                        Value::Bool(value) => (Some(*value as u8 as f64), None),
+                        Value::Unsigned(value) => (Some(*value as f64), None),
+                        Value::Signed(value) => (Some(*value as f64), None),
+                        Value::Float(value) => (Some(*value), None),
+                        Value::Enum { raw, label } => {
+                            (Some(*raw as f64), label.as_deref().and_then(to_text))
+                        }
+                        Value::String(value) => (None, to_text(value)),
+                    };
+                    table.row(|table| {
+                        set_packet(table, pkt, epoch)?;
+                        table.set("parameter", ColumnType::Text, to_text(&sample.name))?;
+                        table.set("value", ColumnType::Float, value.map(Cell::Float))?;
+                        table.set("text", ColumnType::Text, text)
+                    })?;
+                }
+            }
+        }
+        if table.rows() >= BATCH_ROWS {
+            output.sink.write(table)?;
+            table.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining rows, returning the paths of the files.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for (apid, mut output) in std::mem::take(&mut self.outputs) {
+            let path = self.path(apid);
+            if output.table.rows() > 0 {
+                output.sink.write(&output.table)?;
+            }
+            output
+                .sink
+                .finish()
+                .with_context(|| format!("Could not write `{}`", path.display()))?;
+            info!("Exported `{}`", path.display());
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// Columns of the packet, first of every row.
+fn set_packet(table: &mut Table, pkt: &Packet, epoch: Duration) -> Result<()> {
+    let header = &pkt.pri_header;
+    table.set(
+        "apid",
+        ColumnType::Unsigned,
+        Some(Cell::Unsigned(header.apid as u64)),
+    )?;
+    table.set(
+        "sequence_counter",
+        ColumnType::Unsigned,
+        Some(Cell::Unsigned(header.sequence_counter as u64)),
+    )?;
+
+    let time = pkt.sec_header.as_ref();
+    table.set(
+        "time_week",
+        ColumnType::Unsigned,
+        time.map(|time| Cell::Unsigned(time.time_week as u64)),
+    )?;
+    table.set(
+        "time_ms",
+        ColumnType::Unsigned,
+        time.map(|time| Cell::Unsigned(time.time_ms as u64)),
+    )?;
+    table.set(
+        "timestamp",
+        ColumnType::Timestamp,
+        time.map(|time| Cell::Timestamp((epoch + time.time()).as_micros() as i64)),
+    )
+}
+
+/// Columns of the packet layout: the columns of the packet, then the parameters in order.
+fn packet_columns(definition: &PacketDefinition) -> Result<Vec<(String, ColumnType)>> {
+    let mut columns = vec![
+        ("apid".to_string(), ColumnType::Unsigned),
+        ("sequence_counter".to_string(), ColumnType::Unsigned),
+        ("time_week".to_string(), ColumnType::Unsigned),
+        ("time_ms".to_string(), ColumnType::Unsigned),
+        ("timestamp".to_string(), ColumnType::Timestamp),
+    ];
+    for param in &definition.parameters {
+        if PACKET_COLUMNS.contains(&param.name.as_str()) {
+            bail!("Parameter `{}` named as a packet column", param.name);
+        }
+        let column_type = match param.param_type {
+            ParamType::Unsigned => ColumnType::Unsigned,
+            ParamType::Signed => ColumnType::Signed,
+            ParamType::Float => ColumnType::Float,
+            ParamType::Bool => ColumnType::Bool,
+            ParamType::Enum { .. } | ParamType::String => ColumnType::Text,
+        };
+        let count = match &param.repeat {
+            None => 1,
+            Some(Repeat::Count(count)) => *count,
+            // Largest raw value of the count, within the largest data field
+            Some(Repeat::Parameter(name)) => {
+                let length = definition
+                    .parameters
+                    .iter()
+                    .find(|count| &count.name == name)
+                    .map_or(64, |count| count.length);
+                let largest = u64::MAX >> (64 - length.min(64));
+                largest.min((DATA_MAX_SIZE * 8 / param.length) as u64) as usize
+            }
+        };
+        if columns.len() + count > PACKET_COLUMNS.len() + MAX_PARAMETER_COLUMNS {
+            bail!(
+                "More than {} parameter columns, use the sample layout",
+                MAX_PARAMETER_COLUMNS
+            );
+        }
+        match param.repeat {
+            None => columns.push((param.name.clone(), column_type)),
+            Some(_) => {
+                for idx in 0..count {
+                    columns.push((format!("{}[{}]", param.name, idx), column_type));
+                }
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Cell of the value in the packet layout: enumerations are exported as their label.
+fn cell(value: &Value) -> (ColumnType, Cell) {
+    match value {
+        Value::Bool(value) => (ColumnType::Bool, Cell::Bool(*value)),
+        Value::Unsigned(value) => (ColumnType::Unsigned, Cell::Unsigned(*value)),
+        Value::Signed(value) => (ColumnType::Signed, Cell::Signed(*value)),
+        Value::Float(value) => (ColumnType::Float, Cell::Float(*value)),
+        value @ Value::Enum { .. } => (ColumnType::Text, Cell::Text(value.to_string())),
+        Value::String(value) => (ColumnType::Text, Cell::Text(value.clone())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::decom::database::Endianness;
+    use crate::decom::Parameter;
+    use crate::protocol::PktType;
+    use crate::test_utils::{PacketBuilder, TestResult};
+
+    /// Parameters of 8 bits (32 for floats), one after the other.
+    fn definition(apid: u16, parameters: &[(&str, ParamType, Option<Repeat>)]) -> PacketDefinition {
+        let parameters = parameters
+            .iter()
+            .map(|(name, param_type, repeat)| Parameter {
+                name: name.to_string(),
+                offset: None,
+                length: if *param_type == ParamType::Float {
+                    32
+                } else {
+                    8
+                },
+                param_type: param_type.clone(),
+                endianness: Endianness::Big,
+                repeat: repeat.clone(),
+                when: None,
+            })
+            .collect();
+        PacketDefinition {
+            apid,
+            name: String::new(),
+            parameters,
+        }
+    }
+
+    /// APID 0x73 of `volts` and `mode`, APID 0x10 without parameters.
+    fn database() -> Result<Database> {
+        let mode = ParamType::Enum {
+            labels: vec![("SAFE".to_string(), 2)].into_iter().collect(),
+        };
+        Database::new(vec![
+            definition(
+                0x73,
+                &[("volts", ParamType::Float, None), ("mode", mode, None)],
+            ),
+            definition(0x10, &[]),
+        ])
+    }
+
+    /// Housekeeping report (service 3, subservice 25).
+    fn packet(apid: u16, seq: u16, time_ms: Option<u32>) -> Packet {
+        let builder = PacketBuilder::new(PktType::Telemetry, apid)
+            .sequence_counter(seq)
+            .data(&[3, 25]);
+        match time_ms {
+            Some(time_ms) => builder.time(2000, time_ms),
+            None => builder,
+        }
+        .build()
+    }
+
+    #[test]
+    fn per_apid_files() -> TestResult {
+        let dir = std::env::temp_dir().join(format!("export-{}", std::process::id()));
+        let samples = [
+            Sample::new("volts", Value::Float(28.5)),
+            Sample::new(
+                "mode",
+                Value::Enum {
+                    raw: 2,
+                    label: Some("SAFE".to_string()),
+                },
+            ),
+        ];
+
+        // One row per packet
+        let mut exporter = Exporter::new(&dir, ExportConfig::default(), database()?)?;
+        exporter.write(&packet(0x73, 1, Some(1500)), &samples)?;
+        exporter.write(&packet(0x73, 2, None), &samples[1..])?;
+        exporter.write(&packet(0x10, 3, None), &[])?;
+        let paths = exporter.finish()?;
+        assert_eq!(
+            paths,
+            [dir.join("apid-0x010.csv"), dir.join("apid-0x073.csv")]
+        );
+        assert_eq!(
+            fs::read_to_string(&paths[1])?,
+            "apid,sequence_counter,time_week,time_ms,timestamp,volts,mode\n\
+             115,1,2000,1500,2018-05-06T00:00:01.500000Z,28.5,SAFE\n\
+             115,2,,,,,SAFE\n"
+        );
+
+        // One row per sample
+        let config = ExportConfig {
+            layout: Layout::Sample,
+            ..Default::default()
+        };
+        let mut exporter = Exporter::new(&dir, config, database()?)?;
+        exporter.write(&packet(0x73, 1, None), &samples)?;
+        let paths = exporter.finish()?;
+        assert_eq!(
+            fs::read_to_string(&paths[0])?,
+            "apid,sequence_counter,time_week,time_ms,timestamp,parameter,value,text\n\
+             115,1,,,,volts,28.5,\n\
+             115,1,,,,mode,2,SAFE\n"
+        );
+
+        // Parameters changing type
+        let mut exporter = Exporter::new(&dir, ExportConfig::default(), database()?)?;
+        exporter.write(&packet(0x73, 1, None), &samples)?;
+        let changed = [
+            Sample::new("mode", Value::Unsigned(2)),
+            Sample::new("volts", Value::Unsigned(28)),
+        ];
+        assert!(exporter.write(&packet(0x73, 2, None), &changed).is_err());
+        // Not defined
+        let undefined = [Sample::new("amps", Value::Float(1.5))];
+        assert!(exporter.write(&packet(0x73, 3, None), &undefined).is_err());
+        assert!(exporter.write(&packet(0x20, 3, None), &[]).is_err());
+        // Nothing left of the rejected rows
+        exporter.write(&packet(0x73, 4, None), &samples[..1])?;
+        let paths = exporter.finish()?;
+        assert_eq!(
+            fs::read_to_string(&paths[0])?,
+            "apid,sequence_counter,time_week,time_ms,timestamp,volts,mode\n\
+             115,1,,,,28.5,SAFE\n\
+             115,4,,,,28.5,\n"
+        );
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn packet_columns_of_definitions() -> TestResult {
+        let repeated = definition(
+            1,
+            &[
+                ("count", ParamType::Unsigned, None),
+                ("fixed", ParamType::Bool, Some(Repeat::Count(2))),
+                (
+                    "values",
+                    ParamType::Signed,
+                    Some(Repeat::Parameter("count".into())),
+                ),
+            ],
+        );
+        let columns = packet_columns(&repeated)?;
+        let names: Vec<_> = columns[PACKET_COLUMNS.len()..]
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names[..4], ["count", "fixed[0]", "fixed[1]", "values[0]"]);
+        assert_eq!(names.len(), 3 + 255);
+        assert_eq!(
+            columns.last(),
+            Some(&("values[254]".to_string(), ColumnType::Signed))
+        );
+
+        let named = definition(1, &[("apid", ParamType::Unsigned, None)]);
+        assert!(packet_columns(&named).is_err());
+        let wide = definition(1, &[("values", ParamType::Bool, Some(Repeat::Count(1025)))]);
+        assert!(packet_columns(&wide).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+//! Tabular exports of decommutated parameters, to CSV or Apache Parquet files.
+
+// Reachable modules
+mod csv;
+pub mod exporter;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod table;
+
+// Re-exporting
+pub use exporter::{ExportConfig, Exporter, Format, Layout, GPS_EPOCH};
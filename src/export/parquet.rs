@@ -0,0 +1,160 @@
+use std::io::Write;
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray,
+    TimestampMicrosecondArray, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use super::table::{Cell, Column, ColumnType, Sink, Table};
+
+/// Writes the rows as Apache Parquet, one row group per write, Snappy-compressed. All the
+/// columns are nullable; timestamps are in microseconds, UTC.
+pub struct ParquetSink<W: Write + Send> {
+    dst: Option<W>,
+    writer: Option<ArrowWriter<W>>,
+}
+
+impl<W: Write + Send> ParquetSink<W> {
+    pub fn new(dst: W) -> ParquetSink<W> {
+        ParquetSink {
+            dst: Some(dst),
+            writer: None,
+        }
+    }
+}
+
+impl<W: Write + Send> Sink for ParquetSink<W> {
+    fn write(&mut self, table: &Table) -> Result<()> {
+        let schema = Arc::new(Schema::new(
+            table
+                .columns
+                .iter()
+                .map(|column| Field::new(&column.name, data_type(column.column_type), true))
+                .collect::<Vec<_>>(),
+        ));
+        let arrays = table.columns.iter().map(array).collect();
+        let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+        // The schema is the one of the first rows
+        if let Some(dst) = self.dst.take() {
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            self.writer = Some(ArrowWriter::try_new(dst, schema, Some(properties))?);
+        }
+        let writer = self.writer.as_mut().unwrap();
+        writer.write(&batch).context("Could not write the rows")?;
+        writer.flush().context("Could not write the row group")
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer
+                .close()
+                .context("Could not write the Parquet footer")?;
+        }
+        Ok(())
+    }
+}
+
+fn data_type(column_type: ColumnType) -> DataType {
+    match column_type {
+        ColumnType::Bool => DataType::Boolean,
+        ColumnType::Unsigned => DataType::UInt64,
+        ColumnType::Signed => DataType::Int64,
+        ColumnType::Float => DataType::Float64,
+        ColumnType::Text => DataType::Utf8,
+        ColumnType::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+    }
+}
+
+fn array(column: &Column) -> ArrayRef {
+    let cells = column.cells.iter();
+    match column.column_type {
+        ColumnType::Bool => Arc::new(BooleanArray::from_iter(cells.map(|cell| match cell {
+            Some(Cell::Bool(value)) => Some(*value),
+            _ => None,
+        }))),
+        ColumnType::Unsigned => Arc::new(UInt64Array::from_iter(cells.map(|cell| match cell {
+            Some(Cell::Unsigned(value)) => Some(*value),
+            _ => None,
+        }))),
+        ColumnType::Signed => Arc::new(Int64Array::from_iter(cells.map(|cell| match cell {
+            Some(Cell::Signed(value)) => Some(*value),
+            _ => None,
+        }))),
+        ColumnType::Float => Arc::new(Float64Array::from_iter(cells.map(|cell| match cell {
+            Some(Cell::Float(value)) => Some(*value),
+            _ => None,
+        }))),
+        ColumnType::Text => Arc::new(StringArray::from_iter(cells.map(|cell| match cell {
+            Some(Cell::Text(value)) => Some(value.as_str()),
+            _ => None,
+        }))),
+        ColumnType::Timestamp => Arc::new(
+            TimestampMicrosecondArray::from_iter(cells.map(|cell| match cell {
+                Some(Cell::Timestamp(value)) => Some(*value),
+                _ => None,
+            }))
+            .with_timezone("UTC"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs::{self, File};
+
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::test_utils::TestResult;
+
+    #[test]
+    fn typed_columns() -> TestResult {
+        let mut table = Table::new();
+        for row in 0..3 {
+            table.set("seq", ColumnType::Unsigned, Some(Cell::Unsigned(row)))?;
+            table.set("time", ColumnType::Timestamp, Some(Cell::Timestamp(0)))?;
+            let on = (row != 1).then_some(Cell::Bool(row == 0));
+            table.set("on", ColumnType::Bool, on)?;
+            table.end_row();
+        }
+
+        let path = std::env::temp_dir().join(format!("export-{}.parquet", std::process::id()));
+        let mut sink = ParquetSink::new(File::create(&path)?);
+        sink.write(&table)?;
+        table.clear();
+        table.set("seq", ColumnType::Unsigned, Some(Cell::Unsigned(3)))?;
+        table.end_row();
+        sink.write(&table)?;
+        sink.finish()?;
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?.build()?;
+        let batches = reader.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 4);
+        let schema = batches[0].schema();
+        assert_eq!(schema.field(0).data_type(), &DataType::UInt64);
+        assert_eq!(
+            schema.field(1).data_type(),
+            &data_type(ColumnType::Timestamp)
+        );
+        // Empty when not set, even in the rows of the second write
+        let on = batches[0].column(2);
+        assert_eq!(on.data_type(), &DataType::Boolean);
+        assert_eq!(on.null_count(), 2);
+        assert!(on.is_null(1) && on.is_null(3));
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
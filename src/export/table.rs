@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+/// Type of the cells of a column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnType {
+    Bool,
+    Unsigned,
+    Signed,
+    Float,
+    Text,
+    /// Microseconds since the Unix epoch, UTC.
+    Timestamp,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cell {
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Text(String),
+    Timestamp(i64),
+}
+
+/// Destination of the rows of a table, written in batches with the same columns.
+pub trait Sink {
+    fn write(&mut self, table: &Table) -> Result<()>;
+    /// Writes what remains, e.g. a footer.
+    fn finish(&mut self) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct Column {
+    pub name: String,
+    pub column_type: ColumnType,
+    /// One cell per row, empty when the row has no value.
+    pub cells: Vec<Option<Cell>>,
+}
+
+/// Rows being exported, filled one cell at a time. Columns are created when first set,
+/// in order, with empty cells for the previous rows, unless they are given upfront.
+#[derive(Debug, Default)]
+pub struct Table {
+    pub columns: Vec<Column>,
+    index: HashMap<String, usize>,
+    rows: usize,
+    /// No column can be added.
+    fixed: bool,
+}
+
+impl Table {
+    pub fn new() -> Table {
+        Table::default()
+    }
+
+    /// Table of the given columns only.
+    pub fn with_columns<I>(columns: I) -> Result<Table>
+    where
+        I: IntoIterator<Item = (String, ColumnType)>,
+    {
+        let mut table = Table::new();
+        for (name, column_type) in columns {
+            if table.index.contains_key(&name) {
+                bail!("Column `{}` defined twice", name);
+            }
+            table.add_column(name, column_type);
+        }
+        table.fixed = true;
+        Ok(table)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Sets the cell of the current row.
+    pub fn set(&mut self, name: &str, column_type: ColumnType, cell: Option<Cell>) -> Result<()> {
+        let idx = match self.index.get(name) {
+            Some(idx) => *idx,
+            None if self.fixed => bail!("Column `{}` not defined", name),
+            None => self.add_column(name.to_string(), column_type),
+        };
+
+        let column = &mut self.columns[idx];
+        if column.column_type != column_type {
+            bail!(
+                "Column `{}` of type `{:?}`, then `{:?}`",
+                name,
+                column.column_type,
+                column_type
+            );
+        }
+        if column.cells.len() > self.rows {
+            bail!("Column `{}` set twice in a row", name);
+        }
+        column.cells.push(cell);
+        Ok(())
+    }
+
+    /// Adds an empty column, returning its index.
+    fn add_column(&mut self, name: String, column_type: ColumnType) -> usize {
+        self.index.insert(name.clone(), self.columns.len());
+        self.columns.push(Column {
+            name,
+            column_type,
+            cells: vec![None; self.rows],
+        });
+        self.columns.len() - 1
+    }
+
+    /// Ends the current row, leaving the cells not set empty.
+    pub fn end_row(&mut self) {
+        self.rows += 1;
+        for column in &mut self.columns {
+            column.cells.resize(self.rows, None);
+        }
+    }
+
+    /// Fills a row, which is ended if all its cells are set, and discarded otherwise, with
+    /// the columns it created.
+    pub fn row<F>(&mut self, fill: F) -> Result<()>
+    where
+        F: FnOnce(&mut Table) -> Result<()>,
+    {
+        let columns = self.columns.len();
+        match fill(self) {
+            Ok(()) => {
+                self.end_row();
+                Ok(())
+            }
+            Err(e) => {
+                for column in self.columns.drain(columns..) {
+                    self.index.remove(&column.name);
+                }
+                for column in &mut self.columns {
+                    column.cells.truncate(self.rows);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes the rows, keeping the columns.
+    pub fn clear(&mut self) {
+        self.rows = 0;
+        for column in &mut self.columns {
+            column.cells.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn failed_rows() {
+        let mut table = Table::new();
+        let result = table.row(|table| {
+            table.set("a", ColumnType::Unsigned, Some(Cell::Unsigned(1)))?;
+            table.set("b", ColumnType::Bool, Some(Cell::Bool(true)))?;
+            table.set("a", ColumnType::Unsigned, None)
+        });
+        assert!(result.is_err());
+        assert!(table.columns.is_empty());
+
+        // The columns of the failed row can be created again, of another type
+        let result = table.row(|table| table.set("b", ColumnType::Float, Some(Cell::Float(0.5))));
+        assert!(result.is_ok());
+        assert_eq!(table.rows(), 1);
+        assert_eq!(table.columns[0].cells, [Some(Cell::Float(0.5))]);
+    }
+
+    #[test]
+    fn fixed_columns() {
+        let columns = vec![("a".to_string(), ColumnType::Unsigned)];
+        let mut table = Table::with_columns(columns.clone()).unwrap();
+        assert!(table
+            .row(|table| table.set("b", ColumnType::Bool, None))
+            .is_err());
+        table.end_row();
+        assert_eq!(table.columns[0].cells, [None]);
+
+        assert!(Table::with_columns(vec![columns[0].clone(), columns[0].clone()]).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Packet filter expressions, e.g. `apid in 0x70..0x7F and type == tm and len > 100`.
+//!
+//! Comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`) and memberships (`in a..b`, inclusive,
+//! or `in [a, b..c]`) on packet fields, combined with `and`, `or`, `not` and parentheses.
+//! A bare field is true when non-zero. Predicates on a field without a value in a packet
+//! (e.g. `time` without secondary header) are false: `time != 0` does not match such a
+//! packet, while `not time == 0` does.
+//!
+//! Fields are compared as integers, except `time`, which is compared as a number of
+//! seconds (e.g. `time < 1.5`).
+//!
+//! Fields:
+//! - `version`, `type` (`tm` or `tc`), `sec_header`, `apid`, `seq_flags`, `seq`,
+//!   `data_length`: fields of the primary header;
+//! - `len`: length of the whole packet, in bytes;
+//! - `time`: time of the secondary header, in seconds;
+//! - `data[i]`, `data[i..j]`: byte, or big-endian integer over the bytes `i` to `j`
+//!   (inclusive, at most 8), of the user data field.
+
+// Reachable modules
+mod parser;
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+
+use crate::protocol::{Packet, PktType, HEADER_SIZE};
+
+/// Packet field used in an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Version,
+    Type,
+    SecHeader,
+    Apid,
+    SeqFlags,
+    Seq,
+    DataLength,
+    Len,
+    Time,
+    /// Bytes of the user data field, inclusive.
+    Data(RangeInclusive<usize>),
+}
+
+impl Field {
+    pub fn value(&self, pkt: &Packet) -> Option<Value> {
+        let header = &pkt.pri_header;
+        let value = match self {
+            Field::Version => header.version_number as u64,
+            Field::Type => match header.packet_type {
+                PktType::Telemetry => 0,
+                PktType::Telecommand => 1,
+            },
+            Field::SecHeader => header.secondary_header_flag as u64,
+            Field::Apid => header.apid as u64,
+            Field::SeqFlags => header.sequence_flags as u64,
+            Field::Seq => header.sequence_counter as u64,
+            Field::DataLength => header.data_length as u64,
+            Field::Len => (HEADER_SIZE + header.data_field_size()) as u64,
+            Field::Time => {
+                let time = pkt.sec_header.as_ref()?.time();
+                return Some(Value::Float(time.as_secs_f64()));
+            }
+            Field::Data(bytes) => {
+                let data = &pkt.user_data.as_ref()?.data;
+                data.get(bytes.clone())?
+                    .iter()
+                    .fold(0u64, |acc, byte| (acc << 8) | *byte as u64)
+            }
+        };
+        Some(Value::Int(value))
+    }
+}
+
+/// Value of a field or of a literal. Integers are compared exactly, and as floats only
+/// against floats.
+#[derive(Clone, Copy, Debug)]
+pub enum Value {
+    Int(u64),
+    Float(f64),
+}
+
+impl Value {
+    fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(value) => value as f64,
+            Value::Float(value) => value,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (*self, *other) {
+            (Value::Int(lhs), Value::Int(rhs)) => Some(lhs.cmp(&rhs)),
+            (lhs, rhs) => lhs.as_f64().partial_cmp(&rhs.as_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn compare(self, lhs: Value, rhs: Value) -> bool {
+        match self {
+            Op::Eq => lhs == rhs,
+            Op::Ne => lhs != rhs,
+            Op::Lt => lhs < rhs,
+            Op::Le => lhs <= rhs,
+            Op::Gt => lhs > rhs,
+            Op::Ge => lhs >= rhs,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Value),
+    In(Field, Vec<RangeInclusive<Value>>),
+    /// Bare field: true when non-zero.
+    Is(Field),
+}
+
+impl Expr {
+    pub fn matches(&self, pkt: &Packet) -> bool {
+        match self {
+            Expr::And(lhs, rhs) => lhs.matches(pkt) && rhs.matches(pkt),
+            Expr::Or(lhs, rhs) => lhs.matches(pkt) || rhs.matches(pkt),
+            Expr::Not(expr) => !expr.matches(pkt),
+            Expr::Compare(field, op, rhs) => {
+                field.value(pkt).is_some_and(|lhs| op.compare(lhs, *rhs))
+            }
+            Expr::In(field, ranges) => field
+                .value(pkt)
+                .is_some_and(|value| ranges.iter().any(|range| range.contains(&value))),
+            Expr::Is(field) => field.value(pkt).is_some_and(|value| value != Value::Int(0)),
+        }
+    }
+}
+
+/// Parsed filter expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Filter> {
+        Ok(Filter {
+            source: source.to_string(),
+            expr: parser::parse(source)?,
+        })
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn matches(&self, pkt: &Packet) -> bool {
+        self.expr.matches(pkt)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Filter> {
+        Filter::parse(source)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::{PacketBuilder, TestResult};
+
+    fn packet(apid: u16, sec_header: bool, data: &[u8]) -> Packet {
+        let builder = PacketBuilder::new(PktType::Telemetry, apid)
+            .sequence_counter(42)
+            .data(data);
+        match sec_header {
+            true => builder.time(1, 1_500),
+            false => builder,
+        }
+        .build()
+    }
+
+    #[test]
+    fn evaluation() -> TestResult {
+        let pkt = packet(0x75, true, &[0x12, 0x34, 0x56, 0x78]);
+
+        let matching = [
+            "apid in 0x70..0x7F and type == tm and seq_flags == 3 and len >= 20",
+            "apid in [1, 0x70..0x75]",
+            "sec_header and not (type == tc or seq != 42)",
+            "time >= 604801.5 and time < 604802",
+            "data[1] == 0x34 and data[0..2] == 0x123456",
+            "apid == 0 or data_length == 13",
+        ];
+        for source in matching.iter() {
+            assert!(Filter::parse(source)?.matches(&pkt), "{}", source);
+        }
+
+        let not_matching = [
+            "apid in 0x76..0x7F",
+            "type == tc",
+            "data[4] == 0",
+            "not sec_header",
+            "apid != 0x75",
+        ];
+        for source in not_matching.iter() {
+            assert!(!Filter::parse(source)?.matches(&pkt), "{}", source);
+        }
+
+        // Predicates on missing fields are false, so only their negation matches
+        let pkt = packet(0x75, false, &[]);
+        assert!(!Filter::parse("time > 0")?.matches(&pkt));
+        assert!(!Filter::parse("data[0] != 1")?.matches(&pkt));
+        assert!(Filter::parse("not time > 0")?.matches(&pkt));
+
+        Ok(())
+    }
+
+    #[test]
+    fn integer_precision() -> TestResult {
+        let pkt = packet(0x75, false, &[0xFF; 8]);
+
+        let filter = Filter::parse("data[0..7] == 0xFFFFFFFFFFFFFFFF")?;
+        assert!(filter.matches(&pkt));
+        let filter = Filter::parse("data[0..7] == 0xFFFFFFFFFFFFFFFE")?;
+        assert!(!filter.matches(&pkt));
+        let filter = Filter::parse("data[0..7] > 18446744073709551614")?;
+        assert!(filter.matches(&pkt));
+
+        Ok(())
+    }
+}
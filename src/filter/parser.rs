@@ -0,0 +1,437 @@
+//! Recursive descent parser of filter expressions.
+//!
+//! expr    := and ("or" and)*
+//! and     := unary ("and" unary)*
+//! unary   := "not" unary | "(" expr ")" | field [op value | "in" set]
+//! set     := range | "[" range ("," range)* "]"
+//! range   := value [".." value]
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use anyhow::{bail, Result};
+
+use super::{Expr, Field, Op, Value};
+
+/// Max number of user data bytes read as one integer.
+const MAX_DATA_BYTES: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(Value),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    In,
+    Range,
+    Comma,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{}`", ident),
+            Token::Number(number) => write!(f, "`{}`", number),
+            Token::Op(op) => {
+                let op = match op {
+                    Op::Eq => "==",
+                    Op::Ne => "!=",
+                    Op::Lt => "<",
+                    Op::Le => "<=",
+                    Op::Gt => ">",
+                    Op::Ge => ">=",
+                };
+                write!(f, "`{}`", op)
+            }
+            Token::And => write!(f, "`and`"),
+            Token::Or => write!(f, "`or`"),
+            Token::Not => write!(f, "`not`"),
+            Token::In => write!(f, "`in`"),
+            Token::Range => write!(f, "`..`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::LBracket => write!(f, "`[`"),
+            Token::RBracket => write!(f, "`]`"),
+        }
+    }
+}
+
+/// Splits the expression in tokens, with their column (starting at 1).
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let column = idx + 1;
+        let next = chars.get(idx + 1).copied();
+
+        let (token, len) = match chars[idx] {
+            c if c.is_whitespace() => {
+                idx += 1;
+                continue;
+            }
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '[' => (Token::LBracket, 1),
+            ']' => (Token::RBracket, 1),
+            ',' => (Token::Comma, 1),
+            '.' if next == Some('.') => (Token::Range, 2),
+            '=' if next == Some('=') => (Token::Op(Op::Eq), 2),
+            '!' if next == Some('=') => (Token::Op(Op::Ne), 2),
+            '<' if next == Some('=') => (Token::Op(Op::Le), 2),
+            '>' if next == Some('=') => (Token::Op(Op::Ge), 2),
+            '<' => (Token::Op(Op::Lt), 1),
+            '>' => (Token::Op(Op::Gt), 1),
+            '!' => (Token::Not, 1),
+            '&' if next == Some('&') => (Token::And, 2),
+            '|' if next == Some('|') => (Token::Or, 2),
+            c if c.is_ascii_digit() => {
+                let len = number_length(&chars[idx..]);
+                let text: String = chars[idx..idx + len].iter().collect();
+                (Token::Number(parse_number(&text, column)?), len)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let len = chars[idx..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                let ident: String = chars[idx..idx + len].iter().collect();
+                let token = match ident.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "in" => Token::In,
+                    _ => Token::Ident(ident),
+                };
+                (token, len)
+            }
+            c => bail!("Unexpected character `{}` at column {}", c, column),
+        };
+
+        tokens.push((token, column));
+        idx += len;
+    }
+
+    Ok(tokens)
+}
+
+/// Length of the number at the start of the characters, stopping before a `..`.
+fn number_length(chars: &[char]) -> usize {
+    let mut len = 0;
+    while let Some(c) = chars.get(len) {
+        let is_decimal_point = *c == '.' && chars.get(len + 1).is_some_and(|c| c.is_ascii_digit());
+        if c.is_ascii_alphanumeric() || is_decimal_point {
+            len += 1;
+        } else {
+            break;
+        }
+    }
+    len
+}
+
+fn parse_number(text: &str, column: usize) -> Result<Value> {
+    let number = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(Value::Int),
+        None if text.contains('.') => text.parse().ok().map(Value::Float),
+        None => text.parse().ok().map(Value::Int),
+    };
+    match number {
+        Some(number) => Ok(number),
+        None => bail!("Invalid number `{}` at column {}", text, column),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // Column past the end of the expression
+    end: usize,
+}
+
+/// Parses a whole expression.
+pub fn parse(source: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        end: source.chars().count() + 1,
+    };
+    if parser.tokens.is_empty() {
+        bail!("Empty filter expression");
+    }
+
+    let expr = parser.expr()?;
+    if let Some((token, column)) = parser.tokens.get(parser.pos) {
+        bail!("Unexpected {} at column {}", token, column);
+    }
+    Ok(expr)
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.end, |(_, column)| *column)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Reports what was expected instead of the current token.
+    fn expected<T>(&self, what: &str) -> Result<T> {
+        match self.peek() {
+            Some(token) => bail!(
+                "Expected {} at column {}, found {}",
+                what,
+                self.column(),
+                token
+            ),
+            None => bail!("Expected {} at the end of the expression", what),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        if self.peek() == Some(&expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.expected(&expected.to_string())
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(_)) => self.predicate(),
+            _ => self.expected("a field, `not` or `(`"),
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Expr> {
+        let field = self.field()?;
+        match self.peek() {
+            Some(Token::Op(op)) => {
+                let op = *op;
+                self.pos += 1;
+                Ok(Expr::Compare(field, op, self.value()?))
+            }
+            Some(Token::In) => {
+                self.pos += 1;
+                Ok(Expr::In(field, self.set()?))
+            }
+            _ => Ok(Expr::Is(field)),
+        }
+    }
+
+    fn field(&mut self) -> Result<Field> {
+        let column = self.column();
+        let name = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => unreachable!("Fields start with an identifier"),
+        };
+
+        let field = match name.as_str() {
+            "version" => Field::Version,
+            "type" => Field::Type,
+            "sec_header" => Field::SecHeader,
+            "apid" => Field::Apid,
+            "seq_flags" => Field::SeqFlags,
+            "seq" => Field::Seq,
+            "data_length" => Field::DataLength,
+            "len" => Field::Len,
+            "time" => Field::Time,
+            "data" => Field::Data(self.data_bytes()?),
+            _ => bail!("Unknown field `{}` at column {}", name, column),
+        };
+        Ok(field)
+    }
+
+    /// Bytes of `data[i]` or `data[i..j]`.
+    fn data_bytes(&mut self) -> Result<RangeInclusive<usize>> {
+        self.expect(Token::LBracket)?;
+        let column = self.column();
+        let start = self.index()?;
+        let end = if self.peek() == Some(&Token::Range) {
+            self.pos += 1;
+            self.index()?
+        } else {
+            start
+        };
+        self.expect(Token::RBracket)?;
+
+        if end < start || end - start >= MAX_DATA_BYTES {
+            bail!(
+                "Invalid bytes `{}..{}` at column {}: at most {} bytes, in order",
+                start,
+                end,
+                column,
+                MAX_DATA_BYTES
+            );
+        }
+        Ok(start..=end)
+    }
+
+    fn index(&mut self) -> Result<usize> {
+        match self.peek() {
+            Some(Token::Number(Value::Int(number))) => {
+                let index = usize::try_from(*number)?;
+                self.pos += 1;
+                Ok(index)
+            }
+            _ => self.expected("a byte index"),
+        }
+    }
+
+    fn set(&mut self) -> Result<Vec<RangeInclusive<Value>>> {
+        if self.peek() != Some(&Token::LBracket) {
+            return Ok(vec![self.range()?]);
+        }
+
+        self.pos += 1;
+        let mut ranges = vec![self.range()?];
+        while self.peek() == Some(&Token::Comma) {
+            self.pos += 1;
+            ranges.push(self.range()?);
+        }
+        self.expect(Token::RBracket)?;
+        Ok(ranges)
+    }
+
+    fn range(&mut self) -> Result<RangeInclusive<Value>> {
+        let start = self.value()?;
+        if self.peek() != Some(&Token::Range) {
+            return Ok(start..=start);
+        }
+
+        self.pos += 1;
+        let column = self.column();
+        let end = self.value()?;
+        if end < start {
+            bail!("Empty range `{}..{}` at column {}", start, end, column);
+        }
+        Ok(start..=end)
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let value = match self.peek() {
+            Some(Token::Number(number)) => *number,
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "tm" | "false" => Value::Int(0),
+                "tc" | "true" => Value::Int(1),
+                _ => return self.expected("a value"),
+            },
+            _ => return self.expected("a value"),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::TestResult;
+
+    #[test]
+    fn precedence() -> TestResult {
+        let expr = parse("not apid == 1 or seq < 2 && sec_header")?;
+        let expected = Expr::Or(
+            Box::new(Expr::Not(Box::new(Expr::Compare(
+                Field::Apid,
+                Op::Eq,
+                Value::Int(1),
+            )))),
+            Box::new(Expr::And(
+                Box::new(Expr::Compare(Field::Seq, Op::Lt, Value::Int(2))),
+                Box::new(Expr::Is(Field::SecHeader)),
+            )),
+        );
+        assert_eq!(expr, expected);
+
+        let expr = parse("apid in [0x10, 0x20..0x2f] and time <= 1.5")?;
+        let expected = Expr::And(
+            Box::new(Expr::In(
+                Field::Apid,
+                vec![
+                    Value::Int(16)..=Value::Int(16),
+                    Value::Int(32)..=Value::Int(47),
+                ],
+            )),
+            Box::new(Expr::Compare(Field::Time, Op::Le, Value::Float(1.5))),
+        );
+        assert_eq!(expr, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn error_messages() {
+        let error = |source| parse(source).unwrap_err().to_string();
+
+        assert_eq!(error(""), "Empty filter expression");
+        assert_eq!(
+            error("apid == "),
+            "Expected a value at the end of the expression"
+        );
+        assert_eq!(
+            error("apid == and"),
+            "Expected a value at column 9, found `and`"
+        );
+        assert_eq!(error("apdi == 1"), "Unknown field `apdi` at column 1");
+        assert_eq!(
+            error("(apid == 1"),
+            "Expected `)` at the end of the expression"
+        );
+        assert_eq!(error("apid == 1 seq"), "Unexpected `seq` at column 11");
+        assert_eq!(error("apid == 0xZZ"), "Invalid number `0xZZ` at column 9");
+        assert_eq!(error("apid = 1"), "Unexpected character `=` at column 6");
+        assert_eq!(error("apid in 5..1"), "Empty range `5..1` at column 12");
+        assert_eq!(
+            error("data[0..8] == 0"),
+            "Invalid bytes `0..8` at column 6: at most 8 bytes, in order"
+        );
+    }
+}
@@ -0,0 +1,578 @@
+//! AOS Transfer Frames (CCSDS 732.0-B) with M_PDU and B_PDU data fields.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use super::clcw::Clcw;
+use super::extractor::VcPacketExtractor;
+use super::tm::{FECF_SIZE, OCF_SIZE};
+use crate::protocol::hasher;
+use crate::protocol::Packet;
+
+/// Size of the frame primary header (without the FHEC). Fixed size: 6 bytes.
+pub const PRIMARY_HEADER_SIZE: usize = 6;
+
+/// Size of the Frame Header Error Control field.
+pub const FHEC_SIZE: usize = 2;
+
+/// Size of the M_PDU and B_PDU headers.
+pub const PDU_HEADER_SIZE: usize = 2;
+
+/// Transfer Frame Version Number of AOS frames.
+pub const VERSION_NUMBER: u8 = 1;
+
+/// Modulus of the virtual channel frame count of AOS frames (24 bits).
+pub const AOS_COUNTER_MODULUS: u32 = 1 << 24;
+
+/// Bitstream Data Pointer: the whole B_PDU data zone is valid.
+pub const BDP_ALL_VALID: u16 = 0x3FFF;
+
+/// Bitstream Data Pointer: the B_PDU data zone only carries idle data.
+pub const BDP_IDLE_DATA: u16 = 0x3FFE;
+
+/// Managed parameters of an AOS physical channel: they cannot be read from the frames.
+#[derive(Clone, Debug, Default)]
+pub struct AosConfig {
+    pub has_fhec: bool,
+    pub insert_zone_len: usize,
+    pub has_ocf: bool,
+    pub has_fecf: bool,
+}
+
+impl AosConfig {
+    /// Size of the frame data field, for frames of the given length.
+    pub fn data_len(&self, frame_length: usize) -> Option<usize> {
+        let overhead = PRIMARY_HEADER_SIZE
+            + if self.has_fhec { FHEC_SIZE } else { 0 }
+            + self.insert_zone_len
+            + if self.has_ocf { OCF_SIZE } else { 0 }
+            + if self.has_fecf { FECF_SIZE } else { 0 };
+        frame_length.checked_sub(overhead)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AosPrimaryHeader {
+    pub version_number: u8,
+    pub spacecraft_id: u8,
+    pub virtual_channel_id: u8,
+    pub vc_frame_count: u32,
+    pub replay_flag: bool,
+    pub vc_frame_count_usage_flag: bool,
+    pub vc_frame_count_cycle: u8,
+}
+
+impl AosPrimaryHeader {
+    pub fn new(spacecraft_id: u8, virtual_channel_id: u8) -> AosPrimaryHeader {
+        AosPrimaryHeader {
+            version_number: VERSION_NUMBER,
+            spacecraft_id,
+            virtual_channel_id,
+            vc_frame_count: 0,
+            replay_flag: false,
+            vc_frame_count_usage_flag: false,
+            vc_frame_count_cycle: 0,
+        }
+    }
+
+    pub fn from_buffer(buf: &[u8]) -> Result<AosPrimaryHeader> {
+        if buf.len() < PRIMARY_HEADER_SIZE {
+            bail!("Frame of size `{}` is shorter than its header", buf.len());
+        }
+        let mut cursor = Cursor::new(buf);
+
+        let val = cursor.read_u16::<BigEndian>()?;
+        let version_number = ((val & 0xC000) >> 14) as u8;
+        let spacecraft_id = ((val & 0x3FC0) >> 6) as u8;
+        let virtual_channel_id = (val & 0x003F) as u8;
+
+        let vc_frame_count = cursor.read_u24::<BigEndian>()?;
+
+        let val = cursor.read_u8()?;
+        let replay_flag = val & 0x80 != 0;
+        let vc_frame_count_usage_flag = val & 0x40 != 0;
+        let vc_frame_count_cycle = val & 0x0F;
+
+        if version_number != VERSION_NUMBER {
+            bail!("Unexpected AOS frame version number `{}`", version_number);
+        }
+
+        Ok(AosPrimaryHeader {
+            version_number,
+            spacecraft_id,
+            virtual_channel_id,
+            vc_frame_count,
+            replay_flag,
+            vc_frame_count_usage_flag,
+            vc_frame_count_cycle,
+        })
+    }
+
+    pub fn get_buffer(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(PRIMARY_HEADER_SIZE);
+        let mut cursor = Cursor::new(&mut buf);
+
+        // Master channel and virtual channel identification
+        let mut val: u16 = ((self.version_number & 0x03) as u16) << 14;
+        val |= (self.spacecraft_id as u16) << 6;
+        val |= (self.virtual_channel_id & 0x3F) as u16;
+        cursor.write_u16::<BigEndian>(val).unwrap();
+
+        // Virtual channel frame count
+        cursor
+            .write_u24::<BigEndian>(self.vc_frame_count & 0x00FF_FFFF)
+            .unwrap();
+
+        // Signaling field
+        let mut val: u8 = (self.replay_flag as u8) << 7;
+        val |= (self.vc_frame_count_usage_flag as u8) << 6;
+        val |= self.vc_frame_count_cycle & 0x0F;
+        cursor.write_u8(val).unwrap();
+
+        buf
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AosFrame {
+    pub header: AosPrimaryHeader,
+    pub insert_zone: Option<Vec<u8>>,
+    pub data: Vec<u8>,
+    pub ocf: Option<u32>,
+    pub fecf: Option<u16>,
+}
+
+impl AosFrame {
+    /// Decodes a whole frame: its length is the length of the given buffer.
+    pub fn from_buffer(buf: &[u8], config: &AosConfig) -> Result<AosFrame> {
+        let header = AosPrimaryHeader::from_buffer(buf)?;
+        let data_len = match config.data_len(buf.len()) {
+            Some(data_len) => data_len,
+            None => bail!(
+                "Frame of size `{}` has no room for its data field",
+                buf.len()
+            ),
+        };
+
+        if config.has_fecf && hasher::compute(buf) != 0 {
+            bail!("Frame with invalid FECF");
+        }
+
+        let mut start = PRIMARY_HEADER_SIZE;
+        if config.has_fhec {
+            if !fhec::check(&buf[..start + FHEC_SIZE]) {
+                bail!("Frame header with uncorrectable errors (FHEC)");
+            }
+            start += FHEC_SIZE;
+        }
+
+        let insert_zone = if config.insert_zone_len > 0 {
+            let end = start + config.insert_zone_len;
+            let zone = buf[start..end].to_vec();
+            start = end;
+            Some(zone)
+        } else {
+            None
+        };
+
+        let data = buf[start..start + data_len].to_vec();
+
+        let mut cursor = Cursor::new(buf);
+        cursor.set_position((start + data_len) as u64);
+        let ocf = match config.has_ocf {
+            true => Some(cursor.read_u32::<BigEndian>()?),
+            false => None,
+        };
+        let fecf = match config.has_fecf {
+            true => Some(cursor.read_u16::<BigEndian>()?),
+            false => None,
+        };
+
+        Ok(AosFrame {
+            header,
+            insert_zone,
+            data,
+            ocf,
+            fecf,
+        })
+    }
+
+    /// Encodes the frame, computing the FHEC and FECF required by the configuration.
+    pub fn get_buffer(&self, config: &AosConfig) -> Vec<u8> {
+        let mut buf = self.header.get_buffer();
+
+        // (Optional) Frame Header Error Control
+        if config.has_fhec {
+            let check = fhec::encode(&buf);
+            buf.extend_from_slice(&check);
+        }
+
+        // (Optional) Insert Zone
+        if let Some(zone) = &self.insert_zone {
+            buf.extend_from_slice(zone);
+        }
+
+        // Data Field
+        buf.extend_from_slice(&self.data);
+
+        // (Optional) Operational Control Field
+        if let Some(ocf) = self.ocf {
+            buf.extend_from_slice(&ocf.to_be_bytes());
+        }
+
+        // (Optional) Frame Error Control Field
+        if config.has_fecf {
+            hasher::append_checksum(&mut buf);
+        }
+
+        buf
+    }
+
+    /// CLCW reported in the OCF, if any.
+    pub fn clcw(&self) -> Option<Clcw> {
+        self.ocf.and_then(Clcw::from_ocf)
+    }
+}
+
+/// Multiplexing Protocol Data Unit: packets spanning consecutive frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MPdu {
+    pub first_header_pointer: u16,
+    pub packet_zone: Vec<u8>,
+}
+
+impl MPdu {
+    pub fn from_buffer(buf: &[u8]) -> Result<MPdu> {
+        if buf.len() < PDU_HEADER_SIZE {
+            bail!("M_PDU of size `{}` is shorter than its header", buf.len());
+        }
+
+        Ok(MPdu {
+            first_header_pointer: u16::from_be_bytes([buf[0], buf[1]]) & 0x07FF,
+            packet_zone: buf[PDU_HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    pub fn get_buffer(&self) -> Vec<u8> {
+        let mut buf = (self.first_header_pointer & 0x07FF).to_be_bytes().to_vec();
+        buf.extend_from_slice(&self.packet_zone);
+        buf
+    }
+}
+
+/// Bitstream Protocol Data Unit: bits of a synchronous data stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BPdu {
+    pub data_pointer: u16,
+    pub data: Vec<u8>,
+}
+
+impl BPdu {
+    pub fn from_buffer(buf: &[u8]) -> Result<BPdu> {
+        if buf.len() < PDU_HEADER_SIZE {
+            bail!("B_PDU of size `{}` is shorter than its header", buf.len());
+        }
+
+        Ok(BPdu {
+            data_pointer: u16::from_be_bytes([buf[0], buf[1]]) & 0x3FFF,
+            data: buf[PDU_HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    pub fn get_buffer(&self) -> Vec<u8> {
+        let mut buf = (self.data_pointer & 0x3FFF).to_be_bytes().to_vec();
+        buf.extend_from_slice(&self.data);
+        buf
+    }
+
+    /// Number of valid bits at the start of the data zone.
+    pub fn valid_bits(&self) -> usize {
+        match self.data_pointer {
+            BDP_ALL_VALID => self.data.len() * 8,
+            BDP_IDLE_DATA => 0,
+            // The pointer locates the last valid bit
+            last => (last as usize + 1).min(self.data.len() * 8),
+        }
+    }
+}
+
+/// Reassembles the bitstream carried by consecutive B_PDUs of a virtual channel.
+#[derive(Default)]
+pub struct BitstreamAssembler {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitstreamAssembler {
+    pub fn new() -> BitstreamAssembler {
+        BitstreamAssembler::default()
+    }
+
+    pub fn push(&mut self, pdu: &BPdu) {
+        for idx in 0..pdu.valid_bits() {
+            let bit = (pdu.data[idx / 8] >> (7 - idx % 8)) & 0x01;
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let last = self
+                .bytes
+                .last_mut()
+                .expect("Just pushed: should have a byte");
+            *last |= bit << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+
+    /// Number of bits received so far.
+    pub fn len(&self) -> usize {
+        self.bits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Complete bytes of the bitstream, removed from the assembler.
+    pub fn take_bytes(&mut self) -> Vec<u8> {
+        let whole = self.bits / 8;
+        let tail = self.bytes.split_off(whole);
+        self.bits -= whole * 8;
+        std::mem::replace(&mut self.bytes, tail)
+    }
+}
+
+/// Packet extractor demultiplexing the M_PDUs of AOS frames by virtual channel.
+#[derive(Default)]
+pub struct MPduExtractor {
+    channels: HashMap<u8, VcPacketExtractor>,
+}
+
+impl MPduExtractor {
+    pub fn new() -> MPduExtractor {
+        MPduExtractor::default()
+    }
+
+    pub fn push_frame(&mut self, frame: &AosFrame) -> Result<Vec<Packet>> {
+        let pdu = MPdu::from_buffer(&frame.data)?;
+
+        let extractor = self
+            .channels
+            .entry(frame.header.virtual_channel_id)
+            .or_insert_with(|| VcPacketExtractor::new(AOS_COUNTER_MODULUS));
+
+        Ok(extractor.push(
+            frame.header.vc_frame_count,
+            pdu.first_header_pointer,
+            &pdu.packet_zone,
+        ))
+    }
+}
+
+/// Reed-Solomon (10,6) code over GF(16) protecting the frame identification and
+/// signaling fields.
+pub mod fhec {
+    /// Primitive polynomial of the field: x^4 + x + 1.
+    const FIELD_POLY: u8 = 0x13;
+
+    /// Powers of alpha in GF(16).
+    const fn exp_table() -> [u8; 15] {
+        let mut table = [0u8; 15];
+        let mut val: u8 = 1;
+        let mut idx = 0;
+        while idx < 15 {
+            table[idx] = val;
+            val <<= 1;
+            if val & 0x10 != 0 {
+                val ^= FIELD_POLY;
+            }
+            idx += 1;
+        }
+        table
+    }
+
+    const EXP: [u8; 15] = exp_table();
+
+    fn mul(a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        let log_a = EXP.iter().position(|&val| val == a).unwrap();
+        let log_b = EXP.iter().position(|&val| val == b).unwrap();
+        EXP[(log_a + log_b) % 15]
+    }
+
+    /// Generator polynomial (x + a^6)(x + a^7)(x + a^8)(x + a^9), highest degree first.
+    fn generator() -> [u8; 5] {
+        let mut poly = vec![1u8];
+        for root in EXP[6..10].iter() {
+            let mut next = vec![0u8; poly.len() + 1];
+            for (idx, coef) in poly.iter().enumerate() {
+                next[idx] ^= coef;
+                next[idx + 1] ^= mul(*coef, *root);
+            }
+            poly = next;
+        }
+        [poly[0], poly[1], poly[2], poly[3], poly[4]]
+    }
+
+    /// The 6 protected nibbles: identification (2 bytes) and signaling field.
+    fn nibbles(header: &[u8]) -> [u8; 6] {
+        [
+            header[0] >> 4,
+            header[0] & 0x0F,
+            header[1] >> 4,
+            header[1] & 0x0F,
+            header[5] >> 4,
+            header[5] & 0x0F,
+        ]
+    }
+
+    /// FHEC of the 6-byte frame primary header.
+    pub fn encode(header: &[u8]) -> [u8; 2] {
+        let generator = generator();
+        let mut reg = [0u8; 4];
+
+        for nibble in nibbles(header).iter() {
+            let feedback = nibble ^ reg[0];
+            for idx in 0..3 {
+                reg[idx] = reg[idx + 1] ^ mul(feedback, generator[idx + 1]);
+            }
+            reg[3] = mul(feedback, generator[4]);
+        }
+
+        [(reg[0] << 4) | reg[1], (reg[2] << 4) | reg[3]]
+    }
+
+    /// Whether the frame primary header followed by its FHEC is a codeword.
+    pub fn check(buf: &[u8]) -> bool {
+        let mut word = nibbles(buf).to_vec();
+        word.extend_from_slice(&[buf[6] >> 4, buf[6] & 0x0F, buf[7] >> 4, buf[7] & 0x0F]);
+
+        (6..10).all(|root| {
+            // Horner evaluation of the codeword at a^root
+            word.iter()
+                .fold(0u8, |acc, coef| mul(acc, EXP[root]) ^ coef)
+                == 0
+        })
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::{TestResult, VALID_SOURCE};
+
+    fn config() -> AosConfig {
+        AosConfig {
+            has_fhec: true,
+            insert_zone_len: 2,
+            has_ocf: true,
+            has_fecf: true,
+        }
+    }
+
+    #[test]
+    fn frame_round_trip() -> TestResult {
+        let mut header = AosPrimaryHeader::new(0xAB, 0x21);
+        header.vc_frame_count = 0x123456;
+        header.vc_frame_count_usage_flag = true;
+        header.vc_frame_count_cycle = 0x05;
+
+        let frame = AosFrame {
+            header,
+            insert_zone: Some(vec![0xDE, 0xAD]),
+            data: vec![1, 2, 3, 4],
+            ocf: Some(0x0102_0304),
+            fecf: Some(0),
+        };
+
+        let config = config();
+        let buf = frame.get_buffer(&config);
+        assert_eq!(&buf[..6], [0x6A, 0xE1, 0x12, 0x34, 0x56, 0x45]);
+        assert_eq!(hasher::compute(&buf), 0);
+
+        let decoded = AosFrame::from_buffer(&buf, &config)?;
+        assert_eq!(decoded.header, frame.header);
+        assert_eq!(decoded.insert_zone, frame.insert_zone);
+        assert_eq!(decoded.data, frame.data);
+        assert_eq!(decoded.ocf, frame.ocf);
+
+        // Corrupted spacecraft ID: detected by the FHEC
+        let mut corrupted = buf.clone();
+        corrupted[1] ^= 0x40;
+        assert!(AosFrame::from_buffer(&corrupted, &config).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn packets_from_m_pdus() -> TestResult {
+        let config = AosConfig::default();
+        let mut extractor = MPduExtractor::new();
+
+        let mut stream = VALID_SOURCE.to_vec();
+        stream.extend_from_slice(&VALID_SOURCE);
+
+        let frames = [(0, &stream[..30]), (0x07FF, &stream[30..])];
+        let mut count = 0;
+        for (idx, (fhp, zone)) in frames.iter().enumerate() {
+            let mut header = AosPrimaryHeader::new(0xAB, 1);
+            header.vc_frame_count = (0x00FF_FFFF + idx as u32) & 0x00FF_FFFF;
+            let pdu = MPdu {
+                first_header_pointer: *fhp,
+                packet_zone: zone.to_vec(),
+            };
+            let frame = AosFrame {
+                header,
+                insert_zone: None,
+                data: pdu.get_buffer(),
+                ocf: None,
+                fecf: None,
+            };
+
+            let frame = AosFrame::from_buffer(&frame.get_buffer(&config), &config)?;
+            count += extractor.push_frame(&frame)?.len();
+        }
+        assert_eq!(count, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bitstream_from_b_pdus() {
+        let mut assembler = BitstreamAssembler::new();
+
+        // 12 valid bits, then 4 + 8 valid bits
+        let pdus = [
+            BPdu {
+                data_pointer: 11,
+                data: vec![0xAB, 0xCF, 0xFF],
+            },
+            BPdu {
+                data_pointer: BDP_IDLE_DATA,
+                data: vec![0xFF; 3],
+            },
+            BPdu {
+                data_pointer: BDP_ALL_VALID,
+                data: vec![0xDE, 0xF0],
+            },
+        ];
+        for pdu in pdus.iter() {
+            let decoded = BPdu::from_buffer(&pdu.get_buffer()).unwrap();
+            assembler.push(&decoded);
+        }
+
+        assert_eq!(assembler.len(), 28);
+        assert_eq!(assembler.take_bytes(), [0xAB, 0xCD, 0xEF]);
+        assert_eq!(assembler.len(), 4);
+        assert!(!assembler.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! Communications Link Control Word (CCSDS 232.0-B), reported in the OCF of TM frames.
+
+use std::fmt;
+
+/// Protocol in effect on the virtual channel: COP-1.
+pub const COP_IN_EFFECT: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Clcw {
+    pub version_number: u8,
+    pub status_field: u8,
+    pub cop_in_effect: u8,
+    pub virtual_channel_id: u8,
+    pub no_rf_available: bool,
+    pub no_bit_lock: bool,
+    pub lockout: bool,
+    pub wait: bool,
+    pub retransmit: bool,
+    pub farm_b_counter: u8,
+    pub report_value: u8,
+}
+
+impl Clcw {
+    pub fn new(virtual_channel_id: u8) -> Clcw {
+        Clcw {
+            cop_in_effect: COP_IN_EFFECT,
+            virtual_channel_id,
+            ..Clcw::default()
+        }
+    }
+
+    /// Decodes the OCF, which is a CLCW only when its Control Word Type is `0`.
+    pub fn from_ocf(ocf: u32) -> Option<Clcw> {
+        match ocf >> 31 {
+            0 => Some(Clcw::from_word(ocf)),
+            _ => None,
+        }
+    }
+
+    pub fn from_word(val: u32) -> Clcw {
+        Clcw {
+            version_number: ((val >> 29) & 0x03) as u8,
+            status_field: ((val >> 26) & 0x07) as u8,
+            cop_in_effect: ((val >> 24) & 0x03) as u8,
+            virtual_channel_id: ((val >> 18) & 0x3F) as u8,
+            no_rf_available: (val >> 15) & 0x01 != 0,
+            no_bit_lock: (val >> 14) & 0x01 != 0,
+            lockout: (val >> 13) & 0x01 != 0,
+            wait: (val >> 12) & 0x01 != 0,
+            retransmit: (val >> 11) & 0x01 != 0,
+            farm_b_counter: ((val >> 9) & 0x03) as u8,
+            report_value: (val & 0xFF) as u8,
+        }
+    }
+
+    /// Encodes the CLCW. The Control Word Type is always `0`.
+    pub fn to_word(&self) -> u32 {
+        let mut val: u32;
+
+        val = ((self.version_number & 0x03) as u32) << 29;
+        val |= ((self.status_field & 0x07) as u32) << 26;
+        val |= ((self.cop_in_effect & 0x03) as u32) << 24;
+        val |= ((self.virtual_channel_id & 0x3F) as u32) << 18;
+        val |= (self.no_rf_available as u32) << 15;
+        val |= (self.no_bit_lock as u32) << 14;
+        val |= (self.lockout as u32) << 13;
+        val |= (self.wait as u32) << 12;
+        val |= (self.retransmit as u32) << 11;
+        val |= ((self.farm_b_counter & 0x03) as u32) << 9;
+        val |= self.report_value as u32;
+
+        val
+    }
+}
+
+impl fmt::Display for Clcw {
+    /// One-line summary for monitoring, e.g. `VC 3 N(R)=42 B=1 [wait]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "VC {} N(R)={} B={}",
+            self.virtual_channel_id, self.report_value, self.farm_b_counter
+        )?;
+
+        let flags: Vec<&str> = [
+            (self.no_rf_available, "no RF"),
+            (self.no_bit_lock, "no bit lock"),
+            (self.lockout, "lockout"),
+            (self.wait, "wait"),
+            (self.retransmit, "retransmit"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect();
+        if !flags.is_empty() {
+            write!(f, " [{}]", flags.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clcw() {
+        let clcw = Clcw::from_word(0x010C_3A2A);
+
+        assert_eq!(clcw.cop_in_effect, COP_IN_EFFECT);
+        assert_eq!(clcw.virtual_channel_id, 3);
+        assert!(!clcw.no_rf_available);
+        assert!(clcw.lockout);
+        assert!(clcw.wait);
+        assert!(clcw.retransmit);
+        assert_eq!(clcw.farm_b_counter, 1);
+        assert_eq!(clcw.report_value, 0x2A);
+
+        assert_eq!(clcw.to_word(), 0x010C_3A2A);
+        assert_eq!(
+            clcw.to_string(),
+            "VC 3 N(R)=42 B=1 [lockout, wait, retransmit]"
+        );
+    }
+
+    #[test]
+    fn type_2_reports_are_not_clcws() {
+        assert_eq!(
+            Clcw::from_ocf(0x010C_3A2A),
+            Some(Clcw::from_word(0x010C_3A2A))
+        );
+        assert_eq!(Clcw::from_ocf(0x810C_3A2A), None);
+        assert_eq!(Clcw::new(1).to_string(), "VC 1 N(R)=0 B=0");
+    }
+}
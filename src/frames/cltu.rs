@@ -0,0 +1,145 @@
+//! Communications Link Transmission Units (CCSDS 231.0-B) with BCH(63,56) codeblocks.
+
+use anyhow::{bail, Result};
+
+/// Start sequence of every CLTU.
+pub const START_SEQUENCE: [u8; 2] = [0xEB, 0x90];
+
+/// Tail sequence of every CLTU.
+pub const TAIL_SEQUENCE: [u8; 8] = [0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0x79];
+
+/// Information octets per codeblock.
+pub const INFO_SIZE: usize = 7;
+
+/// Size of a codeblock: information octets + parity octet.
+pub const CODEBLOCK_SIZE: usize = INFO_SIZE + 1;
+
+/// Pattern completing the last codeblock.
+pub const FILL_PATTERN: u8 = 0x55;
+
+/// Generator polynomial of the code: x^7 + x^6 + x^2 + 1 (leading term implied).
+const GENERATOR: u8 = 0x45;
+
+/// Parity octet of a codeblock: 7 complemented parity bits and a zero filler bit.
+pub fn parity(info: &[u8]) -> u8 {
+    let mut reg: u8 = 0;
+
+    for byte in info {
+        for bit in (0..8).rev() {
+            let feedback = ((reg >> 6) ^ (byte >> bit)) & 0x01;
+            reg = (reg << 1) & 0x7F;
+            if feedback != 0 {
+                reg ^= GENERATOR;
+            }
+        }
+    }
+
+    (!reg & 0x7F) << 1
+}
+
+/// Encodes the data (usually a TC frame) in a CLTU.
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(INFO_SIZE);
+    let mut buf =
+        Vec::with_capacity(START_SEQUENCE.len() + blocks * CODEBLOCK_SIZE + TAIL_SEQUENCE.len());
+
+    buf.extend_from_slice(&START_SEQUENCE);
+    for chunk in data.chunks(INFO_SIZE) {
+        let mut info = [FILL_PATTERN; INFO_SIZE];
+        info[..chunk.len()].copy_from_slice(chunk);
+
+        buf.extend_from_slice(&info);
+        buf.push(parity(&info));
+    }
+    buf.extend_from_slice(&TAIL_SEQUENCE);
+
+    buf
+}
+
+/// Decodes a CLTU, returning its information octets (including fill data).
+pub fn decode(buf: &[u8]) -> Result<Vec<u8>> {
+    if !buf.starts_with(&START_SEQUENCE) {
+        bail!("CLTU without start sequence");
+    }
+
+    let mut data = Vec::new();
+    let mut blocks = buf[START_SEQUENCE.len()..].chunks(CODEBLOCK_SIZE);
+    loop {
+        let block = match blocks.next() {
+            Some(block) if block == TAIL_SEQUENCE => return Ok(data),
+            Some(block) if block.len() == CODEBLOCK_SIZE => block,
+            _ => bail!("CLTU without tail sequence"),
+        };
+
+        let (info, check) = block.split_at(INFO_SIZE);
+        if parity(info) != check[0] {
+            bail!("Codeblock `{}` with invalid parity", data.len() / INFO_SIZE);
+        }
+        data.extend_from_slice(info);
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::TestResult;
+
+    // Remainder of the division of the 63-bit codeword (parity not complemented) by g(x)
+    fn remainder(info: &[u8], parity: u8) -> u64 {
+        let mut word: u64 = 0;
+        for byte in info {
+            word = (word << 8) | *byte as u64;
+        }
+        word = (word << 7) | ((!parity >> 1) & 0x7F) as u64;
+
+        let generator: u64 = 0xC5; // x^7 + x^6 + x^2 + 1
+        for shift in (0..56).rev() {
+            if word & (1 << (shift + 7)) != 0 {
+                word ^= generator << shift;
+            }
+        }
+        word
+    }
+
+    #[test]
+    fn codeblocks_are_codewords() {
+        let infos: [[u8; 7]; 3] = [
+            [0x00; 7],
+            [0xFF; 7],
+            [0x22, 0xF6, 0x00, 0xFF, 0x00, 0x42, 0x1A],
+        ];
+        for info in infos.iter() {
+            let check = parity(info);
+            assert_eq!(check & 0x01, 0);
+            assert_eq!(remainder(info, check), 0);
+        }
+
+        // All-zero information: all parity bits complemented
+        assert_eq!(parity(&[0x00; 7]), 0xFE);
+    }
+
+    #[test]
+    fn cltu_round_trip() -> TestResult {
+        let data: Vec<u8> = (0..10).collect();
+        let cltu = encode(&data);
+
+        assert_eq!(cltu.len(), 2 + 2 * 8 + 8);
+        assert_eq!(cltu[2 + 8 + 3], FILL_PATTERN);
+
+        let decoded = decode(&cltu)?;
+        assert_eq!(&decoded[..10], &data[..]);
+        assert_eq!(decoded.len(), 14);
+
+        let mut corrupted = cltu.clone();
+        corrupted[4] ^= 0x08;
+        assert!(decode(&corrupted).is_err());
+        assert!(decode(&cltu[..cltu.len() - 1]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+//! Frame Acceptance and Reporting Mechanism (FARM-1), receiving side of COP-1.
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, Result};
+use log::{debug, warn};
+
+use super::ControlCommand;
+use crate::frames::clcw::Clcw;
+use crate::frames::tc::TcFrame;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FarmState {
+    Open,
+    Wait,
+    Lockout,
+}
+
+/// Outcome of the reception of a frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FarmAction {
+    Accept,
+    Discard,
+}
+
+pub struct Farm {
+    virtual_channel_id: u8,
+    state: FarmState,
+    // Receiver Frame Sequence Number: V(R)
+    receiver_seq: u8,
+    positive_window: u8,
+    negative_window: u8,
+    retransmit: bool,
+    farm_b_counter: u8,
+    buffer: VecDeque<TcFrame>,
+    buffer_capacity: usize,
+}
+
+impl Farm {
+    /// FARM of the virtual channel, with a sliding window of `window_width` frames
+    /// (even, from 2 to 254) and room for `buffer_capacity` accepted frames.
+    pub fn new(virtual_channel_id: u8, window_width: u8, buffer_capacity: usize) -> Result<Farm> {
+        if !(2..=254).contains(&window_width) || !window_width.is_multiple_of(2) {
+            bail!(
+                "FARM window width `{}` is not even, from 2 to 254",
+                window_width
+            );
+        }
+
+        Ok(Farm {
+            virtual_channel_id,
+            state: FarmState::Open,
+            receiver_seq: 0,
+            positive_window: window_width / 2,
+            negative_window: window_width / 2,
+            retransmit: false,
+            farm_b_counter: 0,
+            buffer: VecDeque::new(),
+            buffer_capacity,
+        })
+    }
+
+    pub fn state(&self) -> FarmState {
+        self.state
+    }
+
+    /// Report of the current state, to be sent in the OCF of the TM frames.
+    pub fn clcw(&self) -> Clcw {
+        Clcw {
+            lockout: self.state == FarmState::Lockout,
+            wait: self.state == FarmState::Wait,
+            retransmit: self.retransmit,
+            farm_b_counter: self.farm_b_counter,
+            report_value: self.receiver_seq,
+            ..Clcw::new(self.virtual_channel_id)
+        }
+    }
+
+    /// Accepted frame, to be delivered to the higher layers (frees its buffer space).
+    pub fn pop(&mut self) -> Option<TcFrame> {
+        let frame = self.buffer.pop_front();
+        if frame.is_some() && self.state == FarmState::Wait {
+            debug!("Buffer released: leaving the wait state");
+            self.state = FarmState::Open;
+        }
+        frame
+    }
+
+    pub fn receive(&mut self, frame: TcFrame) -> FarmAction {
+        if frame.header.virtual_channel_id != self.virtual_channel_id {
+            warn!(
+                "Discarding frame of virtual channel `{}`",
+                frame.header.virtual_channel_id
+            );
+            return FarmAction::Discard;
+        }
+
+        match (frame.header.bypass_flag, frame.header.control_command_flag) {
+            (false, false) => self.receive_ad(frame),
+            (true, false) => self.receive_bd(frame),
+            (true, true) => self.receive_bc(&frame),
+            (false, true) => {
+                warn!("Discarding frame with invalid bypass/control command flags");
+                FarmAction::Discard
+            }
+        }
+    }
+
+    fn receive_ad(&mut self, frame: TcFrame) -> FarmAction {
+        let seq = frame.header.frame_sequence_number;
+        let ahead = seq.wrapping_sub(self.receiver_seq);
+        let behind = self.receiver_seq.wrapping_sub(seq);
+
+        if self.state == FarmState::Lockout {
+            return FarmAction::Discard;
+        }
+
+        if ahead == 0 {
+            if self.state == FarmState::Wait || self.is_full() {
+                self.state = FarmState::Wait;
+                self.retransmit = true;
+                return FarmAction::Discard;
+            }
+            self.buffer.push_back(frame);
+            self.receiver_seq = self.receiver_seq.wrapping_add(1);
+            self.retransmit = false;
+            return FarmAction::Accept;
+        }
+
+        if ahead < self.positive_window {
+            // Frames were lost: ask for retransmission
+            self.retransmit = true;
+        } else if behind > self.negative_window {
+            warn!("Frame `{}` outside the FARM window: lockout", seq);
+            self.state = FarmState::Lockout;
+        }
+        FarmAction::Discard
+    }
+
+    fn receive_bd(&mut self, frame: TcFrame) -> FarmAction {
+        if self.is_full() {
+            return FarmAction::Discard;
+        }
+        self.buffer.push_back(frame);
+        self.farm_b_counter = (self.farm_b_counter + 1) & 0x03;
+        FarmAction::Accept
+    }
+
+    fn receive_bc(&mut self, frame: &TcFrame) -> FarmAction {
+        let command = match ControlCommand::from_buffer(&frame.data) {
+            Some(command) => command,
+            None => {
+                warn!("Discarding invalid control command `{:?}`", frame.data);
+                return FarmAction::Discard;
+            }
+        };
+
+        match command {
+            ControlCommand::Unlock => {
+                self.state = FarmState::Open;
+                self.retransmit = false;
+            }
+            ControlCommand::SetVr(receiver_seq) => {
+                if self.state != FarmState::Lockout {
+                    self.state = FarmState::Open;
+                    self.receiver_seq = receiver_seq;
+                    self.retransmit = false;
+                }
+            }
+        }
+        self.farm_b_counter = (self.farm_b_counter + 1) & 0x03;
+        FarmAction::Accept
+    }
+
+    fn is_full(&self) -> bool {
+        self.buffer.len() >= self.buffer_capacity
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::frames::tc::TcPrimaryHeader;
+    use crate::test_utils::TestResult;
+
+    fn ad_frame(seq: u8) -> TcFrame {
+        let mut header = TcPrimaryHeader::new(0x0C8, 1);
+        header.frame_sequence_number = seq;
+        TcFrame {
+            header,
+            segment_header: None,
+            data: vec![seq],
+            fecf: None,
+        }
+    }
+
+    fn bc_frame(command: ControlCommand) -> TcFrame {
+        let mut header = TcPrimaryHeader::new(0x0C8, 1);
+        header.bypass_flag = true;
+        header.control_command_flag = true;
+        TcFrame {
+            header,
+            segment_header: None,
+            data: command.get_buffer(),
+            fecf: None,
+        }
+    }
+
+    /// FARM expecting the frame `receiver_seq`, with a window of 5 frames on each side.
+    fn farm_at(receiver_seq: u8) -> Result<Farm> {
+        let mut farm = Farm::new(1, 10, 16)?;
+        assert_eq!(
+            farm.receive(bc_frame(ControlCommand::SetVr(receiver_seq))),
+            FarmAction::Accept
+        );
+        Ok(farm)
+    }
+
+    #[test]
+    fn window_widths() {
+        assert!(Farm::new(1, 2, 1).is_ok());
+        assert!(Farm::new(1, 254, 1).is_ok());
+        assert!(Farm::new(1, 0, 1).is_err());
+        assert!(Farm::new(1, 9, 1).is_err());
+        assert!(Farm::new(1, 255, 1).is_err());
+    }
+
+    #[test]
+    fn frames_in_sequence() -> TestResult {
+        let mut farm = farm_at(0xFE)?;
+
+        assert_eq!(farm.receive(ad_frame(0xFE)), FarmAction::Accept);
+        assert_eq!(farm.receive(ad_frame(0xFF)), FarmAction::Accept);
+        assert_eq!(farm.receive(ad_frame(0x00)), FarmAction::Accept);
+        assert_eq!(farm.clcw().report_value, 0x01);
+        assert_eq!(farm.state(), FarmState::Open);
+
+        // Frames of other virtual channels are ignored
+        let mut frame = ad_frame(0x01);
+        frame.header.virtual_channel_id = 2;
+        assert_eq!(farm.receive(frame), FarmAction::Discard);
+        assert_eq!(farm.clcw().report_value, 0x01);
+
+        let data: Vec<u8> = std::iter::from_fn(|| farm.pop())
+            .map(|frame| frame.data[0])
+            .collect();
+        assert_eq!(data, [0xFE, 0xFF, 0x00]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn retransmit_after_gap() -> TestResult {
+        let mut farm = farm_at(10)?;
+
+        // Within the positive window: frames were lost
+        assert_eq!(farm.receive(ad_frame(14)), FarmAction::Discard);
+        assert!(farm.clcw().retransmit);
+        assert_eq!(farm.state(), FarmState::Open);
+
+        // Expected frame: accepted, retransmission no longer needed
+        assert_eq!(farm.receive(ad_frame(10)), FarmAction::Accept);
+        assert!(!farm.clcw().retransmit);
+
+        Ok(())
+    }
+
+    #[test]
+    fn window_edges() -> TestResult {
+        // Last frame of the negative window: a duplicate, silently discarded
+        let mut farm = farm_at(10)?;
+        assert_eq!(farm.receive(ad_frame(5)), FarmAction::Discard);
+        assert_eq!(farm.state(), FarmState::Open);
+        assert!(!farm.clcw().retransmit);
+
+        // Beyond the negative window
+        assert_eq!(farm.receive(ad_frame(4)), FarmAction::Discard);
+        assert_eq!(farm.state(), FarmState::Lockout);
+
+        // First frame beyond the positive window
+        let mut farm = farm_at(10)?;
+        assert_eq!(farm.receive(ad_frame(15)), FarmAction::Discard);
+        assert_eq!(farm.state(), FarmState::Lockout);
+        assert!(farm.clcw().lockout);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lockout_until_unlock() -> TestResult {
+        let mut farm = farm_at(0)?;
+        farm.receive(ad_frame(100));
+        assert_eq!(farm.state(), FarmState::Lockout);
+
+        // Neither the expected frame nor SET V(R) leave the lockout
+        assert_eq!(farm.receive(ad_frame(0)), FarmAction::Discard);
+        farm.receive(bc_frame(ControlCommand::SetVr(100)));
+        assert_eq!(farm.state(), FarmState::Lockout);
+        assert_eq!(farm.clcw().report_value, 0);
+
+        assert_eq!(
+            farm.receive(bc_frame(ControlCommand::Unlock)),
+            FarmAction::Accept
+        );
+        assert_eq!(farm.state(), FarmState::Open);
+        assert_eq!(farm.receive(ad_frame(0)), FarmAction::Accept);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wait_while_buffer_is_full() -> TestResult {
+        let mut farm = Farm::new(1, 10, 1)?;
+
+        assert_eq!(farm.receive(ad_frame(0)), FarmAction::Accept);
+        assert_eq!(farm.receive(ad_frame(1)), FarmAction::Discard);
+        assert_eq!(farm.state(), FarmState::Wait);
+        assert!(farm.clcw().wait);
+        assert!(farm.clcw().retransmit);
+
+        // Delivering the accepted frame frees the buffer
+        assert_eq!(farm.pop().map(|frame| frame.data), Some(vec![0]));
+        assert_eq!(farm.state(), FarmState::Open);
+        assert_eq!(farm.receive(ad_frame(1)), FarmAction::Accept);
+        assert!(!farm.clcw().retransmit);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,524 @@
+//! Frame Operation Procedure (FOP-1), sending side of COP-1.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::{debug, warn};
+
+use super::ControlCommand;
+use crate::frames::clcw::Clcw;
+use crate::frames::tc::{SegmentHeader, TcFrame, TcPrimaryHeader};
+
+/// Managed parameters of the FOP.
+#[derive(Clone, Debug)]
+pub struct FopConfig {
+    pub spacecraft_id: u16,
+    pub virtual_channel_id: u8,
+    /// MAP of the frames, if the virtual channel uses segment headers.
+    pub map_id: Option<u8>,
+    pub has_fecf: bool,
+    /// Max number of frames sent but not yet acknowledged (K).
+    pub window_width: u8,
+    /// Initial value of the retransmission timer (T1).
+    pub timer_initial: Duration,
+    /// Max number of transmissions of each frame.
+    pub transmission_limit: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FopState {
+    Active,
+    RetransmitWithoutWait,
+    RetransmitWithWait,
+    InitialisingWithoutBc,
+    InitialisingWithBc,
+    Initial,
+}
+
+/// How the service is initiated (from the initial state).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Initiation {
+    WithoutClcwCheck,
+    WithClcwCheck,
+    WithUnlock,
+    WithSetVr(u8),
+}
+
+/// Reason of the termination of the service.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FopAlert {
+    /// The FARM is in lockout.
+    Lockout,
+    /// The transmission limit was reached.
+    Limit,
+    /// The retransmission timer expired while initialising.
+    T1,
+    /// The CLCW acknowledges frames which were not sent.
+    NnR,
+    /// The service was terminated by the user.
+    Term,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FopEvent {
+    /// Frame to be sent to the spacecraft.
+    Transmit(TcFrame),
+    /// Data of the frame with the given sequence number was accepted by the FARM.
+    Accepted(u8),
+    /// The service is initiated: data can be sent.
+    Initialised,
+    /// The service was terminated: back to the initial state.
+    Alert(FopAlert),
+}
+
+pub struct Fop {
+    config: FopConfig,
+    state: FopState,
+    // Transmitter Frame Sequence Number: V(S)
+    sender_seq: u8,
+    // Expected Acknowledgement Frame Sequence Number: NN(R)
+    expected_ack: u8,
+    sent: VecDeque<TcFrame>,
+    waiting: Option<Vec<u8>>,
+    bc_frame: Option<TcFrame>,
+    transmission_count: u32,
+    deadline: Option<Duration>,
+}
+
+impl Fop {
+    pub fn new(config: FopConfig) -> Fop {
+        Fop {
+            config,
+            state: FopState::Initial,
+            sender_seq: 0,
+            expected_ack: 0,
+            sent: VecDeque::new(),
+            waiting: None,
+            bc_frame: None,
+            transmission_count: 0,
+            deadline: None,
+        }
+    }
+
+    pub fn state(&self) -> FopState {
+        self.state
+    }
+
+    /// Number of frames sent but not yet acknowledged.
+    pub fn outstanding(&self) -> usize {
+        self.sent.len()
+    }
+
+    /// Time at which `tick` should be called, if the timer is running.
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    pub fn initiate(&mut self, initiation: Initiation, now: Duration) -> Result<Vec<FopEvent>> {
+        if self.state != FopState::Initial {
+            bail!("Service already initiated: state `{:?}`", self.state);
+        }
+        self.purge();
+
+        let events = match initiation {
+            Initiation::WithoutClcwCheck => {
+                self.state = FopState::Active;
+                vec![FopEvent::Initialised]
+            }
+            Initiation::WithClcwCheck => {
+                self.state = FopState::InitialisingWithoutBc;
+                self.start_timer(now);
+                Vec::new()
+            }
+            Initiation::WithUnlock => self.send_control(ControlCommand::Unlock, now),
+            Initiation::WithSetVr(seq) => {
+                self.sender_seq = seq;
+                self.expected_ack = seq;
+                self.send_control(ControlCommand::SetVr(seq), now)
+            }
+        };
+
+        Ok(events)
+    }
+
+    pub fn terminate(&mut self) -> Vec<FopEvent> {
+        self.alert(FopAlert::Term)
+    }
+
+    /// Sends the data in a type-AD frame, as soon as the sliding window allows it.
+    ///
+    /// Only one data unit can be waiting for the window to open: the next ones are
+    /// rejected until it is sent.
+    pub fn send(&mut self, data: Vec<u8>, now: Duration) -> Result<Vec<FopEvent>> {
+        match self.state {
+            FopState::Active | FopState::RetransmitWithoutWait | FopState::RetransmitWithWait => {}
+            state => bail!("Service not initiated: state `{:?}`", state),
+        }
+        if self.waiting.is_some() {
+            bail!("Data rejected: the FOP is busy");
+        }
+
+        self.waiting = Some(data);
+        Ok(self.send_waiting(now))
+    }
+
+    pub fn receive_clcw(&mut self, clcw: &Clcw, now: Duration) -> Vec<FopEvent> {
+        if clcw.virtual_channel_id != self.config.virtual_channel_id
+            || self.state == FopState::Initial
+        {
+            return Vec::new();
+        }
+
+        if clcw.lockout {
+            return self.alert(FopAlert::Lockout);
+        }
+
+        let ack = clcw.report_value;
+        if ack.wrapping_sub(self.expected_ack) > self.sender_seq.wrapping_sub(self.expected_ack) {
+            warn!("CLCW report value `{}` was never sent", ack);
+            return self.alert(FopAlert::NnR);
+        }
+
+        match self.state {
+            FopState::InitialisingWithoutBc | FopState::InitialisingWithBc => {
+                if !clcw.retransmit && !clcw.wait && ack == self.sender_seq {
+                    self.state = FopState::Active;
+                    self.bc_frame = None;
+                    self.deadline = None;
+                    return vec![FopEvent::Initialised];
+                }
+                return Vec::new();
+            }
+            _ => {}
+        }
+
+        // Removing the acknowledged frames from the sent queue
+        let mut events = Vec::new();
+        while self.expected_ack != ack {
+            self.sent.pop_front();
+            events.push(FopEvent::Accepted(self.expected_ack));
+            self.expected_ack = self.expected_ack.wrapping_add(1);
+        }
+        let acknowledged = !events.is_empty();
+        if acknowledged {
+            self.transmission_count = 1;
+            if self.sent.is_empty() {
+                self.deadline = None;
+            } else {
+                self.start_timer(now);
+            }
+        }
+
+        if clcw.retransmit && !self.sent.is_empty() {
+            if clcw.wait {
+                self.state = FopState::RetransmitWithWait;
+            } else if acknowledged || self.state != FopState::RetransmitWithoutWait {
+                // Each retransmission request is only served once: then the timer rules
+                if self.transmission_count >= self.config.transmission_limit {
+                    events.append(&mut self.alert(FopAlert::Limit));
+                    return events;
+                }
+                events.append(&mut self.retransmit(now));
+            }
+        } else {
+            self.state = FopState::Active;
+        }
+
+        events.append(&mut self.send_waiting(now));
+        events
+    }
+
+    /// Handles the expiry of the retransmission timer, if `now` is past its deadline.
+    pub fn tick(&mut self, now: Duration) -> Vec<FopEvent> {
+        match self.deadline {
+            Some(deadline) if deadline <= now => {}
+            _ => return Vec::new(),
+        }
+
+        if self.transmission_count >= self.config.transmission_limit {
+            let alert = match self.state {
+                FopState::InitialisingWithoutBc | FopState::InitialisingWithBc => FopAlert::T1,
+                _ => FopAlert::Limit,
+            };
+            return self.alert(alert);
+        }
+
+        match self.state {
+            FopState::InitialisingWithoutBc => self.alert(FopAlert::T1),
+            FopState::InitialisingWithBc => {
+                debug!("Retransmitting the control command");
+                self.transmission_count += 1;
+                self.start_timer(now);
+                let frame = self
+                    .bc_frame
+                    .clone()
+                    .expect("Initialising: should have a BC frame");
+                vec![FopEvent::Transmit(frame)]
+            }
+            FopState::RetransmitWithWait => {
+                self.transmission_count += 1;
+                self.start_timer(now);
+                Vec::new()
+            }
+            FopState::Active | FopState::RetransmitWithoutWait => self.retransmit(now),
+            FopState::Initial => Vec::new(),
+        }
+    }
+
+    fn send_waiting(&mut self, now: Duration) -> Vec<FopEvent> {
+        if self.state != FopState::Active || self.sent.len() >= self.config.window_width as usize {
+            return Vec::new();
+        }
+        let data = match self.waiting.take() {
+            Some(data) => data,
+            None => return Vec::new(),
+        };
+
+        let mut header = self.header();
+        header.frame_sequence_number = self.sender_seq;
+        self.sender_seq = self.sender_seq.wrapping_add(1);
+
+        let frame = TcFrame {
+            header,
+            segment_header: self.config.map_id.map(SegmentHeader::new),
+            data,
+            fecf: self.fecf(),
+        };
+
+        if self.sent.is_empty() {
+            self.transmission_count = 1;
+            self.start_timer(now);
+        }
+        self.sent.push_back(frame.clone());
+
+        vec![FopEvent::Transmit(frame)]
+    }
+
+    fn send_control(&mut self, command: ControlCommand, now: Duration) -> Vec<FopEvent> {
+        let mut header = self.header();
+        header.bypass_flag = true;
+        header.control_command_flag = true;
+
+        let frame = TcFrame {
+            header,
+            segment_header: None,
+            data: command.get_buffer(),
+            fecf: self.fecf(),
+        };
+
+        self.state = FopState::InitialisingWithBc;
+        self.bc_frame = Some(frame.clone());
+        self.transmission_count = 1;
+        self.start_timer(now);
+
+        vec![FopEvent::Transmit(frame)]
+    }
+
+    fn retransmit(&mut self, now: Duration) -> Vec<FopEvent> {
+        debug!("Retransmitting `{}` frame(s)", self.sent.len());
+        self.state = FopState::RetransmitWithoutWait;
+        self.transmission_count += 1;
+        self.start_timer(now);
+
+        self.sent
+            .iter()
+            .map(|frame| FopEvent::Transmit(frame.clone()))
+            .collect()
+    }
+
+    fn alert(&mut self, alert: FopAlert) -> Vec<FopEvent> {
+        warn!("FOP alert: `{:?}`", alert);
+        self.purge();
+        self.state = FopState::Initial;
+        vec![FopEvent::Alert(alert)]
+    }
+
+    fn purge(&mut self) {
+        self.sent.clear();
+        self.waiting = None;
+        self.bc_frame = None;
+        self.deadline = None;
+        self.expected_ack = self.sender_seq;
+    }
+
+    fn start_timer(&mut self, now: Duration) {
+        self.deadline = Some(now + self.config.timer_initial);
+    }
+
+    fn header(&self) -> TcPrimaryHeader {
+        TcPrimaryHeader::new(self.config.spacecraft_id, self.config.virtual_channel_id)
+    }
+
+    fn fecf(&self) -> Option<u16> {
+        if self.config.has_fecf {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::frames::cop1::{Farm, FarmAction};
+    use crate::test_utils::TestResult;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn config() -> FopConfig {
+        FopConfig {
+            spacecraft_id: 0x0C8,
+            virtual_channel_id: 1,
+            map_id: Some(0),
+            has_fecf: true,
+            window_width: 4,
+            timer_initial: 5 * SECOND,
+            transmission_limit: 3,
+        }
+    }
+
+    // Sends the frames through the link (encoding and decoding), except the dropped ones
+    fn uplink(events: Vec<FopEvent>, farm: &mut Farm, drop: &[u8]) -> Result<Vec<u8>> {
+        let mut accepted = Vec::new();
+        for event in events {
+            if let FopEvent::Transmit(frame) = event {
+                let seq = frame.header.frame_sequence_number;
+                if drop.contains(&seq) && !frame.header.bypass_flag {
+                    continue;
+                }
+                let frame = TcFrame::from_buffer(&frame.get_buffer()?, true, true)?;
+                if farm.receive(frame) == FarmAction::Accept {
+                    accepted.push(seq);
+                }
+            }
+        }
+        Ok(accepted)
+    }
+
+    #[test]
+    fn retransmission_after_lost_frame() -> TestResult {
+        let mut fop = Fop::new(config());
+        let mut farm = Farm::new(1, 10, 16)?;
+
+        let events = fop.initiate(Initiation::WithoutClcwCheck, Duration::from_secs(0))?;
+        assert_eq!(events, [FopEvent::Initialised]);
+
+        let mut accepted = Vec::new();
+        for idx in 0..3 {
+            let events = fop.send(vec![idx], Duration::from_secs(0))?;
+            accepted.append(&mut uplink(events, &mut farm, &[1])?);
+        }
+        assert_eq!(accepted, [0]);
+        assert!(farm.clcw().retransmit);
+
+        let events = fop.receive_clcw(&farm.clcw(), SECOND);
+        assert_eq!(events[0], FopEvent::Accepted(0));
+        assert_eq!(fop.state(), FopState::RetransmitWithoutWait);
+        assert_eq!(uplink(events, &mut farm, &[])?, [1, 2]);
+
+        let events = fop.receive_clcw(&farm.clcw(), 2 * SECOND);
+        assert_eq!(events, [FopEvent::Accepted(1), FopEvent::Accepted(2)]);
+        assert_eq!(fop.state(), FopState::Active);
+        assert_eq!(fop.outstanding(), 0);
+        assert_eq!(fop.deadline(), None);
+
+        let delivered: Vec<u8> = std::iter::from_fn(|| farm.pop())
+            .map(|frame| frame.data[0])
+            .collect();
+        assert_eq!(delivered, [0, 1, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn timer_expiry_until_limit() -> TestResult {
+        let mut fop = Fop::new(config());
+        fop.initiate(Initiation::WithoutClcwCheck, Duration::from_secs(0))?;
+
+        let events = fop.send(vec![0], Duration::from_secs(0))?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(fop.deadline(), Some(5 * SECOND));
+
+        assert!(fop.tick(4 * SECOND).is_empty());
+        assert_eq!(fop.tick(5 * SECOND).len(), 1); // 2nd transmission
+        assert_eq!(fop.tick(10 * SECOND).len(), 1); // 3rd transmission
+        assert_eq!(fop.tick(15 * SECOND), [FopEvent::Alert(FopAlert::Limit)]);
+        assert_eq!(fop.state(), FopState::Initial);
+        assert!(fop.send(vec![1], 15 * SECOND).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn initiation_with_unlock_and_set_vr() -> TestResult {
+        let mut fop = Fop::new(config());
+        let mut farm = Farm::new(1, 10, 16)?;
+
+        // Frame far outside the window: lockout
+        let mut header = TcPrimaryHeader::new(0x0C8, 1);
+        header.frame_sequence_number = 100;
+        let frame = TcFrame {
+            header,
+            segment_header: None,
+            data: vec![0],
+            fecf: None,
+        };
+        assert_eq!(farm.receive(frame), FarmAction::Discard);
+        assert!(farm.clcw().lockout);
+
+        let events = fop.initiate(Initiation::WithUnlock, Duration::from_secs(0))?;
+        assert_eq!(uplink(events, &mut farm, &[])?, [0]);
+        assert!(!farm.clcw().lockout);
+        assert_eq!(farm.clcw().farm_b_counter, 1);
+        assert_eq!(
+            fop.receive_clcw(&farm.clcw(), SECOND),
+            [FopEvent::Initialised]
+        );
+
+        fop.terminate();
+        let events = fop.initiate(Initiation::WithSetVr(42), SECOND)?;
+        uplink(events, &mut farm, &[])?;
+        assert_eq!(farm.clcw().report_value, 42);
+        assert_eq!(
+            fop.receive_clcw(&farm.clcw(), SECOND),
+            [FopEvent::Initialised]
+        );
+
+        let events = fop.send(vec![7], SECOND)?;
+        assert_eq!(uplink(events, &mut farm, &[])?, [42]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn farm_wait_when_buffer_is_full() -> TestResult {
+        let mut fop = Fop::new(config());
+        let mut farm = Farm::new(1, 10, 1)?;
+        fop.initiate(Initiation::WithoutClcwCheck, Duration::from_secs(0))?;
+
+        let mut accepted = Vec::new();
+        for idx in 0..2 {
+            let events = fop.send(vec![idx], Duration::from_secs(0))?;
+            accepted.append(&mut uplink(events, &mut farm, &[])?);
+        }
+        assert_eq!(accepted, [0]);
+        assert!(farm.clcw().wait);
+
+        fop.receive_clcw(&farm.clcw(), SECOND);
+        assert_eq!(fop.state(), FopState::RetransmitWithWait);
+
+        // Buffer released: the FARM is open again and asks for retransmission
+        farm.pop();
+        let events = fop.receive_clcw(&farm.clcw(), 2 * SECOND);
+        assert_eq!(uplink(events, &mut farm, &[])?, [1]);
+
+        Ok(())
+    }
+}
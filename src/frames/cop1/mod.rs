@@ -0,0 +1,36 @@
+//! Communications Operation Procedure-1 (CCSDS 232.1-B) for reliable TC uplink.
+//!
+//! Both sides are pure state machines: they never read a clock, the current time is
+//! given by the caller, so they can be driven deterministically.
+
+// Reachable modules
+pub mod farm;
+pub mod fop;
+
+// Re-exporting
+pub use farm::{Farm, FarmAction, FarmState};
+pub use fop::{Fop, FopAlert, FopConfig, FopEvent, FopState, Initiation};
+
+/// Control commands carried by type-BC frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlCommand {
+    Unlock,
+    SetVr(u8),
+}
+
+impl ControlCommand {
+    pub fn from_buffer(buf: &[u8]) -> Option<ControlCommand> {
+        match buf {
+            [0x00] => Some(ControlCommand::Unlock),
+            [0x82, 0x00, receiver_seq] => Some(ControlCommand::SetVr(*receiver_seq)),
+            _ => None,
+        }
+    }
+
+    pub fn get_buffer(&self) -> Vec<u8> {
+        match self {
+            ControlCommand::Unlock => vec![0x00],
+            ControlCommand::SetVr(receiver_seq) => vec![0x82, 0x00, *receiver_seq],
+        }
+    }
+}
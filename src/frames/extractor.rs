@@ -0,0 +1,233 @@
+//! Extraction of space packets from the data field of consecutive frames.
+
+use std::collections::HashMap;
+
+use log::warn;
+
+use super::tm::{TmFrame, FHP_IDLE_DATA, FHP_NO_PACKET_START};
+use crate::protocol::HEADER_SIZE;
+use crate::protocol::{Packet, PrimaryHeader, IDLE_APID};
+
+/// Modulus of the virtual channel frame count of TM frames (8 bits).
+pub const TM_COUNTER_MODULUS: u32 = 256;
+
+/// Packet extractor of a single virtual channel.
+pub struct VcPacketExtractor {
+    counter_modulus: u32,
+    last_count: Option<u32>,
+    buffer: Vec<u8>,
+    // Whether `buffer` starts at the beginning of a packet
+    synced: bool,
+}
+
+impl VcPacketExtractor {
+    pub fn new(counter_modulus: u32) -> VcPacketExtractor {
+        VcPacketExtractor {
+            counter_modulus,
+            last_count: None,
+            buffer: Vec::new(),
+            synced: false,
+        }
+    }
+
+    /// Consumes the data field of the next frame of the channel.
+    ///
+    /// `count` is the virtual channel frame count and `fhp` the first header pointer
+    /// of the frame. Returns the (non-idle) packets completed by this frame.
+    pub fn push(&mut self, count: u32, fhp: u16, data: &[u8]) -> Vec<Packet> {
+        if let Some(last) = self.last_count {
+            let expected = (last + 1) % self.counter_modulus;
+            if count != expected {
+                warn!("Frame count gap: expected `{}`, got `{}`", expected, count);
+                self.resync();
+            }
+        }
+        self.last_count = Some(count);
+
+        match fhp {
+            FHP_IDLE_DATA => return Vec::new(),
+            FHP_NO_PACKET_START => {
+                if self.synced {
+                    self.buffer.extend_from_slice(data);
+                }
+                return self.extract();
+            }
+            _ => {}
+        }
+
+        let fhp = fhp as usize;
+        if fhp >= data.len() {
+            warn!(
+                "First header pointer `{}` beyond data field of size `{}`",
+                fhp,
+                data.len()
+            );
+            self.resync();
+            return Vec::new();
+        }
+
+        // Finishing the packet which spans from the previous frames
+        let mut packets = Vec::new();
+        if self.synced {
+            self.buffer.extend_from_slice(&data[..fhp]);
+            packets = self.extract();
+            if !self.buffer.is_empty() {
+                warn!(
+                    "Discarding `{}` bytes inconsistent with the first header pointer",
+                    self.buffer.len()
+                );
+            }
+        }
+
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&data[fhp..]);
+        self.synced = true;
+        packets.append(&mut self.extract());
+
+        packets
+    }
+
+    /// Drops any partial packet: the next packet start is located with the pointer.
+    fn resync(&mut self) {
+        self.buffer.clear();
+        self.synced = false;
+    }
+
+    fn extract(&mut self) -> Vec<Packet> {
+        let mut packets = Vec::new();
+        let mut start = 0;
+
+        while self.buffer.len() - start >= HEADER_SIZE {
+            let header = &self.buffer[start..start + HEADER_SIZE];
+            let data_len = PrimaryHeader::from_buffer(header).data_field_size();
+            let end = start + HEADER_SIZE + data_len;
+            if self.buffer.len() < end {
+                break;
+            }
+
+            let apid = u16::from_be_bytes([header[0], header[1]]) & IDLE_APID;
+            if apid != IDLE_APID {
+                match Packet::try_from_buffers(header, &self.buffer[start + HEADER_SIZE..end]) {
+                    Ok(pkt) => packets.push(pkt),
+                    Err(e) => warn!("Discarding packet: {}", e),
+                }
+            }
+            start = end;
+        }
+
+        self.buffer.drain(..start);
+        packets
+    }
+}
+
+/// Packet extractor demultiplexing TM frames by virtual channel.
+#[derive(Default)]
+pub struct PacketExtractor {
+    channels: HashMap<u8, VcPacketExtractor>,
+}
+
+impl PacketExtractor {
+    pub fn new() -> PacketExtractor {
+        PacketExtractor::default()
+    }
+
+    pub fn push_frame(&mut self, frame: &TmFrame) -> Vec<Packet> {
+        if frame.header.sync_flag {
+            warn!("Ignoring frame with synchronous flag set: no packet boundaries");
+            return Vec::new();
+        }
+
+        let extractor = self
+            .channels
+            .entry(frame.header.virtual_channel_id)
+            .or_insert_with(|| VcPacketExtractor::new(TM_COUNTER_MODULUS));
+
+        extractor.push(
+            frame.header.vc_frame_count as u32,
+            frame.header.first_header_pointer,
+            &frame.data,
+        )
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::frames::tm::TmPrimaryHeader;
+    use crate::test_utils::{VALID_SOURCE as SP1, VALID_TC_SOURCE as SP2};
+
+    fn frame(vc_frame_count: u8, fhp: u16, data: &[u8]) -> TmFrame {
+        let mut header = TmPrimaryHeader::new(0x0C8, 1);
+        header.vc_frame_count = vc_frame_count;
+        header.first_header_pointer = fhp;
+
+        TmFrame {
+            header,
+            sec_header: None,
+            data: data.to_vec(),
+            ocf: None,
+            fecf: None,
+        }
+    }
+
+    fn stream() -> Vec<u8> {
+        let mut stream = SP1.to_vec();
+        stream.extend_from_slice(&SP2);
+        stream.extend_from_slice(&SP1);
+        stream
+    }
+
+    #[test]
+    fn packets_spanning_frames() {
+        let stream = stream();
+        let mut extractor = PacketExtractor::new();
+
+        // SP1 + SP2[0..4] | SP2[4..] + SP1[0..9] | SP1[9..]
+        let pkts = extractor.push_frame(&frame(0, 0, &stream[0..26]));
+        assert_eq!(pkts.len(), 1);
+        assert_eq!(pkts[0].pri_header.apid, 0x0073);
+
+        let pkts = extractor.push_frame(&frame(1, 7, &stream[26..42]));
+        assert_eq!(pkts.len(), 1);
+        assert_eq!(pkts[0].pri_header.apid, 0x0754);
+
+        let pkts = extractor.push_frame(&frame(2, FHP_NO_PACKET_START, &stream[42..]));
+        assert_eq!(pkts.len(), 1);
+        assert_eq!(pkts[0].pri_header.apid, 0x0073);
+    }
+
+    #[test]
+    fn frame_count_gap_discards_partial_packet() {
+        let stream = stream();
+        let mut extractor = PacketExtractor::new();
+
+        extractor.push_frame(&frame(0, 0, &stream[0..26]));
+        // Frame 1 lost: the tail of SP2 must not be glued to the next frame
+        let pkts = extractor.push_frame(&frame(2, 0, &SP1));
+        assert_eq!(pkts.len(), 1);
+        assert_eq!(pkts[0].pri_header.apid, 0x0073);
+
+        let pkts = extractor.push_frame(&frame(3, FHP_IDLE_DATA, &[0x55; 8]));
+        assert!(pkts.is_empty());
+    }
+
+    #[test]
+    fn idle_packets_are_dropped() {
+        // Idle packet: APID 0x7FF, data length 1 (checksum only)
+        let mut idle = vec![0x07, 0xFF, 0xC0, 0x00, 0x00, 0x01];
+        crate::protocol::hasher::append_checksum(&mut idle);
+
+        let mut data = SP2.to_vec();
+        data.extend_from_slice(&idle);
+
+        let mut extractor = PacketExtractor::new();
+        let pkts = extractor.push_frame(&frame(0, 0, &data));
+        assert_eq!(pkts.len(), 1);
+        assert_eq!(pkts[0].pri_header.apid, 0x0754);
+    }
+}
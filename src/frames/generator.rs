@@ -0,0 +1,393 @@
+//! Packing of space packets into fixed-length TM frames (transmit side).
+
+use std::collections::VecDeque;
+
+use anyhow::{bail, Result};
+
+use super::tm::{
+    TmFrame, TmPrimaryHeader, FECF_SIZE, FHP_IDLE_DATA, FHP_NO_PACKET_START, PRIMARY_HEADER_SIZE,
+};
+use crate::protocol::{Packet, PktType, PrimaryHeader, UserDataField, HEADER_SIZE, IDLE_APID};
+
+/// Max size of a frame. First header pointers of 0x7FE and above are reserved, so every
+/// octet of the data field of such a frame can be addressed.
+pub const MAX_FRAME_LENGTH: usize = 2048;
+
+/// Smallest idle packet: primary header + checksum.
+pub const MIN_IDLE_PACKET_SIZE: usize = 8;
+
+/// Pattern filling idle packets and idle frames.
+pub const IDLE_PATTERN: u8 = 0x55;
+
+/// Virtual channel conventionally reserved for idle frames.
+pub const IDLE_VCID: u8 = 7;
+
+/// Idle packet of exactly `size` octets, from `MIN_IDLE_PACKET_SIZE` to the size of the
+/// largest packet: the generator only asks for less than two frames.
+pub(crate) fn idle_packet(size: usize) -> Packet {
+    assert!(size >= MIN_IDLE_PACKET_SIZE, "Idle packet too small");
+
+    let pri_header = PrimaryHeader {
+        version_number: 0,
+        packet_type: PktType::Telemetry,
+        secondary_header_flag: false,
+        apid: IDLE_APID,
+        sequence_flags: 0x03,
+        sequence_counter: 0,
+        data_length: PrimaryHeader::data_length_for(size - HEADER_SIZE)
+            .expect("Idle packet too large"),
+    };
+    let data = vec![IDLE_PATTERN; size - MIN_IDLE_PACKET_SIZE];
+    let user_data = if data.is_empty() {
+        None
+    } else {
+        Some(UserDataField { data })
+    };
+
+    Packet::new(pri_header, None, user_data)
+}
+
+/// Generator of the frames of a single virtual channel.
+pub struct VcGenerator {
+    header: TmPrimaryHeader,
+    data_len: usize,
+    has_fecf: bool,
+    // Packet stream not yet sent, with the offsets where packets start
+    pending: Vec<u8>,
+    starts: VecDeque<usize>,
+}
+
+impl VcGenerator {
+    pub fn new(
+        spacecraft_id: u16,
+        virtual_channel_id: u8,
+        frame_length: usize,
+        has_fecf: bool,
+    ) -> Result<VcGenerator> {
+        let overhead = PRIMARY_HEADER_SIZE + if has_fecf { FECF_SIZE } else { 0 };
+        if frame_length < overhead + MIN_IDLE_PACKET_SIZE {
+            bail!(
+                "Frame length `{}` is too small to carry packets",
+                frame_length
+            );
+        }
+        if frame_length > MAX_FRAME_LENGTH {
+            bail!(
+                "Frame length `{}` exceeds the maximum `{}`",
+                frame_length,
+                MAX_FRAME_LENGTH
+            );
+        }
+
+        Ok(VcGenerator {
+            header: TmPrimaryHeader::new(spacecraft_id, virtual_channel_id),
+            data_len: frame_length - overhead,
+            has_fecf,
+            pending: Vec::new(),
+            starts: VecDeque::new(),
+        })
+    }
+
+    pub fn virtual_channel_id(&self) -> u8 {
+        self.header.virtual_channel_id
+    }
+
+    /// Length of the frame data field.
+    pub fn data_len(&self) -> usize {
+        self.data_len
+    }
+
+    pub fn push(&mut self, pkt: Packet) {
+        self.starts.push_back(self.pending.len());
+        self.pending.append(&mut pkt.into_buffer());
+    }
+
+    /// Whether a full frame can be generated without idle data.
+    pub fn is_ready(&self) -> bool {
+        self.pending.len() >= self.data_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Next full frame, if enough packet data is pending.
+    pub fn next_frame(&mut self) -> Option<TmFrame> {
+        if !self.is_ready() {
+            return None;
+        }
+
+        let fhp = match self.starts.front() {
+            Some(&start) if start < self.data_len => start as u16,
+            _ => FHP_NO_PACKET_START,
+        };
+
+        let data: Vec<u8> = self.pending.drain(..self.data_len).collect();
+        while let Some(&start) = self.starts.front() {
+            if start >= self.data_len {
+                break;
+            }
+            self.starts.pop_front();
+        }
+        for start in self.starts.iter_mut() {
+            *start -= self.data_len;
+        }
+
+        Some(self.frame(fhp, data))
+    }
+
+    /// Completes the pending packet data with idle packets, emitting all its frames.
+    pub fn flush(&mut self) -> Vec<TmFrame> {
+        let mut frames = Vec::new();
+        while let Some(frame) = self.next_frame() {
+            frames.push(frame);
+        }
+        if self.pending.is_empty() {
+            return frames;
+        }
+
+        // The idle packet may spill into one more frame when the gap is too small
+        let mut fill = self.data_len - self.pending.len();
+        if fill < MIN_IDLE_PACKET_SIZE {
+            fill += self.data_len;
+        }
+        self.push(idle_packet(fill));
+
+        while let Some(frame) = self.next_frame() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    /// Frame only carrying idle data.
+    pub fn idle_frame(&mut self) -> TmFrame {
+        let data = vec![IDLE_PATTERN; self.data_len];
+        self.frame(FHP_IDLE_DATA, data)
+    }
+
+    fn frame(&mut self, fhp: u16, data: Vec<u8>) -> TmFrame {
+        let mut header = self.header.clone();
+        header.first_header_pointer = fhp;
+        self.header.vc_frame_count = self.header.vc_frame_count.wrapping_add(1);
+
+        TmFrame {
+            header,
+            sec_header: None,
+            data,
+            ocf: None,
+            fecf: if self.has_fecf { Some(0) } else { None },
+        }
+    }
+}
+
+/// Selection of the next virtual channel to be served.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MuxPolicy {
+    /// Highest priority channel with a full frame ready.
+    Priority,
+    /// Channels with a full frame ready, in turns.
+    RoundRobin,
+}
+
+/// Multiplexer of several virtual channels in a single master channel.
+pub struct Multiplexer {
+    policy: MuxPolicy,
+    // Sorted by decreasing priority
+    channels: Vec<(u8, VcGenerator)>,
+    idle: VcGenerator,
+    mc_frame_count: u8,
+    next: usize,
+}
+
+impl Multiplexer {
+    pub fn new(
+        policy: MuxPolicy,
+        spacecraft_id: u16,
+        frame_length: usize,
+        has_fecf: bool,
+    ) -> Result<Multiplexer> {
+        Ok(Multiplexer {
+            policy,
+            channels: Vec::new(),
+            idle: VcGenerator::new(spacecraft_id, IDLE_VCID, frame_length, has_fecf)?,
+            mc_frame_count: 0,
+            next: 0,
+        })
+    }
+
+    /// Adds a virtual channel. Higher values have higher priority.
+    pub fn add_channel(&mut self, generator: VcGenerator, priority: u8) -> Result<()> {
+        let vcid = generator.virtual_channel_id();
+        if vcid == IDLE_VCID || self.channel(vcid).is_some() {
+            bail!("Virtual channel `{}` already in use", vcid);
+        }
+        if generator.data_len() != self.idle.data_len() {
+            bail!("Virtual channel `{}` has a different frame length", vcid);
+        }
+
+        let pos = self
+            .channels
+            .iter()
+            .position(|(p, _)| *p < priority)
+            .unwrap_or(self.channels.len());
+        self.channels.insert(pos, (priority, generator));
+        Ok(())
+    }
+
+    pub fn push(&mut self, virtual_channel_id: u8, pkt: Packet) -> Result<()> {
+        match self.channel(virtual_channel_id) {
+            Some(generator) => {
+                generator.push(pkt);
+                Ok(())
+            }
+            None => bail!("Unknown virtual channel `{}`", virtual_channel_id),
+        }
+    }
+
+    /// Completes the partial frames of all channels: they become ready to be sent.
+    pub fn flush(&mut self) -> Vec<TmFrame> {
+        let mut frames = Vec::new();
+        for (_, generator) in self.channels.iter_mut() {
+            frames.append(&mut generator.flush());
+        }
+        for frame in frames.iter_mut() {
+            frame.header.mc_frame_count = self.mc_frame_count;
+            self.mc_frame_count = self.mc_frame_count.wrapping_add(1);
+        }
+        frames
+    }
+
+    /// Next frame of the master channel: an idle frame when no channel is ready.
+    pub fn next_frame(&mut self) -> TmFrame {
+        let len = self.channels.len();
+        let selected = match self.policy {
+            MuxPolicy::Priority => self.channels.iter().position(|(_, g)| g.is_ready()),
+            MuxPolicy::RoundRobin => (0..len)
+                .map(|offset| (self.next + offset) % len)
+                .find(|&idx| self.channels[idx].1.is_ready()),
+        };
+
+        let mut frame = match selected {
+            Some(idx) => {
+                self.next = (idx + 1) % len;
+                self.channels[idx]
+                    .1
+                    .next_frame()
+                    .expect("Channel is ready: should generate a frame")
+            }
+            None => self.idle.idle_frame(),
+        };
+
+        frame.header.mc_frame_count = self.mc_frame_count;
+        self.mc_frame_count = self.mc_frame_count.wrapping_add(1);
+        frame
+    }
+
+    fn channel(&mut self, virtual_channel_id: u8) -> Option<&mut VcGenerator> {
+        self.channels
+            .iter_mut()
+            .map(|(_, generator)| generator)
+            .find(|generator| generator.virtual_channel_id() == virtual_channel_id)
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::frames::extractor::PacketExtractor;
+    use crate::protocol::hasher;
+    use crate::test_utils::{valid_packet, TestResult};
+
+    const FRAME_LENGTH: usize = 32;
+
+    #[test]
+    fn idle_packets_are_valid() {
+        for size in [8, 9, 100].iter() {
+            let buf = idle_packet(*size).into_buffer();
+            assert_eq!(buf.len(), *size);
+            assert_eq!(hasher::compute(&buf), 0);
+        }
+    }
+
+    #[test]
+    fn frame_lengths() {
+        assert!(VcGenerator::new(0x0C8, 1, MAX_FRAME_LENGTH, true).is_ok());
+        assert!(VcGenerator::new(0x0C8, 1, MAX_FRAME_LENGTH + 1, true).is_err());
+        assert!(VcGenerator::new(0x0C8, 1, PRIMARY_HEADER_SIZE + 7, false).is_err());
+    }
+
+    #[test]
+    fn generated_frames_are_extracted() -> TestResult {
+        let mut generator = VcGenerator::new(0x0C8, 1, FRAME_LENGTH, true)?;
+        for _ in 0..5 {
+            generator.push(valid_packet());
+        }
+
+        let mut frames = Vec::new();
+        while let Some(frame) = generator.next_frame() {
+            frames.push(frame);
+        }
+        frames.append(&mut generator.flush());
+        assert!(generator.is_empty());
+
+        // 5 * 22 bytes in 24-byte data fields
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[1].header.first_header_pointer, 20);
+        assert_eq!(frames[4].header.vc_frame_count, 4);
+
+        let mut extractor = PacketExtractor::new();
+        let mut count = 0;
+        for frame in frames {
+            let buf = frame.get_buffer();
+            assert_eq!(buf.len(), FRAME_LENGTH);
+
+            let frame = TmFrame::from_buffer(&buf, true)?;
+            count += extractor.push_frame(&frame).len();
+        }
+        assert_eq!(count, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn multiplexing_policies() -> TestResult {
+        let mut mux = Multiplexer::new(MuxPolicy::Priority, 0x0C8, FRAME_LENGTH, false)?;
+        mux.add_channel(VcGenerator::new(0x0C8, 1, FRAME_LENGTH, false)?, 1)?;
+        mux.add_channel(VcGenerator::new(0x0C8, 2, FRAME_LENGTH, false)?, 5)?;
+        assert!(mux.push(3, valid_packet()).is_err());
+
+        for _ in 0..3 {
+            mux.push(1, valid_packet())?;
+            mux.push(2, valid_packet())?;
+        }
+
+        let vcids: Vec<u8> = (0..6)
+            .map(|_| mux.next_frame().header.virtual_channel_id)
+            .collect();
+        assert_eq!(vcids, [2, 2, 1, 1, IDLE_VCID, IDLE_VCID]);
+
+        let mut mux = Multiplexer::new(MuxPolicy::RoundRobin, 0x0C8, FRAME_LENGTH, false)?;
+        mux.add_channel(VcGenerator::new(0x0C8, 1, FRAME_LENGTH, false)?, 1)?;
+        mux.add_channel(VcGenerator::new(0x0C8, 2, FRAME_LENGTH, false)?, 5)?;
+        for _ in 0..3 {
+            mux.push(1, valid_packet())?;
+            mux.push(2, valid_packet())?;
+        }
+
+        let frames: Vec<TmFrame> = (0..5).map(|_| mux.next_frame()).collect();
+        let vcids: Vec<u8> = frames
+            .iter()
+            .map(|frame| frame.header.virtual_channel_id)
+            .collect();
+        assert_eq!(vcids, [2, 1, 2, 1, IDLE_VCID]);
+        assert_eq!(frames[4].header.mc_frame_count, 4);
+        assert_eq!(frames[4].header.first_header_pointer, FHP_IDLE_DATA);
+
+        Ok(())
+    }
+}
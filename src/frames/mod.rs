@@ -0,0 +1,25 @@
+// Reachable modules
+pub mod aos;
+pub mod clcw;
+pub mod cltu;
+pub mod cop1;
+pub mod extractor;
+pub mod generator;
+pub mod pipeline;
+pub mod randomizer;
+pub mod reed_solomon;
+pub mod sync;
+pub mod tc;
+pub mod tm;
+
+// Re-exporting
+pub use aos::{AosConfig, AosFrame, AosPrimaryHeader, BPdu, MPdu, MPduExtractor};
+pub use clcw::Clcw;
+pub use extractor::{PacketExtractor, VcPacketExtractor};
+pub use generator::{Multiplexer, MuxPolicy, VcGenerator};
+pub use pipeline::{DecodedFrame, DecoderStats, TcDecoder, TcEncoder, TmDecoder, TmEncoder};
+pub use randomizer::Randomizer;
+pub use reed_solomon::ReedSolomon;
+pub use sync::{SyncConfig, SyncFrame, SyncState, Synchronizer};
+pub use tc::{SegmentHeader, TcFrame, TcPrimaryHeader};
+pub use tm::{TmFrame, TmPrimaryHeader, TmSecondaryHeader};
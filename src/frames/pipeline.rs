@@ -0,0 +1,312 @@
+//! Coding layer pipelines, between raw channel bits and transfer frames.
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+use crate::frames::cltu;
+use crate::frames::extractor::PacketExtractor;
+use crate::frames::randomizer::Randomizer;
+use crate::frames::reed_solomon::ReedSolomon;
+use crate::frames::sync::{SyncConfig, Synchronizer, ASM};
+use crate::frames::tc::TcFrame;
+use crate::frames::tm::TmFrame;
+use crate::protocol::Packet;
+
+/// TM frame out of the decoder, with the corrections applied by the coding layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedFrame {
+    pub frame: TmFrame,
+    /// Number of wrong bits in the ASM preceding the frame.
+    pub asm_bit_errors: u32,
+    /// Number of symbols corrected by the Reed-Solomon decoder.
+    pub corrected_symbols: usize,
+}
+
+/// Counters of the TM decoder, since its creation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DecoderStats {
+    pub frames: u64,
+    pub corrected_symbols: u64,
+    pub uncorrectable_frames: u64,
+    pub invalid_frames: u64,
+}
+
+/// Downlink: stream of CADUs to TM frames and packets.
+pub struct TmDecoder {
+    sync: Synchronizer,
+    randomizer: Option<Randomizer>,
+    reed_solomon: Option<ReedSolomon>,
+    has_fecf: bool,
+    extractor: PacketExtractor,
+    stats: DecoderStats,
+}
+
+impl TmDecoder {
+    /// The frame length of `sync` is the length of the Reed-Solomon codeblocks, if any.
+    pub fn new(
+        sync: SyncConfig,
+        randomizer: Option<Randomizer>,
+        reed_solomon: Option<ReedSolomon>,
+        has_fecf: bool,
+    ) -> TmDecoder {
+        TmDecoder {
+            sync: Synchronizer::new(sync),
+            randomizer,
+            reed_solomon,
+            has_fecf,
+            extractor: PacketExtractor::new(),
+            stats: DecoderStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> DecoderStats {
+        self.stats
+    }
+
+    /// Consumes the next bytes of the stream, returning the frames completed by them.
+    pub fn push_frames(&mut self, bytes: &[u8]) -> Vec<DecodedFrame> {
+        let mut frames = Vec::new();
+        for mut sync_frame in self.sync.push(bytes) {
+            if let Some(randomizer) = self.randomizer {
+                randomizer.apply(&mut sync_frame.data);
+            }
+
+            let (buf, corrected_symbols) = match &self.reed_solomon {
+                Some(rs) => match rs.decode(&sync_frame.data) {
+                    Ok(decoded) => decoded,
+                    Err(e) => {
+                        warn!("Dropping frame: {:#}", e);
+                        self.stats.uncorrectable_frames += 1;
+                        continue;
+                    }
+                },
+                None => (sync_frame.data, 0),
+            };
+            if corrected_symbols > 0 {
+                debug!("`{}` symbol(s) corrected in frame", corrected_symbols);
+            }
+            self.stats.corrected_symbols += corrected_symbols as u64;
+
+            match TmFrame::from_buffer(&buf, self.has_fecf) {
+                Ok(frame) => {
+                    self.stats.frames += 1;
+                    frames.push(DecodedFrame {
+                        frame,
+                        asm_bit_errors: sync_frame.bit_errors,
+                        corrected_symbols,
+                    });
+                }
+                Err(e) => {
+                    warn!("Dropping frame: {:#}", e);
+                    self.stats.invalid_frames += 1;
+                }
+            }
+        }
+        frames
+    }
+
+    /// Consumes the next bytes of the stream, returning the packets completed by them.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Packet> {
+        let frames = self.push_frames(bytes);
+        frames
+            .iter()
+            .flat_map(|decoded| self.extractor.push_frame(&decoded.frame))
+            .collect()
+    }
+}
+
+/// Downlink: TM frames to CADUs.
+pub struct TmEncoder {
+    randomizer: Option<Randomizer>,
+    reed_solomon: Option<ReedSolomon>,
+}
+
+impl TmEncoder {
+    pub fn new(randomizer: Option<Randomizer>, reed_solomon: Option<ReedSolomon>) -> TmEncoder {
+        TmEncoder {
+            randomizer,
+            reed_solomon,
+        }
+    }
+
+    /// Encodes the frame, followed by its check symbols, randomised if required and
+    /// preceded by the ASM.
+    pub fn encode(&self, frame: &TmFrame) -> Result<Vec<u8>> {
+        let mut buf = frame.get_buffer();
+        if let Some(rs) = &self.reed_solomon {
+            buf = rs.encode(&buf)?;
+        }
+        if let Some(randomizer) = self.randomizer {
+            randomizer.apply(&mut buf);
+        }
+
+        let mut cadu = ASM.to_be_bytes().to_vec();
+        cadu.append(&mut buf);
+        Ok(cadu)
+    }
+}
+
+/// Uplink: TC frames to CLTUs.
+pub struct TcEncoder {
+    randomizer: Option<Randomizer>,
+}
+
+impl TcEncoder {
+    pub fn new(randomizer: Option<Randomizer>) -> TcEncoder {
+        TcEncoder { randomizer }
+    }
+
+    /// Encodes the frame in a CLTU, randomised before BCH encoding if required.
+    pub fn encode(&self, frame: &TcFrame) -> Result<Vec<u8>> {
+        let mut buf = frame.get_buffer()?;
+        if let Some(randomizer) = self.randomizer {
+            randomizer.apply(&mut buf);
+        }
+        Ok(cltu::encode(&buf))
+    }
+}
+
+/// Uplink: CLTUs to TC frames (e.g. on the spacecraft side or in a simulator).
+pub struct TcDecoder {
+    randomizer: Option<Randomizer>,
+    has_segment_header: bool,
+    has_fecf: bool,
+}
+
+impl TcDecoder {
+    pub fn new(
+        randomizer: Option<Randomizer>,
+        has_segment_header: bool,
+        has_fecf: bool,
+    ) -> TcDecoder {
+        TcDecoder {
+            randomizer,
+            has_segment_header,
+            has_fecf,
+        }
+    }
+
+    pub fn decode(&self, buf: &[u8]) -> Result<TcFrame> {
+        let mut data = cltu::decode(buf)?;
+        // Fill data is derandomised along with the frame, then ignored
+        if let Some(randomizer) = self.randomizer {
+            randomizer.apply(&mut data);
+        }
+        TcFrame::from_buffer(&data, self.has_segment_header, self.has_fecf)
+            .context("Invalid frame in CLTU")
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::frames::generator::VcGenerator;
+    use crate::frames::tc::TcPrimaryHeader;
+    use crate::test_utils::{valid_packet, TestResult, VALID_SOURCE};
+
+    const FRAME_LENGTH: usize = 32;
+
+    #[test]
+    fn randomized_tm_round_trip() -> TestResult {
+        let mut generator = VcGenerator::new(0x0C8, 1, FRAME_LENGTH, true)?;
+        for _ in 0..3 {
+            generator.push(valid_packet());
+        }
+        let frames = generator.flush();
+
+        let encoder = TmEncoder::new(Some(Randomizer::Tm), None);
+        let mut stream = Vec::new();
+        for frame in frames.iter() {
+            stream.append(&mut encoder.encode(frame)?);
+        }
+
+        // Without derandomisation, no valid packet comes out
+        let config = SyncConfig {
+            frame_length: FRAME_LENGTH,
+            max_bit_errors: 0,
+            flywheel: 0,
+        };
+        let mut decoder = TmDecoder::new(config.clone(), None, None, true);
+        assert!(decoder.push(&stream).is_empty());
+
+        let mut decoder = TmDecoder::new(config, Some(Randomizer::Tm), None, true);
+        let mut packets = decoder.push(&stream);
+        assert_eq!(packets.len(), 3);
+        let last = packets.pop().unwrap().into_buffer();
+        assert_eq!(last, VALID_SOURCE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reed_solomon_corrections() -> TestResult {
+        let rs = ReedSolomon::new(2, 200)?;
+        let mut generator = VcGenerator::new(0x0C8, 1, rs.frame_length(), false)?;
+        for _ in 0..4 {
+            generator.push(valid_packet());
+        }
+        let frames = generator.flush();
+        assert_eq!(frames.len(), 3);
+
+        let encoder = TmEncoder::new(Some(Randomizer::Tm), Some(rs));
+        let mut cadus = Vec::new();
+        for frame in frames.iter() {
+            cadus.push(encoder.encode(frame)?);
+        }
+        // Correctable errors in the first codeblock, too many in the second one
+        cadus[0][10] ^= 0xFF;
+        cadus[0][11] ^= 0x01;
+        for idx in (4..cadus[1].len()).step_by(2).take(17) {
+            cadus[1][idx] ^= 0xFF;
+        }
+
+        let config = SyncConfig {
+            frame_length: rs.codeblock_length(),
+            max_bit_errors: 0,
+            flywheel: 0,
+        };
+        let mut decoder = TmDecoder::new(config, Some(Randomizer::Tm), Some(rs), false);
+        let decoded = decoder.push_frames(&cadus.concat());
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].corrected_symbols, 2);
+        assert_eq!(decoded[0].frame, frames[0]);
+        assert_eq!(decoded[1].frame, frames[2]);
+        assert_eq!(
+            decoder.stats(),
+            DecoderStats {
+                frames: 2,
+                corrected_symbols: 2,
+                uncorrectable_frames: 1,
+                invalid_frames: 0,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn randomized_tc_round_trip() -> TestResult {
+        let frame = TcFrame {
+            header: TcPrimaryHeader::new(0x0C8, 2),
+            segment_header: None,
+            data: vec![0x00; 20],
+            fecf: Some(0),
+        };
+
+        let cltu = TcEncoder::new(Some(Randomizer::Tc)).encode(&frame)?;
+        // Long runs of zeros are broken up on the channel
+        assert!(cltu[2..].windows(4).all(|w| w != [0x00; 4]));
+
+        let decoded = TcDecoder::new(Some(Randomizer::Tc), false, true).decode(&cltu)?;
+        assert_eq!(decoded.data, frame.data);
+        assert!(TcDecoder::new(None, false, true).decode(&cltu).is_err());
+
+        Ok(())
+    }
+}
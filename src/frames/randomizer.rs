@@ -0,0 +1,88 @@
+//! Pseudo-randomisers of CCSDS 131.0-B (TM) and CCSDS 231.0-B (TC).
+//!
+//! Both sequences are generated by an LFSR seeded with all ones at the start of every
+//! frame. Randomising is a XOR with the sequence: derandomising is the same operation.
+
+/// Period of both sequences, in bytes (255 bits * 8).
+pub const PERIOD: usize = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Randomizer {
+    /// h(x) = x^8 + x^7 + x^5 + x^3 + 1
+    Tm,
+    /// h(x) = x^8 + x^6 + x^4 + x^3 + x^2 + x + 1
+    Tc,
+}
+
+/// One period of the sequence generated with the given feedback taps.
+const fn generate(taps: u8) -> [u8; PERIOD] {
+    let mut table = [0u8; PERIOD];
+    let mut reg: u8 = 0xFF;
+
+    let mut idx = 0;
+    while idx < PERIOD * 8 {
+        // Output: oldest bit of the register
+        table[idx / 8] |= (reg & 0x01) << (7 - idx % 8);
+        let feedback = ((reg & taps).count_ones() & 0x01) as u8;
+        reg = (reg >> 1) | (feedback << 7);
+        idx += 1;
+    }
+
+    table
+}
+
+const TM_SEQUENCE: [u8; PERIOD] = generate(0b1010_1001);
+const TC_SEQUENCE: [u8; PERIOD] = generate(0b0101_1111);
+
+impl Randomizer {
+    /// One period of the sequence.
+    pub fn sequence(self) -> &'static [u8; PERIOD] {
+        match self {
+            Randomizer::Tm => &TM_SEQUENCE,
+            Randomizer::Tc => &TC_SEQUENCE,
+        }
+    }
+
+    /// Randomises (or derandomises) a whole frame, in place.
+    pub fn apply(self, buf: &mut [u8]) {
+        let sequence = self.sequence();
+        for (idx, byte) in buf.iter_mut().enumerate() {
+            *byte ^= sequence[idx % PERIOD];
+        }
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // First bytes of the sequences, as given by the standards
+    const TM_REFERENCE: [u8; 20] = [
+        0xFF, 0x48, 0x0E, 0xC0, 0x9A, 0x0D, 0x70, 0xBC, 0x8E, 0x2C, 0x93, 0xAD, 0xA7, 0xB7, 0x46,
+        0xCE, 0x5A, 0x97, 0x7D, 0xCC,
+    ];
+    const TC_REFERENCE: [u8; 8] = [0xFF, 0x39, 0x9E, 0x5A, 0x68, 0xE9, 0x06, 0xF5];
+
+    #[test]
+    fn reference_sequences() {
+        assert_eq!(Randomizer::Tm.sequence()[..20], TM_REFERENCE);
+        assert_eq!(Randomizer::Tc.sequence()[..8], TC_REFERENCE);
+    }
+
+    #[test]
+    fn randomizing_twice_is_identity() {
+        let frame: Vec<u8> = (0..600).map(|idx| idx as u8).collect();
+
+        let mut buf = frame.clone();
+        Randomizer::Tm.apply(&mut buf);
+        assert_ne!(buf, frame);
+        assert_eq!(buf[PERIOD] ^ buf[0], frame[PERIOD] ^ frame[0]);
+
+        Randomizer::Tm.apply(&mut buf);
+        assert_eq!(buf, frame);
+    }
+}
@@ -0,0 +1,481 @@
+//! Reed-Solomon (255,223) code of CCSDS 131.0-B, with symbols in the dual basis.
+//!
+//! Codewords are interleaved symbol by symbol (depth 1 to 8) and can be shortened
+//! by a virtual fill: leading zero symbols which are neither transmitted nor corrected.
+
+use anyhow::{bail, Result};
+
+/// Symbols per codeword.
+pub const CODEWORD_SIZE: usize = 255;
+
+/// Check symbols per codeword.
+pub const PARITY_SIZE: usize = 32;
+
+/// Information symbols per codeword.
+pub const DATA_SIZE: usize = CODEWORD_SIZE - PARITY_SIZE;
+
+/// Max number of symbol errors corrected per codeword.
+pub const MAX_CORRECTIONS: usize = PARITY_SIZE / 2;
+
+const NN: usize = CODEWORD_SIZE;
+// Index form of zero
+const A0: usize = NN;
+// Field generator polynomial: x^8 + x^7 + x^2 + x + 1
+const GF_POLY: usize = 0x187;
+// First consecutive root of the code generator polynomial, in index form
+const FCR: usize = 112;
+// Primitive element used to generate the roots, and its multiplicative inverse
+const PRIM: usize = 11;
+const IPRIM: usize = 116;
+
+/// Power of alpha to polynomial form (with `ALPHA_TO[A0] == 0`).
+const ALPHA_TO: [u8; 256] = alpha_to();
+/// Polynomial form to power of alpha (with `INDEX_OF[0] == A0`).
+const INDEX_OF: [usize; 256] = index_of();
+/// Code generator polynomial, in index form.
+const GENPOLY: [usize; PARITY_SIZE + 1] = genpoly();
+/// Conventional to dual basis.
+const TAL_TAB: [u8; 256] = tal_tab();
+/// Dual to conventional basis.
+const TAL1_TAB: [u8; 256] = tal1_tab();
+
+const fn modnn(x: usize) -> usize {
+    x % NN
+}
+
+const fn alpha_to() -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut sr = 1;
+    let mut i = 0;
+    while i < NN {
+        table[i] = sr as u8;
+        sr <<= 1;
+        if sr & 0x100 != 0 {
+            sr ^= GF_POLY;
+        }
+        i += 1;
+    }
+    table
+}
+
+const fn index_of() -> [usize; 256] {
+    let mut table = [A0; 256];
+    let mut i = 0;
+    while i < NN {
+        table[ALPHA_TO[i] as usize] = i;
+        i += 1;
+    }
+    table
+}
+
+const fn genpoly() -> [usize; PARITY_SIZE + 1] {
+    let mut poly = [0usize; PARITY_SIZE + 1];
+    poly[0] = 1;
+
+    // Product of (x - alpha^(PRIM * (FCR + i))), in polynomial form
+    let mut i = 0;
+    let mut root = FCR * PRIM;
+    while i < PARITY_SIZE {
+        poly[i + 1] = 1;
+        let mut j = i;
+        while j > 0 {
+            poly[j] = if poly[j] != 0 {
+                poly[j - 1] ^ ALPHA_TO[modnn(INDEX_OF[poly[j]] + root)] as usize
+            } else {
+                poly[j - 1]
+            };
+            j -= 1;
+        }
+        poly[0] = ALPHA_TO[modnn(INDEX_OF[poly[0]] + root)] as usize;
+        i += 1;
+        root += PRIM;
+    }
+
+    let mut i = 0;
+    while i <= PARITY_SIZE {
+        poly[i] = INDEX_OF[poly[i]];
+        i += 1;
+    }
+    poly
+}
+
+const fn tal_tab() -> [u8; 256] {
+    const TAL: [u8; 8] = [0x8D, 0xEF, 0xEC, 0x86, 0xFA, 0x99, 0xAF, 0x7B];
+
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut j = 0;
+        while j < 8 {
+            if i & (1 << j) != 0 {
+                table[i] ^= TAL[7 - j];
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+const fn tal1_tab() -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[TAL_TAB[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+/// Check symbols of a (possibly shortened) codeword, in the conventional basis.
+fn encode_codeword(data: &[u8]) -> [u8; PARITY_SIZE] {
+    let mut parity = [0u8; PARITY_SIZE];
+
+    for symbol in data {
+        let feedback = INDEX_OF[(symbol ^ parity[0]) as usize];
+        if feedback != A0 {
+            for j in 1..PARITY_SIZE {
+                parity[j] ^= ALPHA_TO[modnn(feedback + GENPOLY[PARITY_SIZE - j])];
+            }
+        }
+        parity.copy_within(1.., 0);
+        parity[PARITY_SIZE - 1] = match feedback {
+            A0 => 0,
+            _ => ALPHA_TO[modnn(feedback + GENPOLY[0])],
+        };
+    }
+
+    parity
+}
+
+/// Corrects a codeword shortened by `pad` symbols, in the conventional basis.
+///
+/// Returns the number of symbols corrected, or `None` when the codeword is uncorrectable.
+fn decode_codeword(data: &mut [u8], pad: usize) -> Option<usize> {
+    // Syndromes, in index form
+    let mut syndromes = [data[0] as usize; PARITY_SIZE];
+    for symbol in &data[1..] {
+        for (i, syndrome) in syndromes.iter_mut().enumerate() {
+            *syndrome = match *syndrome {
+                0 => *symbol as usize,
+                s => (*symbol ^ ALPHA_TO[modnn(INDEX_OF[s] + (FCR + i) * PRIM)]) as usize,
+            };
+        }
+    }
+    if syndromes.iter().all(|s| *s == 0) {
+        return Some(0);
+    }
+    for syndrome in syndromes.iter_mut() {
+        *syndrome = INDEX_OF[*syndrome];
+    }
+
+    // Berlekamp-Massey: error locator polynomial
+    let mut lambda = [0usize; PARITY_SIZE + 1];
+    lambda[0] = 1;
+    let mut b = [0usize; PARITY_SIZE + 1];
+    for (bi, li) in b.iter_mut().zip(lambda.iter()) {
+        *bi = INDEX_OF[*li];
+    }
+    let mut el = 0;
+    for r in 1..=PARITY_SIZE {
+        let mut discr_r = 0;
+        for i in 0..r {
+            if lambda[i] != 0 && syndromes[r - i - 1] != A0 {
+                discr_r ^= ALPHA_TO[modnn(INDEX_OF[lambda[i]] + syndromes[r - i - 1])] as usize;
+            }
+        }
+        let discr_r = INDEX_OF[discr_r];
+
+        if discr_r == A0 {
+            b.copy_within(..PARITY_SIZE, 1);
+            b[0] = A0;
+            continue;
+        }
+
+        let mut t = [0usize; PARITY_SIZE + 1];
+        t[0] = lambda[0];
+        for i in 0..PARITY_SIZE {
+            t[i + 1] = match b[i] {
+                A0 => lambda[i + 1],
+                bi => lambda[i + 1] ^ ALPHA_TO[modnn(discr_r + bi)] as usize,
+            };
+        }
+        if 2 * el < r {
+            el = r - el;
+            for (bi, li) in b.iter_mut().zip(lambda.iter()) {
+                *bi = match *li {
+                    0 => A0,
+                    li => modnn(INDEX_OF[li] + NN - discr_r),
+                };
+            }
+        } else {
+            b.copy_within(..PARITY_SIZE, 1);
+            b[0] = A0;
+        }
+        lambda = t;
+    }
+
+    let mut deg_lambda = 0;
+    for (i, li) in lambda.iter_mut().enumerate() {
+        *li = INDEX_OF[*li];
+        if *li != A0 {
+            deg_lambda = i;
+        }
+    }
+    if deg_lambda == 0 || deg_lambda > MAX_CORRECTIONS {
+        return None;
+    }
+
+    // Chien search: roots of the error locator polynomial
+    let mut reg = lambda;
+    let mut roots = Vec::with_capacity(deg_lambda);
+    let mut locations = Vec::with_capacity(deg_lambda);
+    let mut k = IPRIM - 1;
+    for i in 1..=NN {
+        let mut q = 1;
+        for j in (1..=deg_lambda).rev() {
+            if reg[j] != A0 {
+                reg[j] = modnn(reg[j] + j);
+                q ^= ALPHA_TO[reg[j]];
+            }
+        }
+        if q == 0 {
+            roots.push(i);
+            locations.push(k);
+            if roots.len() == deg_lambda {
+                break;
+            }
+        }
+        k = modnn(k + IPRIM);
+    }
+    if roots.len() != deg_lambda {
+        return None;
+    }
+
+    // Error evaluator polynomial, in index form
+    let deg_omega = deg_lambda - 1;
+    let mut omega = [A0; PARITY_SIZE + 1];
+    for (i, oi) in omega.iter_mut().enumerate().take(deg_omega + 1) {
+        let mut tmp = 0;
+        for j in 0..=i {
+            if syndromes[i - j] != A0 && lambda[j] != A0 {
+                tmp ^= ALPHA_TO[modnn(syndromes[i - j] + lambda[j])];
+            }
+        }
+        *oi = INDEX_OF[tmp as usize];
+    }
+
+    // Forney: error values
+    for (root, location) in roots.iter().zip(locations.iter()) {
+        let mut num1 = 0;
+        for i in (0..=deg_omega).rev() {
+            if omega[i] != A0 {
+                num1 ^= ALPHA_TO[modnn(omega[i] + i * root)];
+            }
+        }
+        let num2 = ALPHA_TO[modnn(root * (FCR + NN - 1))];
+
+        // Formal derivative of lambda: its odd terms
+        let mut den = 0;
+        let mut i = deg_lambda.min(PARITY_SIZE - 1) & !1;
+        loop {
+            if lambda[i + 1] != A0 {
+                den ^= ALPHA_TO[modnn(lambda[i + 1] + i * root)];
+            }
+            if i < 2 {
+                break;
+            }
+            i -= 2;
+        }
+        if den == 0 {
+            return None;
+        }
+
+        if num1 != 0 {
+            // Errors cannot be located in the virtual fill
+            if *location < pad {
+                return None;
+            }
+            data[location - pad] ^= ALPHA_TO[modnn(
+                INDEX_OF[num1 as usize] + INDEX_OF[num2 as usize] + NN - INDEX_OF[den as usize],
+            )];
+        }
+    }
+
+    Some(roots.len())
+}
+
+/// Interleaved RS(255,223) codeblocks of a fixed length.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReedSolomon {
+    interleaving: usize,
+    virtual_fill: usize,
+}
+
+impl ReedSolomon {
+    /// `virtual_fill` is the number of symbols removed from each codeword.
+    pub fn new(interleaving: usize, virtual_fill: usize) -> Result<ReedSolomon> {
+        if !(1..=8).contains(&interleaving) {
+            bail!("Interleaving depth `{}` not in 1..=8", interleaving);
+        }
+        if virtual_fill >= DATA_SIZE {
+            bail!("Virtual fill `{}` leaves no data symbols", virtual_fill);
+        }
+
+        Ok(ReedSolomon {
+            interleaving,
+            virtual_fill,
+        })
+    }
+
+    pub fn interleaving(&self) -> usize {
+        self.interleaving
+    }
+
+    /// Length of the frames protected by the code.
+    pub fn frame_length(&self) -> usize {
+        self.interleaving * (DATA_SIZE - self.virtual_fill)
+    }
+
+    /// Length of the codeblocks: the frame followed by the check symbols.
+    pub fn codeblock_length(&self) -> usize {
+        self.frame_length() + self.interleaving * PARITY_SIZE
+    }
+
+    /// Appends the check symbols to the frame.
+    pub fn encode(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        if frame.len() != self.frame_length() {
+            bail!(
+                "Frame of length `{}` instead of `{}`",
+                frame.len(),
+                self.frame_length()
+            );
+        }
+
+        let mut buf = frame.to_vec();
+        buf.resize(self.codeblock_length(), 0);
+        for idx in 0..self.interleaving {
+            let data: Vec<u8> = self
+                .codeword(frame, idx)
+                .map(|symbol| TAL1_TAB[symbol as usize])
+                .collect();
+            let parity = encode_codeword(&data);
+            for (j, symbol) in parity.iter().enumerate() {
+                buf[frame.len() + j * self.interleaving + idx] = TAL_TAB[*symbol as usize];
+            }
+        }
+
+        Ok(buf)
+    }
+
+    /// Corrects the codeblock, returning the frame and the number of symbols corrected.
+    pub fn decode(&self, codeblock: &[u8]) -> Result<(Vec<u8>, usize)> {
+        if codeblock.len() != self.codeblock_length() {
+            bail!(
+                "Codeblock of length `{}` instead of `{}`",
+                codeblock.len(),
+                self.codeblock_length()
+            );
+        }
+
+        let mut buf = codeblock.to_vec();
+        let mut corrected = 0;
+        for idx in 0..self.interleaving {
+            let mut data: Vec<u8> = self
+                .codeword(codeblock, idx)
+                .map(|symbol| TAL1_TAB[symbol as usize])
+                .collect();
+            match decode_codeword(&mut data, self.virtual_fill) {
+                Some(count) => corrected += count,
+                None => bail!("Uncorrectable codeword `{}`", idx),
+            }
+            for (j, symbol) in data.iter().enumerate() {
+                buf[j * self.interleaving + idx] = TAL_TAB[*symbol as usize];
+            }
+        }
+
+        buf.truncate(self.frame_length());
+        Ok((buf, corrected))
+    }
+
+    /// Symbols of the given codeword, out of an interleaved buffer.
+    fn codeword<'a>(&self, buf: &'a [u8], idx: usize) -> impl Iterator<Item = u8> + 'a {
+        buf.iter().skip(idx).step_by(self.interleaving).copied()
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::TestResult;
+
+    fn frame(len: usize) -> Vec<u8> {
+        (0..len).map(|idx| (idx * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn tables() {
+        // Values of the reference implementation of the CCSDS code
+        assert_eq!(
+            ALPHA_TO[..10],
+            [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x87, 0x89]
+        );
+        assert_eq!(
+            GENPOLY,
+            [
+                0, 249, 59, 66, 4, 43, 126, 251, 97, 30, 3, 213, 50, 66, 170, 5, 24, 5, 170, 66,
+                50, 213, 3, 30, 97, 251, 126, 43, 4, 66, 59, 249, 0
+            ]
+        );
+        assert_eq!(TAL_TAB[..4], [0x00, 0x7B, 0xAF, 0xD4]);
+        assert_eq!(TAL1_TAB[..4], [0x00, 0xCC, 0xAC, 0x60]);
+    }
+
+    #[test]
+    fn corrects_up_to_16_errors_per_codeword() -> TestResult {
+        let rs = ReedSolomon::new(4, 0)?;
+        let frame = frame(rs.frame_length());
+        let codeblock = rs.encode(&frame)?;
+        assert_eq!(codeblock.len(), 1020);
+        assert_eq!(rs.decode(&codeblock)?, (frame.clone(), 0));
+
+        // 16 errors in every codeword, including the check symbols
+        let mut corrupted = codeblock.clone();
+        for idx in (0..corrupted.len()).step_by(15).take(64) {
+            corrupted[idx] ^= 0xA5;
+        }
+        assert_eq!(rs.decode(&corrupted)?, (frame, 64));
+
+        // 17 errors in the first codeword
+        let mut corrupted = codeblock;
+        for idx in (0..17).map(|idx| idx * 4 * 3) {
+            corrupted[idx] ^= 0xFF;
+        }
+        assert!(rs.decode(&corrupted).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn shortened_codewords() -> TestResult {
+        let rs = ReedSolomon::new(2, 100)?;
+        let frame = frame(rs.frame_length());
+        assert_eq!(frame.len(), 246);
+
+        let mut codeblock = rs.encode(&frame)?;
+        assert_eq!(codeblock.len(), 310);
+        codeblock[0] = 0;
+        codeblock[301] ^= 0x01;
+        assert_eq!(rs.decode(&codeblock)?, (frame, 2));
+
+        assert!(ReedSolomon::new(9, 0).is_err());
+        assert!(ReedSolomon::new(1, DATA_SIZE).is_err());
+
+        Ok(())
+    }
+}
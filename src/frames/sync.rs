@@ -0,0 +1,301 @@
+//! Frame synchronisation: search of the Attached Sync Marker in a raw bitstream of CADUs.
+
+use log::{debug, warn};
+
+/// Attached Sync Marker of TM and AOS channel access data units.
+pub const ASM: u32 = 0x1ACF_FC1D;
+
+/// Size of the ASM, in bits.
+const ASM_BITS: usize = 32;
+
+#[derive(Clone, Debug)]
+pub struct SyncConfig {
+    /// Length of the frames following each ASM (e.g. with Reed-Solomon check symbols).
+    pub frame_length: usize,
+    /// Max number of wrong bits in an ASM still recognised as such.
+    pub max_bit_errors: u32,
+    /// Number of consecutive missing ASMs tolerated before losing the lock.
+    pub flywheel: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncState {
+    /// Looking for an ASM at every bit position.
+    Search,
+    /// ASM found where expected.
+    Lock,
+    /// ASM missing where expected, for the given number of consecutive frames.
+    Flywheel(u32),
+}
+
+/// Frame aligned by the synchroniser.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncFrame {
+    pub data: Vec<u8>,
+    /// Number of wrong bits in the ASM preceding the frame.
+    pub bit_errors: u32,
+    /// Whether the polarity of the stream is inverted (corrected in `data`).
+    pub inverted: bool,
+    /// Offset, in bits, of the frame relative to the bytes of the stream.
+    pub bit_offset: u8,
+    /// Whether the frame was output without a valid ASM (flywheel).
+    pub flywheel: bool,
+}
+
+pub struct Synchronizer {
+    config: SyncConfig,
+    state: SyncState,
+    inverted: bool,
+    buffer: Vec<u8>,
+    // Position, in bits, of the next bit to be processed in `buffer`
+    position: usize,
+}
+
+impl Synchronizer {
+    pub fn new(config: SyncConfig) -> Synchronizer {
+        Synchronizer {
+            config,
+            state: SyncState::Search,
+            inverted: false,
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn state(&self) -> SyncState {
+        self.state
+    }
+
+    /// Consumes the next bytes of the stream, returning the frames completed by them.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<SyncFrame> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut frames = Vec::new();
+        loop {
+            let frame = match self.state {
+                SyncState::Search => self.search(),
+                SyncState::Lock | SyncState::Flywheel(_) => self.track(),
+            };
+            match frame {
+                Some(frame) => frames.push(frame),
+                None => break,
+            }
+        }
+
+        // Dropping the bytes already processed
+        let consumed = self.position / 8;
+        self.buffer.drain(..consumed);
+        self.position -= consumed * 8;
+
+        frames
+    }
+
+    fn search(&mut self) -> Option<SyncFrame> {
+        let frame_bits = self.config.frame_length * 8;
+
+        while self.position + ASM_BITS <= self.bits() {
+            let errors = (self.read_u32(self.position) ^ ASM).count_ones();
+            let inverted = if errors <= self.config.max_bit_errors {
+                false
+            } else if ASM_BITS as u32 - errors <= self.config.max_bit_errors {
+                true
+            } else {
+                self.position += 1;
+                continue;
+            };
+
+            // ASM found: waiting for the whole frame
+            if self.position + ASM_BITS + frame_bits > self.bits() {
+                return None;
+            }
+
+            debug!(
+                "ASM found at bit offset `{}` (inverted: {})",
+                self.position % 8,
+                inverted
+            );
+            self.inverted = inverted;
+            self.state = SyncState::Lock;
+            let errors = if inverted {
+                ASM_BITS as u32 - errors
+            } else {
+                errors
+            };
+            return Some(self.output(errors, false));
+        }
+
+        None
+    }
+
+    fn track(&mut self) -> Option<SyncFrame> {
+        let frame_bits = self.config.frame_length * 8;
+        if self.position + ASM_BITS + frame_bits > self.bits() {
+            return None;
+        }
+
+        let mut word = self.read_u32(self.position);
+        if self.inverted {
+            word = !word;
+        }
+        let errors = (word ^ ASM).count_ones();
+        if errors <= self.config.max_bit_errors {
+            self.state = SyncState::Lock;
+            return Some(self.output(errors, false));
+        }
+
+        let missed = match self.state {
+            SyncState::Flywheel(missed) => missed + 1,
+            _ => 1,
+        };
+        if missed > self.config.flywheel {
+            warn!("Lock lost after `{}` missing ASM(s)", missed);
+            self.state = SyncState::Search;
+            return self.search();
+        }
+
+        self.state = SyncState::Flywheel(missed);
+        Some(self.output(errors, true))
+    }
+
+    /// Frame following the ASM at the current position, which moves past it.
+    fn output(&mut self, bit_errors: u32, flywheel: bool) -> SyncFrame {
+        let bit_offset = (self.position % 8) as u8;
+        let start = self.position + ASM_BITS;
+
+        let data = (0..self.config.frame_length)
+            .map(|idx| {
+                let byte = self.read_u8(start + idx * 8);
+                if self.inverted {
+                    !byte
+                } else {
+                    byte
+                }
+            })
+            .collect();
+        self.position = start + self.config.frame_length * 8;
+
+        SyncFrame {
+            data,
+            bit_errors,
+            inverted: self.inverted,
+            bit_offset,
+            flywheel,
+        }
+    }
+
+    fn bits(&self) -> usize {
+        self.buffer.len() * 8
+    }
+
+    /// Byte starting at any bit position.
+    fn read_u8(&self, position: usize) -> u8 {
+        let idx = position / 8;
+        let shift = position % 8;
+        if shift == 0 {
+            return self.buffer[idx];
+        }
+        (self.buffer[idx] << shift) | (self.buffer[idx + 1] >> (8 - shift))
+    }
+
+    fn read_u32(&self, position: usize) -> u32 {
+        (0..4).fold(0, |acc, idx| {
+            (acc << 8) | self.read_u8(position + idx * 8) as u32
+        })
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FRAME_LENGTH: usize = 6;
+
+    fn config() -> SyncConfig {
+        SyncConfig {
+            frame_length: FRAME_LENGTH,
+            max_bit_errors: 2,
+            flywheel: 1,
+        }
+    }
+
+    fn cadu(asm: u32, fill: u8) -> Vec<u8> {
+        let mut buf = asm.to_be_bytes().to_vec();
+        buf.extend_from_slice(&[fill; FRAME_LENGTH]);
+        buf
+    }
+
+    // Delays the whole stream by the given number of bits (< 8)
+    fn slip(stream: &[u8], bits: u32) -> Vec<u8> {
+        let mut out = Vec::with_capacity(stream.len() + 1);
+        let mut carry = 0u8;
+        for byte in stream {
+            out.push(carry | (byte >> bits));
+            carry = byte.checked_shl(8 - bits).unwrap_or(0);
+        }
+        out.push(carry);
+        out
+    }
+
+    #[test]
+    fn bit_slipped_stream_with_errors() {
+        let mut stream = vec![0x12, 0x34, 0x56];
+        stream.append(&mut cadu(ASM, 0xA1));
+        stream.append(&mut cadu(ASM ^ 0x0000_0101, 0xA2)); // 2 wrong bits
+        stream.append(&mut cadu(ASM, 0xA3));
+        let stream = slip(&stream, 3);
+
+        let mut sync = Synchronizer::new(config());
+        // Byte by byte: frames are only output once complete
+        let frames: Vec<SyncFrame> = stream.iter().flat_map(|b| sync.push(&[*b])).collect();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].data, [0xA1; FRAME_LENGTH]);
+        assert_eq!(frames[0].bit_offset, 3);
+        assert_eq!(frames[1].bit_errors, 2);
+        assert_eq!(frames[2].data, [0xA3; FRAME_LENGTH]);
+        assert!(frames
+            .iter()
+            .all(|frame| !frame.inverted && !frame.flywheel));
+        assert_eq!(sync.state(), SyncState::Lock);
+    }
+
+    #[test]
+    fn inverted_polarity() {
+        let stream: Vec<u8> = [cadu(ASM, 0x0F), cadu(ASM, 0xF0)]
+            .concat()
+            .iter()
+            .map(|byte| !byte)
+            .collect();
+
+        let mut sync = Synchronizer::new(config());
+        let frames = sync.push(&stream);
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].inverted);
+        assert_eq!(frames[0].data, [0x0F; FRAME_LENGTH]);
+        assert_eq!(frames[1].data, [0xF0; FRAME_LENGTH]);
+    }
+
+    #[test]
+    fn flywheel_then_lock_lost() {
+        let mut stream = cadu(ASM, 0xB1);
+        stream.append(&mut cadu(0, 0xB2)); // missing ASM: flywheel
+        stream.append(&mut cadu(ASM, 0xB3));
+        stream.append(&mut cadu(0, 0xB4));
+        stream.append(&mut cadu(0, 0xB5)); // second missing ASM: lock lost
+        stream.append(&mut cadu(ASM, 0xB6));
+
+        let mut sync = Synchronizer::new(config());
+        let frames = sync.push(&stream);
+
+        let fills: Vec<u8> = frames.iter().map(|frame| frame.data[0]).collect();
+        assert_eq!(fills, [0xB1, 0xB2, 0xB3, 0xB4, 0xB6]);
+        assert!(frames[1].flywheel);
+        assert!(frames[3].flywheel);
+        assert!(!frames[4].flywheel);
+    }
+}
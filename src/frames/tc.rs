@@ -0,0 +1,276 @@
+//! TC Transfer Frames (CCSDS 232.0-B).
+
+use std::io::Cursor;
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use super::tm::FECF_SIZE;
+use crate::protocol::hasher;
+
+/// Size of the frame primary header. Fixed size: 5 bytes.
+pub const PRIMARY_HEADER_SIZE: usize = 5;
+
+/// Size of the segment header.
+pub const SEGMENT_HEADER_SIZE: usize = 1;
+
+/// Max size of a TC frame.
+pub const MAX_FRAME_LENGTH: usize = 1024;
+
+/// Transfer Frame Version Number of TC frames.
+pub const VERSION_NUMBER: u8 = 0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TcPrimaryHeader {
+    pub version_number: u8,
+    pub bypass_flag: bool,
+    pub control_command_flag: bool,
+    pub spacecraft_id: u16,
+    pub virtual_channel_id: u8,
+    /// Frame length: as specified by the protocol, #octets = FRAME_LENGTH + 1
+    pub frame_length: u16,
+    pub frame_sequence_number: u8,
+}
+
+impl TcPrimaryHeader {
+    /// Header of a sequence-controlled (type AD) data frame.
+    pub fn new(spacecraft_id: u16, virtual_channel_id: u8) -> TcPrimaryHeader {
+        TcPrimaryHeader {
+            version_number: VERSION_NUMBER,
+            bypass_flag: false,
+            control_command_flag: false,
+            spacecraft_id,
+            virtual_channel_id,
+            frame_length: 0,
+            frame_sequence_number: 0,
+        }
+    }
+
+    pub fn from_buffer(buf: &[u8]) -> Result<TcPrimaryHeader> {
+        if buf.len() < PRIMARY_HEADER_SIZE {
+            bail!("Frame of size `{}` is shorter than its header", buf.len());
+        }
+        let mut cursor = Cursor::new(buf);
+
+        let val = cursor.read_u16::<BigEndian>()?;
+        let version_number = ((val & 0xC000) >> 14) as u8;
+        let bypass_flag = val & 0x2000 != 0;
+        let control_command_flag = val & 0x1000 != 0;
+        let spacecraft_id = val & 0x03FF;
+
+        let val = cursor.read_u16::<BigEndian>()?;
+        let virtual_channel_id = ((val & 0xFC00) >> 10) as u8;
+        let frame_length = val & 0x03FF;
+
+        let frame_sequence_number = cursor.read_u8()?;
+
+        if version_number != VERSION_NUMBER {
+            bail!("Unexpected TC frame version number `{}`", version_number);
+        }
+
+        Ok(TcPrimaryHeader {
+            version_number,
+            bypass_flag,
+            control_command_flag,
+            spacecraft_id,
+            virtual_channel_id,
+            frame_length,
+            frame_sequence_number,
+        })
+    }
+
+    pub fn get_buffer(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(PRIMARY_HEADER_SIZE);
+        let mut cursor = Cursor::new(&mut buf);
+
+        let mut val: u16;
+
+        // First 2 bytes
+        val = ((self.version_number & 0x03) as u16) << 14;
+        val |= (self.bypass_flag as u16) << 13;
+        val |= (self.control_command_flag as u16) << 12;
+        val |= self.spacecraft_id & 0x03FF;
+        cursor.write_u16::<BigEndian>(val).unwrap();
+
+        // Next 2 bytes
+        val = ((self.virtual_channel_id & 0x3F) as u16) << 10;
+        val |= self.frame_length & 0x03FF;
+        cursor.write_u16::<BigEndian>(val).unwrap();
+
+        // Final byte
+        cursor.write_u8(self.frame_sequence_number).unwrap();
+
+        buf
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SegmentHeader {
+    pub sequence_flags: u8,
+    pub map_id: u8,
+}
+
+impl SegmentHeader {
+    /// Header of a segment carrying complete packets on the given MAP.
+    pub fn new(map_id: u8) -> SegmentHeader {
+        SegmentHeader {
+            sequence_flags: 0x03,
+            map_id,
+        }
+    }
+
+    pub fn from_byte(val: u8) -> SegmentHeader {
+        SegmentHeader {
+            sequence_flags: (val & 0xC0) >> 6,
+            map_id: val & 0x3F,
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        ((self.sequence_flags & 0x03) << 6) | (self.map_id & 0x3F)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TcFrame {
+    pub header: TcPrimaryHeader,
+    pub segment_header: Option<SegmentHeader>,
+    pub data: Vec<u8>,
+    pub fecf: Option<u16>,
+}
+
+impl TcFrame {
+    /// Decodes a frame, which might be followed by fill data (e.g. from a CLTU).
+    ///
+    /// Whether the frames of the virtual channel carry a segment header and an FECF
+    /// are managed parameters: they cannot be read from the frame itself.
+    pub fn from_buffer(buf: &[u8], has_segment_header: bool, has_fecf: bool) -> Result<TcFrame> {
+        let header = TcPrimaryHeader::from_buffer(buf)?;
+
+        let len = header.frame_length as usize + 1;
+        if buf.len() < len {
+            bail!(
+                "Frame of length `{}` truncated to `{}` bytes",
+                len,
+                buf.len()
+            );
+        }
+        let buf = &buf[..len];
+
+        if has_fecf && hasher::compute(buf) != 0 {
+            bail!("Frame with invalid FECF");
+        }
+
+        let mut start = PRIMARY_HEADER_SIZE;
+        let end = if has_fecf { len - FECF_SIZE } else { len };
+
+        // Control commands (type-BC frames) are never segmented
+        let segment_header = if has_segment_header && !header.control_command_flag && start < end {
+            start += SEGMENT_HEADER_SIZE;
+            Some(SegmentHeader::from_byte(buf[PRIMARY_HEADER_SIZE]))
+        } else {
+            None
+        };
+        if start > end {
+            bail!("Frame of length `{}` has no room for its data field", len);
+        }
+        let data = buf[start..end].to_vec();
+
+        let fecf = match has_fecf {
+            true => Some(u16::from_be_bytes([buf[end], buf[end + 1]])),
+            false => None,
+        };
+
+        Ok(TcFrame {
+            header,
+            segment_header,
+            data,
+            fecf,
+        })
+    }
+
+    /// Encodes the frame, setting its frame length. When present, the FECF is recomputed.
+    pub fn get_buffer(&self) -> Result<Vec<u8>> {
+        let len = self.encoded_len();
+        if len > MAX_FRAME_LENGTH {
+            bail!("Frame of length `{}` exceeds the maximum", len);
+        }
+
+        let mut header = self.header.clone();
+        header.frame_length = (len - 1) as u16;
+
+        let mut buf = header.get_buffer();
+        if let Some(segment_header) = self.segment_header {
+            buf.push(segment_header.to_byte());
+        }
+        buf.extend_from_slice(&self.data);
+        if self.fecf.is_some() {
+            hasher::append_checksum(&mut buf);
+        }
+
+        Ok(buf)
+    }
+
+    /// Size of the encoded frame.
+    pub fn encoded_len(&self) -> usize {
+        let segment_len = self.segment_header.map_or(0, |_| SEGMENT_HEADER_SIZE);
+        let fecf_len = self.fecf.map_or(0, |_| FECF_SIZE);
+
+        PRIMARY_HEADER_SIZE + segment_len + self.data.len() + fecf_len
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::TestResult;
+
+    const HEADER: [u8; 5] = [0x20, 0xC8, 0x0C, 0x0A, 0x2A];
+
+    #[test]
+    fn test_primary_header() -> TestResult {
+        let header = TcPrimaryHeader::from_buffer(&HEADER)?;
+
+        assert!(header.bypass_flag);
+        assert!(!header.control_command_flag);
+        assert_eq!(header.spacecraft_id, 0x0C8);
+        assert_eq!(header.virtual_channel_id, 3);
+        assert_eq!(header.frame_length, 0x00A);
+        assert_eq!(header.frame_sequence_number, 0x2A);
+
+        assert_eq!(header.get_buffer(), HEADER);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_round_trip() -> TestResult {
+        let frame = TcFrame {
+            header: TcPrimaryHeader::new(0x0C8, 3),
+            segment_header: Some(SegmentHeader::new(5)),
+            data: vec![1, 2, 3, 4],
+            fecf: Some(0),
+        };
+
+        let mut buf = frame.get_buffer()?;
+        assert_eq!(buf.len(), 12);
+        assert_eq!(buf[3], 11);
+        assert_eq!(buf[5], 0xC5);
+
+        // Fill data after the frame is ignored
+        buf.extend_from_slice(&[0x55, 0x55]);
+        let decoded = TcFrame::from_buffer(&buf, true, true)?;
+        assert_eq!(decoded.segment_header, frame.segment_header);
+        assert_eq!(decoded.data, frame.data);
+
+        buf[7] ^= 0x01;
+        assert!(TcFrame::from_buffer(&buf, true, true).is_err());
+
+        Ok(())
+    }
+}
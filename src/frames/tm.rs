@@ -0,0 +1,377 @@
+//! TM Transfer Frames (CCSDS 132.0-B).
+
+use std::io::{Cursor, Read};
+
+use anyhow::{bail, Context, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::frames::clcw::Clcw;
+use crate::protocol::hasher;
+use crate::protocol::Packet;
+
+/// Size of the frame primary header. Fixed size: 6 bytes.
+pub const PRIMARY_HEADER_SIZE: usize = 6;
+
+/// Size of the Operational Control Field.
+pub const OCF_SIZE: usize = 4;
+
+/// Size of the Frame Error Control Field.
+pub const FECF_SIZE: usize = 2;
+
+/// Max size of the data field of the secondary header (6-bit length, minus one).
+pub const MAX_SECONDARY_HEADER_SIZE: usize = 64;
+
+/// First Header Pointer: no packet starts in the data field of the frame.
+pub const FHP_NO_PACKET_START: u16 = 0x07FF;
+
+/// First Header Pointer: the data field of the frame only carries idle data.
+pub const FHP_IDLE_DATA: u16 = 0x07FE;
+
+/// Transfer Frame Version Number of TM frames.
+pub const VERSION_NUMBER: u8 = 0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TmPrimaryHeader {
+    pub version_number: u8,
+    pub spacecraft_id: u16,
+    pub virtual_channel_id: u8,
+    pub ocf_flag: bool,
+    pub mc_frame_count: u8,
+    pub vc_frame_count: u8,
+    pub secondary_header_flag: bool,
+    pub sync_flag: bool,
+    pub packet_order_flag: bool,
+    pub segment_length_id: u8,
+    pub first_header_pointer: u16,
+}
+
+impl TmPrimaryHeader {
+    /// Header of a packet-carrying frame (synchronous, no secondary header nor OCF).
+    pub fn new(spacecraft_id: u16, virtual_channel_id: u8) -> TmPrimaryHeader {
+        TmPrimaryHeader {
+            version_number: VERSION_NUMBER,
+            spacecraft_id,
+            virtual_channel_id,
+            ocf_flag: false,
+            mc_frame_count: 0,
+            vc_frame_count: 0,
+            secondary_header_flag: false,
+            sync_flag: false,
+            packet_order_flag: false,
+            segment_length_id: 0x03,
+            first_header_pointer: 0,
+        }
+    }
+
+    pub fn from_buffer(buf: &[u8]) -> Result<TmPrimaryHeader> {
+        if buf.len() < PRIMARY_HEADER_SIZE {
+            bail!("Frame of size `{}` is shorter than its header", buf.len());
+        }
+        let mut cursor = Cursor::new(buf);
+
+        let val = cursor.read_u16::<BigEndian>()?;
+        let version_number = ((val & 0xC000) >> 14) as u8;
+        let spacecraft_id = (val & 0x3FF0) >> 4;
+        let virtual_channel_id = ((val & 0x000E) >> 1) as u8;
+        let ocf_flag = val & 0x0001 != 0;
+
+        let mc_frame_count = cursor.read_u8()?;
+        let vc_frame_count = cursor.read_u8()?;
+
+        let val = cursor.read_u16::<BigEndian>()?;
+        let secondary_header_flag = val & 0x8000 != 0;
+        let sync_flag = val & 0x4000 != 0;
+        let packet_order_flag = val & 0x2000 != 0;
+        let segment_length_id = ((val & 0x1800) >> 11) as u8;
+        let first_header_pointer = val & 0x07FF;
+
+        if version_number != VERSION_NUMBER {
+            bail!("Unexpected TM frame version number `{}`", version_number);
+        }
+
+        Ok(TmPrimaryHeader {
+            version_number,
+            spacecraft_id,
+            virtual_channel_id,
+            ocf_flag,
+            mc_frame_count,
+            vc_frame_count,
+            secondary_header_flag,
+            sync_flag,
+            packet_order_flag,
+            segment_length_id,
+            first_header_pointer,
+        })
+    }
+
+    pub fn get_buffer(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(PRIMARY_HEADER_SIZE);
+        let mut cursor = Cursor::new(&mut buf);
+
+        let mut val: u16;
+
+        // Frame identification
+        val = ((self.version_number & 0x03) as u16) << 14;
+        val |= (self.spacecraft_id & 0x03FF) << 4;
+        val |= ((self.virtual_channel_id & 0x07) as u16) << 1;
+        val |= self.ocf_flag as u16;
+        cursor.write_u16::<BigEndian>(val).unwrap();
+
+        // Frame counts
+        cursor.write_u8(self.mc_frame_count).unwrap();
+        cursor.write_u8(self.vc_frame_count).unwrap();
+
+        // Frame data field status
+        val = (self.secondary_header_flag as u16) << 15;
+        val |= (self.sync_flag as u16) << 14;
+        val |= (self.packet_order_flag as u16) << 13;
+        val |= ((self.segment_length_id & 0x03) as u16) << 11;
+        val |= self.first_header_pointer & 0x07FF;
+        cursor.write_u16::<BigEndian>(val).unwrap();
+
+        buf
+    }
+}
+
+/// Data field of the frame secondary header (without its identification octet).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TmSecondaryHeader(Vec<u8>);
+
+impl TmSecondaryHeader {
+    /// Fails if the data field is not of 1 to 64 octets.
+    pub fn new(data: Vec<u8>) -> Result<TmSecondaryHeader> {
+        if data.is_empty() || data.len() > MAX_SECONDARY_HEADER_SIZE {
+            bail!(
+                "Secondary header of size `{}`, not between 1 and {}",
+                data.len(),
+                MAX_SECONDARY_HEADER_SIZE
+            );
+        }
+        Ok(TmSecondaryHeader(data))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TmFrame {
+    pub header: TmPrimaryHeader,
+    pub sec_header: Option<TmSecondaryHeader>,
+    pub data: Vec<u8>,
+    pub ocf: Option<u32>,
+    pub fecf: Option<u16>,
+}
+
+impl TmFrame {
+    /// Frame whose data field starts with the first of the given packets.
+    pub fn from_packets(mut header: TmPrimaryHeader, packets: Vec<Packet>) -> TmFrame {
+        header.first_header_pointer = 0;
+
+        let mut data = Vec::new();
+        for pkt in packets {
+            data.append(&mut pkt.into_buffer());
+        }
+
+        TmFrame {
+            header,
+            sec_header: None,
+            data,
+            ocf: None,
+            fecf: None,
+        }
+    }
+
+    /// Decodes a whole frame: its length is the length of the given buffer.
+    pub fn from_buffer(buf: &[u8], has_fecf: bool) -> Result<TmFrame> {
+        let header = TmPrimaryHeader::from_buffer(buf)?;
+        if has_fecf && hasher::compute(buf) != 0 {
+            bail!("Frame with invalid FECF");
+        }
+        let mut cursor = Cursor::new(buf);
+        cursor.set_position(PRIMARY_HEADER_SIZE as u64);
+
+        let sec_header = if header.secondary_header_flag {
+            let id = cursor.read_u8().context("Missing secondary header")?;
+            let len = (id & 0x3F) as usize + 1;
+            let mut sec_buf = vec![0; len];
+            cursor.read_exact(&mut sec_buf).with_context(|| {
+                format!("Could not read the secondary header of size `{}`", len)
+            })?;
+            Some(TmSecondaryHeader(sec_buf))
+        } else {
+            None
+        };
+
+        let trailer_len =
+            if header.ocf_flag { OCF_SIZE } else { 0 } + if has_fecf { FECF_SIZE } else { 0 };
+        let start = cursor.position() as usize;
+        if buf.len() < start + trailer_len {
+            bail!(
+                "Frame of size `{}` has no room for its data field",
+                buf.len()
+            );
+        }
+        let end = buf.len() - trailer_len;
+        let data = buf[start..end].to_vec();
+
+        cursor.set_position(end as u64);
+        let ocf = match header.ocf_flag {
+            true => Some(cursor.read_u32::<BigEndian>()?),
+            false => None,
+        };
+        let fecf = match has_fecf {
+            true => Some(cursor.read_u16::<BigEndian>()?),
+            false => None,
+        };
+
+        Ok(TmFrame {
+            header,
+            sec_header,
+            data,
+            ocf,
+            fecf,
+        })
+    }
+
+    /// Encodes the frame. When present, the FECF is recomputed over the encoded frame.
+    pub fn get_buffer(&self) -> Vec<u8> {
+        let mut header = self.header.clone();
+        header.secondary_header_flag = self.sec_header.is_some();
+        header.ocf_flag = self.ocf.is_some();
+
+        let mut buf = header.get_buffer();
+
+        // (Optional) Secondary Header: version `00` + length of its data field
+        if let Some(sec_header) = &self.sec_header {
+            buf.push((sec_header.0.len() - 1) as u8);
+            buf.extend_from_slice(&sec_header.0);
+        }
+
+        // Data Field
+        buf.extend_from_slice(&self.data);
+
+        // (Optional) Operational Control Field
+        if let Some(ocf) = self.ocf {
+            buf.extend_from_slice(&ocf.to_be_bytes());
+        }
+
+        // (Optional) Frame Error Control Field
+        if self.fecf.is_some() {
+            hasher::append_checksum(&mut buf);
+        }
+
+        buf
+    }
+
+    /// CLCW reported in the OCF, if any.
+    pub fn clcw(&self) -> Option<Clcw> {
+        self.ocf.and_then(Clcw::from_ocf)
+    }
+
+    /// Size of the encoded frame.
+    pub fn encoded_len(&self) -> usize {
+        let sec_header_len = self.sec_header.as_ref().map_or(0, |sec| sec.0.len() + 1);
+        let ocf_len = if self.ocf.is_some() { OCF_SIZE } else { 0 };
+        let fecf_len = if self.fecf.is_some() { FECF_SIZE } else { 0 };
+
+        PRIMARY_HEADER_SIZE + sec_header_len + self.data.len() + ocf_len + fecf_len
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::TestResult;
+
+    const HEADER: [u8; 6] = [0x0C, 0x83, 0x12, 0x34, 0x18, 0x05];
+
+    #[test]
+    fn test_primary_header() -> TestResult {
+        let header = TmPrimaryHeader::from_buffer(&HEADER)?;
+
+        assert_eq!(header.spacecraft_id, 0x0C8);
+        assert_eq!(header.virtual_channel_id, 1);
+        assert!(header.ocf_flag);
+        assert_eq!(header.mc_frame_count, 0x12);
+        assert_eq!(header.vc_frame_count, 0x34);
+        assert!(!header.secondary_header_flag);
+        assert!(!header.sync_flag);
+        assert_eq!(header.segment_length_id, 0x03);
+        assert_eq!(header.first_header_pointer, 0x0005);
+
+        assert_eq!(header.get_buffer(), HEADER);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_round_trip() -> TestResult {
+        let mut header = TmPrimaryHeader::new(0x0C8, 2);
+        header.first_header_pointer = 3;
+
+        let frame = TmFrame {
+            header,
+            sec_header: Some(TmSecondaryHeader::new(vec![0xAA, 0xBB])?),
+            data: vec![1, 2, 3, 4, 5, 6],
+            ocf: Some(0x0102_0304),
+            fecf: Some(0),
+        };
+
+        let buf = frame.get_buffer();
+        assert_eq!(buf.len(), frame.encoded_len());
+        assert_eq!(buf[6], 0x01); // secondary header length - 1
+        assert_eq!(hasher::compute(&buf), 0);
+
+        let decoded = TmFrame::from_buffer(&buf, true)?;
+        assert!(decoded.header.secondary_header_flag);
+        assert!(decoded.header.ocf_flag);
+        assert_eq!(decoded.sec_header, frame.sec_header);
+        assert_eq!(decoded.data, frame.data);
+        assert_eq!(decoded.ocf, frame.ocf);
+        assert_eq!(decoded.get_buffer(), buf);
+
+        // Secondary header data field of 1 to 64 octets
+        let mut frame = frame;
+        let sec_header = TmSecondaryHeader::new(vec![0; MAX_SECONDARY_HEADER_SIZE])?;
+        frame.sec_header = Some(sec_header);
+        assert_eq!(frame.get_buffer()[6], 0x3F);
+        assert!(TmSecondaryHeader::new(vec![0; MAX_SECONDARY_HEADER_SIZE + 1]).is_err());
+        assert!(TmSecondaryHeader::new(Vec::new()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fecf_and_clcw() -> TestResult {
+        let mut clcw = Clcw::new(2);
+        clcw.report_value = 0x42;
+
+        let frame = TmFrame {
+            header: TmPrimaryHeader::new(0x0C8, 2),
+            sec_header: None,
+            data: vec![0; 10],
+            ocf: Some(clcw.to_word()),
+            fecf: Some(0),
+        };
+        let mut buf = frame.get_buffer();
+        assert_eq!(TmFrame::from_buffer(&buf, true)?.clcw(), Some(clcw));
+
+        buf[8] ^= 0x10;
+        assert!(TmFrame::from_buffer(&buf, true).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_frame() {
+        assert!(TmFrame::from_buffer(&HEADER[..4], false).is_err());
+        assert!(TmFrame::from_buffer(&HEADER, true).is_err());
+    }
+}
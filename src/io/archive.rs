@@ -0,0 +1,491 @@
+//! Archives: raw packet streams with a sidecar index, for random access by time or APID.
+//!
+//! The data file is a plain packet stream (readable by `Reader`). The index, next to it
+//! with an `.idx` extension, holds one fixed-size entry per packet: its offset, APID,
+//! sequence counter, secondary header time and ground time. It can be rebuilt from the
+//! data file, at the cost of the ground times.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::warn;
+
+use super::reader::HEADER_SIZE;
+use crate::protocol::{Packet, PrimaryHeader, SecondaryHeader};
+
+pub const INDEX_MAGIC: [u8; 8] = *b"SPKTIDX\0";
+pub const INDEX_VERSION: u16 = 1;
+
+/// Size of an index entry: offset, APID, sequence counter and both times.
+pub const ENTRY_SIZE: usize = 28;
+
+/// Encoding of unknown times in the index.
+const NO_TIME: u64 = u64::MAX;
+
+/// Path of the index of the given archive.
+pub fn index_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut path = path.as_ref().as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexEntry {
+    /// Offset of the packet in the data file.
+    pub offset: u64,
+    pub apid: u16,
+    pub sequence_counter: u16,
+    /// Time of the secondary header, if any.
+    pub packet_time: Option<Duration>,
+    /// Time the packet was received on ground (since the Unix epoch), if known.
+    pub ground_time: Option<Duration>,
+}
+
+impl IndexEntry {
+    /// Entry of a raw packet, whose header is whole.
+    fn from_raw(offset: u64, buf: &[u8], ground_time: Option<Duration>) -> IndexEntry {
+        let pri_header = PrimaryHeader::from_buffer(&buf[..HEADER_SIZE]);
+        let packet_time = match buf.get(HEADER_SIZE..HEADER_SIZE + 8) {
+            Some(sec_header) if pri_header.secondary_header_flag => {
+                Some(SecondaryHeader::from_buffer(sec_header).time())
+            }
+            _ => None,
+        };
+
+        IndexEntry {
+            offset,
+            apid: pri_header.apid,
+            sequence_counter: pri_header.sequence_counter,
+            packet_time,
+            ground_time,
+        }
+    }
+
+    fn from_buffer(buf: &[u8]) -> Result<IndexEntry> {
+        let mut cursor = &buf[..ENTRY_SIZE];
+        let read_time = |nanos: u64| match nanos {
+            NO_TIME => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        };
+
+        Ok(IndexEntry {
+            offset: cursor.read_u64::<BigEndian>()?,
+            apid: cursor.read_u16::<BigEndian>()?,
+            sequence_counter: cursor.read_u16::<BigEndian>()?,
+            packet_time: read_time(cursor.read_u64::<BigEndian>()?),
+            ground_time: read_time(cursor.read_u64::<BigEndian>()?),
+        })
+    }
+
+    fn get_buffer(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(ENTRY_SIZE);
+        let write_time = |time: Option<Duration>| time.map_or(NO_TIME, |t| t.as_nanos() as u64);
+
+        // Writing to a Vec cannot fail
+        buf.write_u64::<BigEndian>(self.offset).unwrap();
+        buf.write_u16::<BigEndian>(self.apid).unwrap();
+        buf.write_u16::<BigEndian>(self.sequence_counter).unwrap();
+        buf.write_u64::<BigEndian>(write_time(self.packet_time))
+            .unwrap();
+        buf.write_u64::<BigEndian>(write_time(self.ground_time))
+            .unwrap();
+        buf
+    }
+}
+
+/// Packets to select from an archive. Unset criteria match every packet.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+    pub apids: Option<Vec<u16>>,
+    /// Range of secondary header times: packets without one never match.
+    pub packet_time: Option<Range<Duration>>,
+    /// Range of ground times: packets without one never match.
+    pub ground_time: Option<Range<Duration>>,
+}
+
+impl Selection {
+    pub fn matches(&self, entry: &IndexEntry) -> bool {
+        let in_range = |range: &Option<Range<Duration>>, time: Option<Duration>| match range {
+            Some(range) => time.is_some_and(|time| range.contains(&time)),
+            None => true,
+        };
+
+        self.apids
+            .as_ref()
+            .is_none_or(|apids| apids.contains(&entry.apid))
+            && in_range(&self.packet_time, entry.packet_time)
+            && in_range(&self.ground_time, entry.ground_time)
+    }
+}
+
+/// Appends packets to an archive, and their entries to its index.
+pub struct ArchiveWriter {
+    data: BufWriter<File>,
+    index: BufWriter<File>,
+    offset: u64,
+}
+
+impl ArchiveWriter {
+    /// Creates the archive and its index, replacing existing ones.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<ArchiveWriter> {
+        let path = path.as_ref();
+        let data = File::create(path)
+            .with_context(|| format!("Could not create the archive {}", path.display()))?;
+        let mut index = create_index(path)?;
+        index.flush()?;
+
+        Ok(ArchiveWriter {
+            data: BufWriter::new(data),
+            index,
+            offset: 0,
+        })
+    }
+
+    /// Opens the archive to append to it, rebuilding its index if missing.
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<ArchiveWriter> {
+        let path = path.as_ref();
+        if !index_path(path).exists() {
+            rebuild_index(path)?;
+        }
+
+        let entries = read_index(path)?;
+        let offset = match entries.last() {
+            Some(entry) => entry.offset + packet_size(path, entry.offset)?,
+            None => 0,
+        };
+
+        // Dropping whatever follows the last indexed packet (e.g. a truncated write)
+        let data = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Could not open the archive {}", path.display()))?;
+        data.set_len(offset)?;
+        let mut data = BufWriter::new(data);
+        data.seek(SeekFrom::End(0))?;
+
+        let index = OpenOptions::new().append(true).open(index_path(path))?;
+        Ok(ArchiveWriter {
+            data,
+            index: BufWriter::new(index),
+            offset,
+        })
+    }
+
+    /// Archives the packet, received on ground at the given time if known.
+    pub fn write(&mut self, ground_time: Option<Duration>, pkt: Packet) -> Result<()> {
+        let buf = pkt.into_buffer();
+        let entry = IndexEntry::from_raw(self.offset, &buf, ground_time);
+
+        self.data
+            .write_all(&buf)
+            .context("Could not write the packet")?;
+        self.index
+            .write_all(&entry.get_buffer())
+            .context("Could not write the index entry")?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Flushes the packets before their index entries.
+    pub fn flush(&mut self) -> Result<()> {
+        self.data.flush().context("Could not flush the archive")?;
+        self.index.flush().context("Could not flush the index")
+    }
+}
+
+impl Drop for ArchiveWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("{:#}", e);
+        }
+    }
+}
+
+fn create_index(path: &Path) -> Result<BufWriter<File>> {
+    let path = index_path(path);
+    let file = File::create(&path)
+        .with_context(|| format!("Could not create the index {}", path.display()))?;
+
+    let mut index = BufWriter::new(file);
+    index.write_all(&INDEX_MAGIC)?;
+    index.write_u16::<BigEndian>(INDEX_VERSION)?;
+    Ok(index)
+}
+
+fn read_index(path: &Path) -> Result<Vec<IndexEntry>> {
+    let path = index_path(path);
+    let mut buf = Vec::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_end(&mut buf))
+        .with_context(|| format!("Could not read the index {}", path.display()))?;
+
+    let header_size = INDEX_MAGIC.len() + 2;
+    if buf.len() < header_size || buf[..INDEX_MAGIC.len()] != INDEX_MAGIC {
+        bail!("Not an archive index: {}", path.display());
+    }
+    let version = u16::from_be_bytes([buf[8], buf[9]]);
+    if version != INDEX_VERSION {
+        bail!("Unsupported index version `{}`", version);
+    }
+
+    let entries = &buf[header_size..];
+    if !entries.len().is_multiple_of(ENTRY_SIZE) {
+        warn!(
+            "Ignoring the truncated entry at the end of {}",
+            path.display()
+        );
+    }
+    entries
+        .chunks_exact(ENTRY_SIZE)
+        .map(IndexEntry::from_buffer)
+        .collect()
+}
+
+/// Size of the packet at the given offset, as announced by its header.
+fn packet_size(path: &Path, offset: u64) -> Result<u64> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut header = [0; HEADER_SIZE];
+    file.read_exact(&mut header)
+        .context("Index points past the end of the archive")?;
+    Ok((HEADER_SIZE + PrimaryHeader::from_buffer(&header).data_field_size()) as u64)
+}
+
+/// Rebuilds the index of an archive from its packets, which loses their ground times.
+/// Returns the number of indexed packets; a truncated last packet is ignored.
+pub fn rebuild_index<P: AsRef<Path>>(path: P) -> Result<usize> {
+    let path = path.as_ref();
+    let data = File::open(path)
+        .with_context(|| format!("Could not open the archive {}", path.display()))?;
+    let mut data = BufReader::new(data);
+    let mut index = create_index(path)?;
+
+    let mut offset = 0;
+    let mut count = 0;
+    let mut buf = vec![0; HEADER_SIZE];
+    loop {
+        buf.resize(HEADER_SIZE, 0);
+        match data.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let data_len = PrimaryHeader::from_buffer(&buf).data_field_size();
+        buf.resize(HEADER_SIZE + data_len, 0);
+        match data.read_exact(&mut buf[HEADER_SIZE..]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                warn!("Ignoring the truncated packet at offset `{}`", offset);
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        index.write_all(&IndexEntry::from_raw(offset, &buf, None).get_buffer())?;
+        offset += buf.len() as u64;
+        count += 1;
+    }
+
+    index.flush().context("Could not write the index")?;
+    Ok(count)
+}
+
+/// Gives random access to the packets of an archive, through its index.
+pub struct Archive {
+    data: BufReader<File>,
+    entries: Vec<IndexEntry>,
+}
+
+impl Archive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Archive> {
+        let path = path.as_ref();
+        let entries = read_index(path)?;
+        let data = File::open(path)
+            .with_context(|| format!("Could not open the archive {}", path.display()))?;
+
+        Ok(Archive {
+            data: BufReader::new(data),
+            entries,
+        })
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&mut self, idx: usize) -> Result<Packet> {
+        let offset = match self.entries.get(idx) {
+            Some(entry) => entry.offset,
+            None => bail!("No packet `{}` in an archive of `{}`", idx, self.len()),
+        };
+
+        self.data.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; HEADER_SIZE];
+        self.data
+            .read_exact(&mut header)
+            .with_context(|| format!("Could not read the packet at offset `{}`", offset))?;
+        let mut data = vec![0; PrimaryHeader::from_buffer(&header).data_field_size()];
+        self.data
+            .read_exact(&mut data)
+            .with_context(|| format!("Could not read the packet at offset `{}`", offset))?;
+
+        Packet::try_from_buffers(&header, &data)
+    }
+
+    /// Reads only the packets matching the selection, in archive order.
+    pub fn select<'a>(&'a mut self, selection: &Selection) -> Selected<'a> {
+        let matching = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| selection.matches(entry))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        Selected {
+            archive: self,
+            matching: matching.into_iter(),
+        }
+    }
+}
+
+/// Iterator over the packets of an archive matching a selection.
+pub struct Selected<'a> {
+    archive: &'a mut Archive,
+    matching: std::vec::IntoIter<usize>,
+}
+
+impl Iterator for Selected<'_> {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Result<Packet>> {
+        let idx = self.matching.next()?;
+        Some(self.archive.get(idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.matching.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    use crate::protocol::PktType;
+    use crate::test_utils::{valid_packet, PacketBuilder, TestResult, VALID_SOURCE};
+
+    /// Packet of the given APID, without secondary header.
+    fn packet(apid: u16) -> Packet {
+        PacketBuilder::new(PktType::Telemetry, apid)
+            .data(&[0x42])
+            .build()
+    }
+
+    fn archive_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("archive-{}-{}", std::process::id(), name))
+    }
+
+    fn write_archive(path: &Path) -> Result<()> {
+        let mut writer = ArchiveWriter::create(path)?;
+        for sec in 0..6 {
+            writer.write(Some(Duration::from_secs(100 + sec)), packet(sec as u16 % 2))?;
+        }
+        writer.write(None, valid_packet())?;
+        writer.flush()
+    }
+
+    #[test]
+    fn selections() -> TestResult {
+        let path = archive_path("selections");
+        write_archive(&path)?;
+
+        let mut archive = Archive::open(&path)?;
+        assert_eq!(archive.len(), 7);
+        let last = &archive.entries()[6];
+        assert_eq!(last.apid, 0x73);
+        assert_eq!(last.sequence_counter, 0x123);
+        assert_eq!(
+            last.packet_time,
+            Some(Duration::from_secs(0x1234 * 604_800) + Duration::from_millis(0xABCDEF))
+        );
+
+        let selection = Selection {
+            apids: Some(vec![1]),
+            ground_time: Some(Duration::from_secs(101)..Duration::from_secs(105)),
+            ..Default::default()
+        };
+        let apids = archive
+            .select(&selection)
+            .map(|pkt| pkt.map(|pkt| pkt.pri_header.apid))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(apids, [1, 1]);
+
+        let selection = Selection {
+            packet_time: Some(Duration::ZERO..Duration::MAX),
+            ..Default::default()
+        };
+        let packets = archive.select(&selection).collect::<Result<Vec<_>>>()?;
+        assert_eq!(packets.len(), 1);
+        assert_eq!(
+            packets.into_iter().next().map(Packet::into_buffer),
+            Some(VALID_SOURCE.to_vec())
+        );
+
+        fs::remove_file(index_path(&path))?;
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn rebuilt_index() -> TestResult {
+        let path = archive_path("rebuilt");
+        write_archive(&path)?;
+        let entries = Archive::open(&path)?.entries().to_vec();
+
+        // Interrupted write, then a lost index
+        let mut data = OpenOptions::new().append(true).open(&path)?;
+        data.write_all(&VALID_SOURCE[..10])?;
+        fs::remove_file(index_path(&path))?;
+
+        assert_eq!(rebuild_index(&path)?, 7);
+        let rebuilt = Archive::open(&path)?.entries().to_vec();
+        for (entry, rebuilt) in entries.iter().zip(rebuilt.iter()) {
+            assert_eq!(rebuilt.ground_time, None);
+            assert_eq!(
+                IndexEntry {
+                    ground_time: None,
+                    ..entry.clone()
+                },
+                *rebuilt
+            );
+        }
+
+        // Appending drops the truncated packet
+        let mut writer = ArchiveWriter::append(&path)?;
+        writer.write(None, packet(3))?;
+        drop(writer);
+        let mut archive = Archive::open(&path)?;
+        assert_eq!(archive.len(), 8);
+        assert_eq!(archive.get(7)?.pri_header.apid, 3);
+
+        fs::remove_file(index_path(&path))?;
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
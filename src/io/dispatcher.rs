@@ -0,0 +1,366 @@
+//! Routing of packets to independent consumers, each subscribed to part of the stream.
+//!
+//! Every subscription has its own bounded queue, with a policy for when its consumer
+//! falls behind. Packets are shared between subscriptions, not copied.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::filter::Filter;
+use crate::protocol::{Packet, PktType};
+use crate::pus::ServiceHeader;
+
+/// Packets a consumer subscribes to.
+#[derive(Clone)]
+pub enum Criterion {
+    Apid(u16),
+    ApidRange(RangeInclusive<u16>),
+    Type(PktType),
+    /// PUS service, and subservice if any.
+    Service(u8, Option<u8>),
+    Predicate(Arc<dyn Fn(&Packet) -> bool + Send + Sync>),
+    Filter(Filter),
+}
+
+impl Criterion {
+    pub fn predicate<F>(predicate: F) -> Criterion
+    where
+        F: Fn(&Packet) -> bool + Send + Sync + 'static,
+    {
+        Criterion::Predicate(Arc::new(predicate))
+    }
+
+    pub fn matches(&self, pkt: &Packet) -> bool {
+        match self {
+            Criterion::Apid(apid) => pkt.pri_header.apid == *apid,
+            Criterion::ApidRange(apids) => apids.contains(&pkt.pri_header.apid),
+            Criterion::Type(packet_type) => pkt.pri_header.packet_type == *packet_type,
+            Criterion::Service(service, subservice) => match ServiceHeader::from_packet(pkt) {
+                Some((header, _)) => {
+                    header.service == *service
+                        && subservice.is_none_or(|subservice| header.subservice == subservice)
+                }
+                None => false,
+            },
+            Criterion::Predicate(predicate) => predicate(pkt),
+            Criterion::Filter(filter) => filter.matches(pkt),
+        }
+    }
+}
+
+impl fmt::Debug for Criterion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Criterion::Apid(apid) => write!(f, "Apid({:#x})", apid),
+            Criterion::ApidRange(apids) => write!(f, "ApidRange({:#x?})", apids),
+            Criterion::Type(packet_type) => write!(f, "Type({:?})", packet_type),
+            Criterion::Service(service, subservice) => {
+                write!(f, "Service({}, {:?})", service, subservice)
+            }
+            Criterion::Predicate(_) => write!(f, "Predicate"),
+            Criterion::Filter(filter) => write!(f, "Filter({:?})", filter.to_string()),
+        }
+    }
+}
+
+/// What to do with a packet when the queue of a subscription is full.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+    /// Wait for the consumer: slows down every other subscription.
+    Block,
+    DropOldest,
+    DropNewest,
+}
+
+struct State {
+    packets: VecDeque<Arc<Packet>>,
+    dropped: u64,
+    // No more packets from the dispatcher
+    closed: bool,
+    // No more consumer
+    unsubscribed: bool,
+}
+
+struct Queue {
+    state: Mutex<State>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl Queue {
+    /// Queues the packet, returning whether the consumer is still subscribed.
+    fn push(&self, pkt: Arc<Packet>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while state.packets.len() >= self.capacity && !state.unsubscribed {
+            match self.overflow {
+                Overflow::Block => state = self.not_full.wait(state).unwrap(),
+                Overflow::DropOldest => {
+                    state.packets.pop_front();
+                    state.dropped += 1;
+                }
+                Overflow::DropNewest => {
+                    state.dropped += 1;
+                    return true;
+                }
+            }
+        }
+        if state.unsubscribed {
+            return false;
+        }
+
+        state.packets.push_back(pkt);
+        self.not_empty.notify_one();
+        true
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_all();
+    }
+}
+
+/// Receiving end of a subscription. Dropping it unsubscribes.
+pub struct Subscription {
+    queue: Arc<Queue>,
+}
+
+impl Subscription {
+    /// Next packet, or `None` once the dispatcher is gone and the queue is empty.
+    pub fn recv(&self) -> Option<Arc<Packet>> {
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(pkt) = state.packets.pop_front() {
+                self.queue.not_full.notify_one();
+                return Some(pkt);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.queue.not_empty.wait(state).unwrap();
+        }
+    }
+
+    /// Same as `recv`, giving up after the timeout.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Arc<Packet>> {
+        let state = self.queue.state.lock().unwrap();
+        let (mut state, _) = self
+            .queue
+            .not_empty
+            .wait_timeout_while(state, timeout, |state| {
+                state.packets.is_empty() && !state.closed
+            })
+            .unwrap();
+
+        let pkt = state.packets.pop_front();
+        if pkt.is_some() {
+            self.queue.not_full.notify_one();
+        }
+        pkt
+    }
+
+    pub fn try_recv(&self) -> Option<Arc<Packet>> {
+        self.recv_timeout(Duration::ZERO)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Arc<Packet>> + '_ {
+        std::iter::from_fn(move || self.recv())
+    }
+
+    /// Packets dropped so far because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.state.lock().unwrap().dropped
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.state.lock().unwrap().packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().unsubscribed = true;
+        self.queue.not_full.notify_all();
+    }
+}
+
+/// Routes every packet to the subscriptions it matches.
+#[derive(Default)]
+pub struct Dispatcher {
+    subscribers: Vec<(Criterion, Arc<Queue>)>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Dispatcher {
+        Dispatcher::default()
+    }
+
+    /// Subscribes to the matching packets, through a queue of the given capacity.
+    pub fn subscribe(
+        &mut self,
+        criterion: Criterion,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Subscription {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(State {
+                packets: VecDeque::with_capacity(capacity),
+                dropped: 0,
+                closed: false,
+                unsubscribed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            overflow,
+        });
+
+        self.subscribers.push((criterion, Arc::clone(&queue)));
+        Subscription { queue }
+    }
+
+    /// Number of active subscriptions.
+    pub fn subscriptions(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// Routes the packet, returning the number of subscriptions it matched.
+    pub fn dispatch(&mut self, pkt: Packet) -> usize {
+        let pkt = Arc::new(pkt);
+        let mut matched = 0;
+
+        self.subscribers.retain(|(criterion, queue)| {
+            if !criterion.matches(&pkt) {
+                return !queue.state.lock().unwrap().unsubscribed;
+            }
+            let subscribed = queue.push(Arc::clone(&pkt));
+            if subscribed {
+                matched += 1;
+            }
+            subscribed
+        });
+
+        matched
+    }
+
+    /// Routes the packets of the channel until it is closed, then closes the subscriptions.
+    pub fn run(mut self, channel: Receiver<Packet>) {
+        for pkt in channel {
+            self.dispatch(pkt);
+        }
+    }
+}
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        for (_, queue) in &self.subscribers {
+            queue.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+
+    use crate::test_utils::PacketBuilder;
+
+    fn packet(packet_type: PktType, apid: u16, service: u8, subservice: u8) -> Packet {
+        PacketBuilder::new(packet_type, apid)
+            .data(&[service, subservice])
+            .build()
+    }
+
+    #[test]
+    fn routing() {
+        let mut dispatcher = Dispatcher::new();
+        let housekeeping =
+            dispatcher.subscribe(Criterion::Service(3, Some(25)), 8, Overflow::Block);
+        let events = dispatcher.subscribe(Criterion::Service(5, None), 8, Overflow::Block);
+        let science = dispatcher.subscribe(Criterion::ApidRange(0x100..=0x1FF), 8, Overflow::Block);
+        let commands =
+            dispatcher.subscribe(Criterion::Type(PktType::Telecommand), 8, Overflow::Block);
+        let odd = dispatcher.subscribe(
+            Criterion::predicate(|pkt| pkt.pri_header.apid % 2 == 1),
+            8,
+            Overflow::Block,
+        );
+
+        let filtered = dispatcher.subscribe(
+            Criterion::Filter("type == tc or data[0] == 128".parse().unwrap()),
+            8,
+            Overflow::Block,
+        );
+
+        let mut dispatch =
+            |packet_type, apid, service| dispatcher.dispatch(packet(packet_type, apid, service, 1));
+        assert_eq!(dispatch(PktType::Telemetry, 0x10, 3), 0);
+        assert_eq!(dispatch(PktType::Telemetry, 0x11, 5), 2);
+        assert_eq!(dispatch(PktType::Telemetry, 0x101, 128), 3);
+        assert_eq!(dispatch(PktType::Telecommand, 0x20, 3), 2);
+        assert_eq!(
+            dispatcher.dispatch(packet(PktType::Telemetry, 0x10, 3, 25)),
+            1
+        );
+        drop(dispatcher);
+
+        assert_eq!(housekeeping.iter().count(), 1);
+        assert_eq!(events.iter().count(), 1);
+        assert_eq!(science.recv().unwrap().pri_header.apid, 0x101);
+        assert!(science.recv().is_none());
+        assert_eq!(commands.iter().count(), 1);
+        assert_eq!(odd.iter().count(), 2);
+        assert_eq!(filtered.iter().count(), 2);
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let mut dispatcher = Dispatcher::new();
+        let oldest = dispatcher.subscribe(Criterion::Apid(1), 2, Overflow::DropOldest);
+        let newest = dispatcher.subscribe(Criterion::Apid(1), 2, Overflow::DropNewest);
+        for service in 0..5 {
+            dispatcher.dispatch(packet(PktType::Telemetry, 1, service, 0));
+        }
+        drop(dispatcher);
+
+        let services = |sub: &Subscription| {
+            sub.iter()
+                .map(|pkt| ServiceHeader::from_packet(&pkt).unwrap().0.service)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(oldest.dropped(), 3);
+        assert_eq!(services(&oldest), [3, 4]);
+        assert_eq!(newest.dropped(), 3);
+        assert_eq!(services(&newest), [0, 1]);
+    }
+
+    #[test]
+    fn blocking_and_unsubscribing() {
+        let mut dispatcher = Dispatcher::new();
+        let slow = dispatcher.subscribe(Criterion::Apid(1), 1, Overflow::Block);
+        let gone = dispatcher.subscribe(Criterion::Apid(1), 1, Overflow::Block);
+        drop(gone);
+
+        let producer = thread::spawn(move || {
+            for _ in 0..10 {
+                dispatcher.dispatch(packet(PktType::Telemetry, 1, 0, 0));
+            }
+            dispatcher.subscriptions()
+        });
+
+        assert_eq!(slow.iter().count(), 10);
+        assert_eq!(slow.dropped(), 0);
+        assert_eq!(producer.join().unwrap(), 1);
+    }
+}
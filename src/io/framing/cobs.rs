@@ -0,0 +1,153 @@
+//! Consistent Overhead Byte Stuffing: zero-free frames, delimited by zeros.
+
+use log::warn;
+
+use super::{Deframer, Framer, MAX_PAYLOAD_SIZE};
+
+pub const DELIMITER: u8 = 0x00;
+
+/// Code of the blocks of 254 non-zero bytes, not followed by a zero.
+const MAX_CODE: u8 = 0xFF;
+
+/// Encodes the data, without delimiters.
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(data.len() + data.len() / 254 + 1);
+    let mut code_idx = 0;
+    let mut code = 1;
+    buf.push(0);
+
+    for byte in data {
+        if *byte != 0 {
+            buf.push(*byte);
+            code += 1;
+        }
+        if *byte == 0 || code == MAX_CODE {
+            buf[code_idx] = code;
+            code_idx = buf.len();
+            code = 1;
+            buf.push(0);
+        }
+    }
+    buf[code_idx] = code;
+
+    buf
+}
+
+/// Decodes the data of a frame, without delimiters.
+pub fn decode(buf: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(buf.len());
+
+    let mut idx = 0;
+    while idx < buf.len() {
+        let code = buf[idx];
+        let end = idx + code as usize;
+        if code == 0 || end > buf.len() {
+            return None;
+        }
+        data.extend_from_slice(&buf[idx + 1..end]);
+        if code != MAX_CODE && end < buf.len() {
+            data.push(0);
+        }
+        idx = end;
+    }
+
+    Some(data)
+}
+
+pub struct Cobs {
+    frame: Vec<u8>,
+    // Whether a delimiter was ever seen: bytes before it are line noise
+    synced: bool,
+}
+
+impl Cobs {
+    pub fn new() -> Cobs {
+        Cobs {
+            frame: Vec::new(),
+            synced: false,
+        }
+    }
+}
+
+impl Default for Cobs {
+    fn default() -> Self {
+        Cobs::new()
+    }
+}
+
+impl Framer for Cobs {
+    /// Frames start with a delimiter as well, flushing any line noise at the receiver.
+    fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![DELIMITER];
+        buf.append(&mut encode(payload));
+        buf.push(DELIMITER);
+        buf
+    }
+}
+
+impl Deframer for Cobs {
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if byte == DELIMITER {
+            let valid = self.synced;
+            self.synced = true;
+            let frame = std::mem::take(&mut self.frame);
+
+            if !valid || frame.is_empty() {
+                return None;
+            }
+            let payload = decode(&frame);
+            if payload.is_none() {
+                warn!(
+                    "Dropping COBS frame of size `{}`: invalid codes",
+                    frame.len()
+                );
+            }
+            return payload;
+        }
+        if !self.synced {
+            return None;
+        }
+
+        // Overhead: one byte every 254
+        if self.frame.len() > MAX_PAYLOAD_SIZE + MAX_PAYLOAD_SIZE / 254 {
+            warn!(
+                "Dropping COBS frame longer than `{}` bytes",
+                MAX_PAYLOAD_SIZE
+            );
+            self.synced = false;
+            self.frame.clear();
+            return None;
+        }
+        self.frame.push(byte);
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reference_encodings() {
+        let cases: [(&[u8], &[u8]); 5] = [
+            (&[0x00], &[0x01, 0x01]),
+            (&[0x00, 0x00], &[0x01, 0x01, 0x01]),
+            (&[0x11, 0x22, 0x00, 0x33], &[0x03, 0x11, 0x22, 0x02, 0x33]),
+            (&[0x11, 0x00, 0x00, 0x00], &[0x02, 0x11, 0x01, 0x01, 0x01]),
+            (&[], &[0x01]),
+        ];
+        for (data, encoded) in cases.iter() {
+            assert_eq!(encode(data), *encoded);
+            assert_eq!(decode(encoded).as_deref(), Some(*data));
+        }
+
+        // Blocks of 254 non-zero bytes
+        let data: Vec<u8> = (1..=255).collect();
+        let encoded = encode(&data);
+        assert_eq!(encoded[0], MAX_CODE);
+        assert_eq!(encoded[255..], [0x02, 0xFF]);
+        assert_eq!(decode(&encoded), Some(data));
+
+        assert_eq!(decode(&[0x05, 0x11]), None);
+    }
+}
@@ -0,0 +1,154 @@
+//! Asynchronous HDLC-like framing (RFC 1662): flags, byte stuffing and a 16-bit FCS.
+
+use log::warn;
+
+use super::{Deframer, Framer, MAX_PAYLOAD_SIZE};
+
+pub const FLAG: u8 = 0x7E;
+pub const ESCAPE: u8 = 0x7D;
+
+/// Bit flipped in escaped bytes.
+const ESCAPE_XOR: u8 = 0x20;
+
+/// Size of the Frame Check Sequence, sent least significant byte first.
+pub const FCS_SIZE: usize = 2;
+
+/// Remainder of the FCS computed over a frame and its own (valid) FCS.
+const GOOD_FCS: u16 = 0xF0B8;
+
+/// FCS-16 of ITU-T X.25 (reflected polynomial `0x8408`, complemented).
+pub fn fcs(data: &[u8]) -> u16 {
+    !register(data)
+}
+
+fn register(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 0x0001 != 0 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+pub struct Hdlc {
+    frame: Vec<u8>,
+    // Whether a flag was ever seen: bytes before it are line noise
+    synced: bool,
+    escaped: bool,
+}
+
+impl Hdlc {
+    pub fn new() -> Hdlc {
+        Hdlc {
+            frame: Vec::new(),
+            synced: false,
+            escaped: false,
+        }
+    }
+
+    fn drop_frame(&mut self) {
+        self.synced = false;
+        self.frame.clear();
+    }
+}
+
+impl Default for Hdlc {
+    fn default() -> Self {
+        Hdlc::new()
+    }
+}
+
+impl Framer for Hdlc {
+    fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let fcs = fcs(payload).to_le_bytes();
+
+        let mut buf = Vec::with_capacity(payload.len() + FCS_SIZE + 2);
+        buf.push(FLAG);
+        for byte in payload.iter().chain(fcs.iter()) {
+            match *byte {
+                FLAG | ESCAPE => buf.extend_from_slice(&[ESCAPE, byte ^ ESCAPE_XOR]),
+                byte => buf.push(byte),
+            }
+        }
+        buf.push(FLAG);
+        buf
+    }
+}
+
+impl Deframer for Hdlc {
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if byte == FLAG {
+            // Escape followed by a flag: aborted frame
+            let valid = self.synced && !self.escaped;
+            self.synced = true;
+            self.escaped = false;
+            let mut frame = std::mem::take(&mut self.frame);
+
+            if !valid || frame.is_empty() {
+                return None;
+            }
+            if frame.len() <= FCS_SIZE || register(&frame) != GOOD_FCS {
+                warn!("Dropping HDLC frame of size `{}`: invalid FCS", frame.len());
+                return None;
+            }
+            frame.truncate(frame.len() - FCS_SIZE);
+            return Some(frame);
+        }
+        if !self.synced {
+            return None;
+        }
+
+        let byte = match (self.escaped, byte) {
+            (false, ESCAPE) => {
+                self.escaped = true;
+                return None;
+            }
+            (false, byte) => byte,
+            (true, byte) => byte ^ ESCAPE_XOR,
+        };
+        self.escaped = false;
+
+        if self.frame.len() == MAX_PAYLOAD_SIZE + FCS_SIZE {
+            warn!(
+                "Dropping HDLC frame longer than `{}` bytes",
+                MAX_PAYLOAD_SIZE
+            );
+            self.drop_frame();
+            return None;
+        }
+        self.frame.push(byte);
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fcs() {
+        // Check value of CRC-16/X-25
+        assert_eq!(fcs(b"123456789"), 0x906E);
+    }
+
+    #[test]
+    fn stuffing_and_fcs() {
+        let mut hdlc = Hdlc::new();
+        let frame = hdlc.encode(&[FLAG, 0x01, ESCAPE]);
+        assert_eq!(frame[..6], [FLAG, ESCAPE, 0x5E, 0x01, ESCAPE, 0x5D]);
+
+        let payloads: Vec<Vec<u8>> = frame.iter().filter_map(|b| hdlc.push(*b)).collect();
+        assert_eq!(payloads, [vec![FLAG, 0x01, ESCAPE]]);
+
+        // Corrupted payload
+        let mut frame = hdlc.encode(&[0x01, 0x02, 0x03]);
+        frame[2] ^= 0x04;
+        assert!(frame.iter().all(|b| hdlc.push(*b).is_none()));
+    }
+}
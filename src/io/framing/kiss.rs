@@ -0,0 +1,140 @@
+//! KISS TNC framing: SLIP-like delimiters, and a leading port/command byte.
+
+use log::{debug, warn};
+
+use super::{Deframer, Framer, MAX_PAYLOAD_SIZE};
+
+pub const FEND: u8 = 0xC0;
+pub const FESC: u8 = 0xDB;
+pub const TFEND: u8 = 0xDC;
+pub const TFESC: u8 = 0xDD;
+
+/// Command of the frames carrying data (low nibble of the port/command byte).
+pub const DATA_FRAME: u8 = 0x00;
+
+pub struct Kiss {
+    /// Port of the frames sent (high nibble of the port/command byte).
+    port: u8,
+    payload: Vec<u8>,
+    // Whether a frame delimiter was ever seen: bytes before it are line noise
+    synced: bool,
+    escaped: bool,
+}
+
+impl Kiss {
+    pub fn new(port: u8) -> Kiss {
+        Kiss {
+            port: port & 0x0F,
+            payload: Vec::new(),
+            synced: false,
+            escaped: false,
+        }
+    }
+
+    fn drop_frame(&mut self) {
+        self.synced = false;
+        self.payload.clear();
+    }
+}
+
+/// Port 0, the only one of single-port TNCs.
+impl Default for Kiss {
+    fn default() -> Self {
+        Kiss::new(0)
+    }
+}
+
+impl Framer for Kiss {
+    fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(payload.len() + 3);
+        buf.push(FEND);
+        buf.push((self.port << 4) | DATA_FRAME);
+        for byte in payload {
+            match *byte {
+                FEND => buf.extend_from_slice(&[FESC, TFEND]),
+                FESC => buf.extend_from_slice(&[FESC, TFESC]),
+                byte => buf.push(byte),
+            }
+        }
+        buf.push(FEND);
+        buf
+    }
+}
+
+impl Deframer for Kiss {
+    /// Frames of every port are accepted: only their command is checked.
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if byte == FEND {
+            let valid = self.synced && !self.escaped;
+            self.synced = true;
+            self.escaped = false;
+            let frame = std::mem::take(&mut self.payload);
+
+            return match frame.split_first() {
+                Some((command, payload)) if valid => {
+                    if command & 0x0F != DATA_FRAME {
+                        debug!("Ignoring KISS command `{:#04X}`", command);
+                        return None;
+                    }
+                    Some(payload.to_vec())
+                }
+                _ => None,
+            };
+        }
+        if !self.synced {
+            return None;
+        }
+
+        let byte = match (self.escaped, byte) {
+            (false, FESC) => {
+                self.escaped = true;
+                return None;
+            }
+            (false, byte) => byte,
+            (true, TFEND) => FEND,
+            (true, TFESC) => FESC,
+            (true, byte) => {
+                warn!(
+                    "Dropping KISS frame: invalid escape sequence `{:#04X}`",
+                    byte
+                );
+                self.drop_frame();
+                return None;
+            }
+        };
+        self.escaped = false;
+
+        // Port/command byte on top of the payload
+        if self.payload.len() > MAX_PAYLOAD_SIZE {
+            warn!(
+                "Dropping KISS frame longer than `{}` bytes",
+                MAX_PAYLOAD_SIZE
+            );
+            self.drop_frame();
+            return None;
+        }
+        self.payload.push(byte);
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands_and_escaping() {
+        let mut kiss = Kiss::new(2);
+        let frame = kiss.encode(&[FEND, 0x01, FESC]);
+        assert_eq!(frame, [FEND, 0x20, FESC, TFEND, 0x01, FESC, TFESC, FEND]);
+
+        // TX delay command (`0x01`) ignored, back-to-back frames sharing a delimiter
+        let stream = [FEND, 0x01, 0x32, FEND, 0x00, 0x05, FEND];
+        let payloads: Vec<Vec<u8>> = frame
+            .iter()
+            .chain(stream.iter())
+            .filter_map(|b| kiss.push(*b))
+            .collect();
+        assert_eq!(payloads, [vec![FEND, 0x01, FESC], vec![0x05]]);
+    }
+}
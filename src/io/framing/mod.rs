@@ -0,0 +1,154 @@
+//! Serial-link framing: each space packet is wrapped in a frame of the link layer.
+//!
+//! Deframers are fed byte by byte, so they can sit behind any byte stream (capture
+//! file, pseudo-terminal...) and are resynchronised by the next frame delimiter.
+
+use std::io::{self, Read, Write};
+
+// Reachable modules
+pub mod cobs;
+pub mod hdlc;
+pub mod kiss;
+pub mod slip;
+
+// Re-exporting
+pub use cobs::Cobs;
+pub use hdlc::Hdlc;
+pub use kiss::Kiss;
+pub use slip::Slip;
+
+use super::reader::BUFFER_SIZE;
+
+/// Max size of a payload: any longer frame is dropped.
+pub const MAX_PAYLOAD_SIZE: usize = BUFFER_SIZE;
+
+/// Size of the chunks read from the underlying stream.
+const CHUNK_SIZE: usize = 4096;
+
+pub trait Framer {
+    /// Wraps the payload in a whole frame, delimiters included.
+    fn encode(&self, payload: &[u8]) -> Vec<u8>;
+}
+
+pub trait Deframer {
+    /// Consumes the next byte, returning the payload of the frame it completes, if any.
+    ///
+    /// Invalid frames are reported and dropped.
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>>;
+}
+
+/// Stream of the payloads of the frames read from the underlying stream.
+///
+/// Frames dropped by the deframer are missing from the stream: each frame should carry
+/// whole packets, so that `Reader` is not desynchronised.
+pub struct DeframedReader<R, D> {
+    reader: R,
+    deframer: D,
+    chunk: Vec<u8>,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read, D: Deframer> DeframedReader<R, D> {
+    pub fn new(src: R, deframer: D) -> DeframedReader<R, D> {
+        DeframedReader {
+            reader: src,
+            deframer,
+            chunk: vec![0; CHUNK_SIZE],
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: Read, D: Deframer> Read for DeframedReader<R, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.pending.len() {
+            let len = self.reader.read(&mut self.chunk)?;
+            if len == 0 {
+                return Ok(0);
+            }
+
+            self.pending.clear();
+            self.position = 0;
+            for byte in &self.chunk[..len] {
+                if let Some(mut payload) = self.deframer.push(*byte) {
+                    self.pending.append(&mut payload);
+                }
+            }
+        }
+
+        let len = buf.len().min(self.pending.len() - self.position);
+        buf[..len].copy_from_slice(&self.pending[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Stream wrapping each call to `write` in its own frame.
+///
+/// Meant to be used by `Writer`, which writes whole packets at once.
+pub struct FramedWriter<W, F> {
+    writer: W,
+    framer: F,
+}
+
+impl<W: Write, F: Framer> FramedWriter<W, F> {
+    pub fn new(dst: W, framer: F) -> FramedWriter<W, F> {
+        FramedWriter {
+            writer: dst,
+            framer,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, F: Framer> Write for FramedWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write_all(&self.framer.encode(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::io::{Reader, Writer};
+    use crate::protocol::Packet;
+    use crate::test_utils::{valid_packet, TestResult, VALID_SOURCE};
+
+    fn round_trip<F: Framer, D: Deframer + Unpin>(framer: F, deframer: D) -> TestResult {
+        let mut writer = Writer::new(FramedWriter::new(Vec::new(), framer));
+        for _ in 0..3 {
+            writer.write(valid_packet())?;
+        }
+        let mut stream = vec![0x12, 0x34]; // line noise before the first frame
+        stream.append(&mut writer.into_inner().into_inner());
+
+        let (mut reader, receiver) = Reader::new(DeframedReader::new(&stream[..], deframer));
+        reader.run()?;
+        drop(reader);
+
+        let packets: Vec<Vec<u8>> = receiver.iter().map(Packet::into_buffer).collect();
+        assert_eq!(packets, vec![VALID_SOURCE.to_vec(); 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn packets_through_every_framing() -> TestResult {
+        round_trip(Kiss::default(), Kiss::default())?;
+        round_trip(Slip::new(), Slip::new())?;
+        round_trip(Hdlc::new(), Hdlc::new())?;
+        round_trip(Cobs::new(), Cobs::new())?;
+        Ok(())
+    }
+}
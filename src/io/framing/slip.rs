@@ -0,0 +1,123 @@
+//! Serial Line IP framing (RFC 1055).
+
+use log::warn;
+
+use super::{Deframer, Framer, MAX_PAYLOAD_SIZE};
+
+pub const END: u8 = 0xC0;
+pub const ESC: u8 = 0xDB;
+pub const ESC_END: u8 = 0xDC;
+pub const ESC_ESC: u8 = 0xDD;
+
+pub struct Slip {
+    payload: Vec<u8>,
+    // Whether a frame delimiter was ever seen: bytes before it are line noise
+    synced: bool,
+    escaped: bool,
+}
+
+impl Slip {
+    pub fn new() -> Slip {
+        Slip {
+            payload: Vec::new(),
+            synced: false,
+            escaped: false,
+        }
+    }
+
+    fn drop_frame(&mut self) {
+        self.synced = false;
+        self.payload.clear();
+    }
+}
+
+impl Default for Slip {
+    fn default() -> Self {
+        Slip::new()
+    }
+}
+
+impl Framer for Slip {
+    /// Frames start with an `END` as well, flushing any line noise at the receiver.
+    fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(payload.len() + 2);
+        buf.push(END);
+        for byte in payload {
+            match *byte {
+                END => buf.extend_from_slice(&[ESC, ESC_END]),
+                ESC => buf.extend_from_slice(&[ESC, ESC_ESC]),
+                byte => buf.push(byte),
+            }
+        }
+        buf.push(END);
+        buf
+    }
+}
+
+impl Deframer for Slip {
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if byte == END {
+            let valid = self.synced && !self.escaped;
+            self.synced = true;
+            self.escaped = false;
+            let payload = std::mem::take(&mut self.payload);
+            return match valid && !payload.is_empty() {
+                true => Some(payload),
+                false => None,
+            };
+        }
+        if !self.synced {
+            return None;
+        }
+
+        let byte = match (self.escaped, byte) {
+            (false, ESC) => {
+                self.escaped = true;
+                return None;
+            }
+            (false, byte) => byte,
+            (true, ESC_END) => END,
+            (true, ESC_ESC) => ESC,
+            (true, byte) => {
+                warn!(
+                    "Dropping SLIP frame: invalid escape sequence `{:#04X}`",
+                    byte
+                );
+                self.drop_frame();
+                return None;
+            }
+        };
+        self.escaped = false;
+
+        if self.payload.len() == MAX_PAYLOAD_SIZE {
+            warn!(
+                "Dropping SLIP frame longer than `{}` bytes",
+                MAX_PAYLOAD_SIZE
+            );
+            self.drop_frame();
+            return None;
+        }
+        self.payload.push(byte);
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        let mut slip = Slip::new();
+        let frame = slip.encode(&[0x01, END, ESC, 0x02]);
+        assert_eq!(frame, [END, 0x01, ESC, ESC_END, ESC, ESC_ESC, 0x02, END]);
+
+        let payloads: Vec<Vec<u8>> = frame.iter().filter_map(|b| slip.push(*b)).collect();
+        assert_eq!(payloads, [vec![0x01, END, ESC, 0x02]]);
+
+        // Invalid escape: the frame is dropped up to the next delimiter
+        let frame = [END, 0x01, ESC, 0x02, 0x03, END, 0x04, END];
+        let payloads: Vec<Vec<u8>> = frame.iter().filter_map(|b| slip.push(*b)).collect();
+        assert_eq!(payloads, [vec![0x04]]);
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::{BufRead, BufReader, Read};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+use super::reader::BUFFER_SIZE;
+use crate::protocol::encapsulation::{self, EncapsulationPacket};
+use crate::protocol::{self, Packet, PrimaryHeader, HEADER_SIZE};
+
+/// Size of the channel to communicate with the reader
+const CHANNEL_SIZE: usize = 1024;
+
+/// Max size of the encapsulation packets: larger ones are taken for a corrupted stream.
+const ENCAPSULATION_MAX_SIZE: usize = 16 * BUFFER_SIZE;
+
+#[derive(Debug)]
+pub enum MixedPacket {
+    Space(Packet),
+    Encapsulation(EncapsulationPacket),
+}
+
+/// Same as `Reader`, for streams interleaving space packets and encapsulation packets.
+///
+/// Both kinds are told apart by the packet version number: the 3 first bits of a packet.
+/// Idle encapsulation packets are dropped.
+pub struct MixedReader<R> {
+    reader: BufReader<R>,
+    buf: Vec<u8>,
+    channel: SyncSender<MixedPacket>,
+}
+
+impl<R: Read + Unpin> MixedReader<R> {
+    pub fn new(src: R) -> (MixedReader<R>, Receiver<MixedPacket>) {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_SIZE);
+        let reader = BufReader::with_capacity(BUFFER_SIZE, src);
+
+        (
+            MixedReader {
+                reader,
+                buf: Vec::new(),
+                channel: sender,
+            },
+            receiver,
+        )
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        while let Some(pkt) = self.read()? {
+            match pkt {
+                MixedPacket::Encapsulation(pkt) if pkt.is_idle() => {
+                    debug!("Dropping idle encapsulation packet");
+                }
+                pkt => self.channel.send(pkt)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Next packet of the stream, or `None` at its end. Invalid space packets (e.g. wrong
+    /// checksum) are reported and dropped.
+    fn read(&mut self) -> Result<Option<MixedPacket>> {
+        loop {
+            let version_number = match self.reader.fill_buf()?.first() {
+                Some(first) => first >> 5,
+                None => return Ok(None),
+            };
+
+            self.buf.clear();
+            match version_number {
+                protocol::VERSION_NUMBER => {
+                    self.read_to(HEADER_SIZE)?;
+                    let data_len = PrimaryHeader::from_buffer(&self.buf).data_field_size();
+                    self.read_to(HEADER_SIZE + data_len)?;
+
+                    let (header, data) = self.buf.split_at(HEADER_SIZE);
+                    match Packet::try_from_buffers(header, data) {
+                        Ok(pkt) => return Ok(Some(MixedPacket::Space(pkt))),
+                        Err(e) => warn!("Dropping invalid space packet: {:#}", e),
+                    }
+                }
+                encapsulation::VERSION_NUMBER => {
+                    self.read_to(1)?;
+                    self.read_to(encapsulation::header_size(self.buf[0]))?;
+                    let len = EncapsulationPacket::packet_length(&self.buf)?;
+                    if len > ENCAPSULATION_MAX_SIZE {
+                        bail!("Encapsulation packet of length `{}` is too large", len);
+                    }
+                    self.read_to(len)?;
+
+                    let pkt = EncapsulationPacket::from_buffer(&self.buf)?;
+                    return Ok(Some(MixedPacket::Encapsulation(pkt)));
+                }
+                _ => bail!("Unknown packet version number `{}`", version_number),
+            }
+        }
+    }
+
+    /// Reads the current packet up to the given size.
+    fn read_to(&mut self, len: usize) -> Result<()> {
+        let start = self.buf.len();
+        self.buf.resize(len, 0);
+        self.reader
+            .read_exact(&mut self.buf[start..])
+            .with_context(|| format!("Could not read the packet up to size `{}`", len))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::{TestResult, VALID_SOURCE};
+
+    #[test]
+    fn interleaved_packets() -> TestResult {
+        let encapsulated = EncapsulationPacket::new(2, vec![0x45, 0x00, 0x00]);
+        let mut stream = VALID_SOURCE.to_vec();
+        stream.append(&mut EncapsulationPacket::idle().get_buffer()?);
+        stream.append(&mut encapsulated.get_buffer()?);
+        stream.extend_from_slice(&VALID_SOURCE);
+        // Dropped: wrong checksum
+        let mut corrupted = VALID_SOURCE;
+        corrupted[21] ^= 0xFF;
+        stream.extend_from_slice(&corrupted);
+
+        let (mut reader, receiver) = MixedReader::new(&stream[..]);
+        reader.run()?;
+        drop(reader);
+
+        let packets: Vec<MixedPacket> = receiver.iter().collect();
+        assert_eq!(packets.len(), 3);
+        assert!(matches!(packets[0], MixedPacket::Space(_)));
+        match &packets[1] {
+            MixedPacket::Encapsulation(pkt) => assert_eq!(*pkt, encapsulated),
+            pkt => panic!("Unexpected packet: {:?}", pkt),
+        }
+        assert!(matches!(packets[2], MixedPacket::Space(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_version_number() {
+        let (mut reader, _receiver) = MixedReader::new(&[0x20, 0x00][..]);
+        assert!(reader.run().is_err());
+    }
+}
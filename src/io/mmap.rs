@@ -0,0 +1,223 @@
+//! Memory-mapped packet dumps: packets are read in place, without copying.
+//!
+//! Packet boundaries can only be found by walking the headers in order, so parallel
+//! processing first builds the boundary index, then splits it across threads.
+
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use super::reader::HEADER_SIZE;
+use crate::protocol::{hasher, Packet, PrimaryHeader};
+
+/// Packet borrowed from the mapped region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PacketView<'a> {
+    offset: usize,
+    buf: &'a [u8],
+}
+
+impl<'a> PacketView<'a> {
+    /// Offset of the packet in the dump.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The whole packet: header and data field.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn header(&self) -> &'a [u8] {
+        &self.buf[..HEADER_SIZE]
+    }
+
+    /// Data field, including the checksum.
+    pub fn data(&self) -> &'a [u8] {
+        &self.buf[HEADER_SIZE..]
+    }
+
+    pub fn pri_header(&self) -> PrimaryHeader {
+        PrimaryHeader::from_buffer(self.header())
+    }
+
+    pub fn apid(&self) -> u16 {
+        self.pri_header().apid
+    }
+
+    pub fn sequence_counter(&self) -> u16 {
+        self.pri_header().sequence_counter
+    }
+
+    /// Whether the checksum of the packet is valid.
+    pub fn is_valid(&self) -> bool {
+        self.buf.len() >= HEADER_SIZE + 2 && hasher::compute(self.buf) == 0
+    }
+
+    /// Copies the packet out of the mapped region.
+    pub fn to_packet(&self) -> Result<Packet> {
+        Packet::try_from_buffers(self.header(), self.data())
+    }
+}
+
+/// Size of the packet at the start of the buffer, if its header is whole.
+fn packet_size(buf: &[u8]) -> Option<usize> {
+    buf.get(..HEADER_SIZE)
+        .map(|header| HEADER_SIZE + PrimaryHeader::from_buffer(header).data_field_size())
+}
+
+/// Offsets of the packets of a raw dump. A truncated last packet is ignored.
+pub fn boundaries(buf: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+
+    while let Some(size) = packet_size(&buf[offset..]) {
+        if offset + size > buf.len() {
+            break;
+        }
+        offsets.push(offset);
+        offset += size;
+    }
+    if offset < buf.len() {
+        warn!("Ignoring the truncated packet at offset `{}`", offset);
+    }
+
+    offsets
+}
+
+/// Walks the packets of a raw dump, in order.
+pub struct Views<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Views<'a> {
+    pub fn new(buf: &'a [u8]) -> Views<'a> {
+        Views { buf, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for Views<'a> {
+    type Item = PacketView<'a>;
+
+    fn next(&mut self) -> Option<PacketView<'a>> {
+        let rest = &self.buf[self.offset..];
+        let size = packet_size(rest).filter(|size| *size <= rest.len())?;
+
+        let view = PacketView {
+            offset: self.offset,
+            buf: &rest[..size],
+        };
+        self.offset += size;
+        Some(view)
+    }
+}
+
+/// Raw packet dump mapped in memory.
+pub struct MappedFile {
+    mmap: Mmap,
+}
+
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MappedFile> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+
+        // SAFETY: the dump must not be modified while mapped. It is only ever read, and
+        // dumps are not written to once complete.
+        let mmap = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Could not map {}", path.display()))?;
+        Ok(MappedFile { mmap })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    pub fn views(&self) -> Views<'_> {
+        Views::new(&self.mmap)
+    }
+
+    pub fn boundaries(&self) -> Vec<usize> {
+        boundaries(&self.mmap)
+    }
+
+    /// View of the packet at the given offset, which must be a packet boundary.
+    pub fn view(&self, offset: usize) -> Result<PacketView<'_>> {
+        let rest = match self.mmap.get(offset..) {
+            Some(rest) => rest,
+            None => bail!("Offset `{}` past the end of the dump", offset),
+        };
+        match packet_size(rest) {
+            Some(size) if size <= rest.len() => Ok(PacketView {
+                offset,
+                buf: &rest[..size],
+            }),
+            _ => bail!("Truncated packet at offset `{}`", offset),
+        }
+    }
+
+    /// Views of the packets at the given boundaries, to be processed in parallel. Same as
+    /// `view`, boundaries without a whole packet are errors.
+    pub fn par_views<'a>(
+        &'a self,
+        boundaries: &'a [usize],
+    ) -> impl IndexedParallelIterator<Item = Result<PacketView<'a>>> + 'a {
+        boundaries.par_iter().map(move |&offset| self.view(offset))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::io::Write;
+
+    use crate::test_utils::{TestResult, VALID_SOURCE};
+
+    #[test]
+    fn mapped_dump() -> TestResult {
+        let path = std::env::temp_dir().join(format!("dump-{}", std::process::id()));
+        let mut file = File::create(&path)?;
+        for _ in 0..1000 {
+            file.write_all(&VALID_SOURCE)?;
+        }
+        let mut corrupted = VALID_SOURCE;
+        corrupted[10] ^= 0xFF;
+        file.write_all(&corrupted)?;
+        file.write_all(&VALID_SOURCE[..12])?; // interrupted dump
+        drop(file);
+
+        let dump = MappedFile::open(&path)?;
+        assert_eq!(dump.views().count(), 1001);
+
+        let boundaries = dump.boundaries();
+        assert_eq!(boundaries.len(), 1001);
+        assert_eq!(boundaries[2], 2 * VALID_SOURCE.len());
+
+        let view = dump.view(boundaries[1])?;
+        assert_eq!(view.apid(), 0x73);
+        assert_eq!(view.sequence_counter(), 0x123);
+        assert_eq!(view.to_packet()?.into_buffer(), VALID_SOURCE);
+        assert!(dump.view(1001 * VALID_SOURCE.len()).is_err());
+
+        let valid = dump
+            .par_views(&boundaries)
+            .filter(|view| view.as_ref().is_ok_and(|view| view.is_valid()))
+            .count();
+        assert_eq!(valid, 1000);
+        let offsets = [0, 1001 * VALID_SOURCE.len()];
+        let views: Vec<_> = dump.par_views(&offsets).collect();
+        assert!(views[0].is_ok() && views[1].is_err());
+
+        drop(dump);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
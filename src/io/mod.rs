@@ -1,5 +1,28 @@
 // Reachable modules
+pub mod archive;
+pub mod dispatcher;
+pub mod framing;
+pub mod mixed_reader;
+pub mod mmap;
+#[cfg(feature = "serde")]
+pub mod ndjson;
+pub mod net;
+pub mod pcap;
+pub mod pipeline;
 pub mod reader;
+pub mod recording;
+pub mod writer;
 
 // Re-exporting
+pub use archive::{Archive, ArchiveWriter, Selection};
+pub use dispatcher::{Criterion, Dispatcher, Overflow, Subscription};
+pub use mixed_reader::{MixedPacket, MixedReader};
+pub use mmap::{MappedFile, PacketView};
+#[cfg(feature = "serde")]
+pub use ndjson::{NdjsonReader, NdjsonWriter};
+pub use net::{TcpServer, UdpReader, UdpSender};
+pub use pcap::{PcapReader, PcapngWriter};
+pub use pipeline::Pipeline;
 pub use reader::Reader;
+pub use recording::{RecordingReader, RecordingWriter, Replayer};
+pub use writer::Writer;
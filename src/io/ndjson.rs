@@ -0,0 +1,120 @@
+//! Newline-delimited JSON streams of packets (`serde` feature): one packet object per line,
+//! as described in [`encoding`](crate::protocol::encoding).
+
+use std::io::{BufRead, Write};
+
+use anyhow::{Context, Result};
+
+use crate::protocol::encoding::{Encoded, Encoding};
+use crate::protocol::Packet;
+
+/// Writes one JSON object per packet, each on its own line.
+pub struct NdjsonWriter<W> {
+    writer: W,
+    encoding: Encoding,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(dst: W, encoding: Encoding) -> NdjsonWriter<W> {
+        NdjsonWriter {
+            writer: dst,
+            encoding,
+        }
+    }
+
+    /// Writes the whole line at once, so that a reader never sees a partial object.
+    pub fn write(&mut self, pkt: &Packet) -> Result<()> {
+        let encoded = Encoded {
+            packet: pkt,
+            encoding: self.encoding,
+        };
+        let mut line = serde_json::to_vec(&encoded).context("Could not serialize the packet")?;
+        line.push(b'\n');
+        self.writer
+            .write_all(&line)
+            .context("Could not write the packet")
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("Could not flush the packets")
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the packets of a stream written by `NdjsonWriter` (with the hex encoding), skipping
+/// blank lines.
+pub struct NdjsonReader<R> {
+    reader: R,
+    line_number: usize,
+}
+
+impl<R: BufRead> NdjsonReader<R> {
+    pub fn new(src: R) -> NdjsonReader<R> {
+        NdjsonReader {
+            reader: src,
+            line_number: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonReader<R> {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Result<Packet>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            self.line_number += 1;
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => break,
+                Err(e) => return Some(Err(e).context("Could not read the packets")),
+            }
+        }
+
+        let line_number = self.line_number;
+        Some(
+            serde_json::from_str(&line)
+                .with_context(|| format!("Invalid packet at line `{}`", line_number)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::{valid_packet, TestResult, VALID_SOURCE};
+
+    #[test]
+    fn write_then_read() -> TestResult {
+        let pkt = valid_packet();
+        let mut writer = NdjsonWriter::new(Vec::new(), Encoding::Hex);
+        writer.write(&pkt)?;
+        writer.write(&pkt)?;
+        let stream = String::from_utf8(writer.into_inner())?;
+        assert_eq!(stream.lines().count(), 2);
+        assert!(stream
+            .lines()
+            .all(|line| line.contains(r#""user_data":"a5a55a5ac33c""#)));
+
+        let stream = stream.replace('\n', "\n\n");
+        let packets = NdjsonReader::new(stream.as_bytes()).collect::<Result<Vec<_>>>()?;
+        assert_eq!(packets.len(), 2);
+        for pkt in packets {
+            assert_eq!(pkt.into_buffer(), VALID_SOURCE);
+        }
+
+        let mut reader = NdjsonReader::new(&b"{\"type\": \"tm\", \"apid\": 1}\n{}\n"[..]);
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(format!("{:#}", err).contains("line `2`"));
+        assert!(reader.next().is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! Network transports: one packet per UDP datagram, or packet streams over TCP.
+
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+use super::reader::{Reader, BUFFER_SIZE, HEADER_SIZE};
+use super::writer::Writer;
+use crate::protocol::{Packet, PrimaryHeader};
+
+/// Size of the channel to communicate with the UDP reader
+const CHANNEL_SIZE: usize = 1024;
+
+/// Receives one packet per datagram.
+pub struct UdpReader {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+    channel: SyncSender<Packet>,
+}
+
+impl UdpReader {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<(UdpReader, Receiver<Packet>)> {
+        let socket = UdpSocket::bind(addr).context("Could not bind the UDP socket")?;
+        Ok(UdpReader::from_socket(socket))
+    }
+
+    pub fn from_socket(socket: UdpSocket) -> (UdpReader, Receiver<Packet>) {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_SIZE);
+
+        (
+            UdpReader {
+                socket,
+                // One more byte than the largest packet, to detect longer datagrams
+                buf: vec![0; BUFFER_SIZE + 1],
+                channel: sender,
+            },
+            receiver,
+        )
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Receives packets until the receiving end of the channel is dropped.
+    pub fn run(&mut self) -> Result<()> {
+        loop {
+            let pkt = match self.recv()? {
+                Some(pkt) => pkt,
+                None => continue,
+            };
+            if self.channel.send(pkt).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Receives the next datagram. Invalid datagrams are reported and dropped (`None`).
+    pub fn recv(&mut self) -> Result<Option<Packet>> {
+        let (len, src) = self
+            .socket
+            .recv_from(&mut self.buf)
+            .context("Could not receive a datagram")?;
+
+        match parse_datagram(&self.buf[..len]) {
+            Ok(pkt) => Ok(Some(pkt)),
+            Err(e) => {
+                warn!("Dropping datagram of size `{}` from {}: {:#}", len, src, e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Packet filling the whole datagram, as announced by its `data_length`.
+fn parse_datagram(buf: &[u8]) -> Result<Packet> {
+    if buf.len() < HEADER_SIZE {
+        bail!("Datagram shorter than a packet header");
+    }
+
+    let data_len = PrimaryHeader::from_buffer(buf).data_field_size();
+    if buf.len() != HEADER_SIZE + data_len {
+        bail!(
+            "Datagram length does not match the data field of size `{}`",
+            data_len
+        );
+    }
+
+    Packet::try_from_buffers(&buf[..HEADER_SIZE], &buf[HEADER_SIZE..])
+}
+
+/// Sends one packet per datagram.
+pub struct UdpSender {
+    socket: UdpSocket,
+}
+
+impl UdpSender {
+    /// Sends from an ephemeral port to the given address.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<UdpSender> {
+        let addr = resolve(addr)?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+
+        let socket = UdpSocket::bind(local).context("Could not bind the UDP socket")?;
+        socket
+            .connect(addr)
+            .with_context(|| format!("Could not connect to {}", addr))?;
+        Ok(UdpSender { socket })
+    }
+
+    pub fn send(&self, pkt: Packet) -> Result<()> {
+        let buf = pkt.into_buffer();
+        self.socket
+            .send(&buf)
+            .with_context(|| format!("Could not send the packet of size `{}`", buf.len()))?;
+        Ok(())
+    }
+}
+
+/// Listens for front-ends pushing packet streams.
+pub struct TcpServer {
+    listener: TcpListener,
+}
+
+impl TcpServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<TcpServer> {
+        let listener = TcpListener::bind(addr).context("Could not bind the TCP listener")?;
+        Ok(TcpServer { listener })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Waits for the next connection, and reads its packet stream.
+    pub fn accept(&self) -> Result<(Reader<TcpStream>, Receiver<Packet>)> {
+        let (stream, peer) = self
+            .listener
+            .accept()
+            .context("Could not accept a connection")?;
+        info!("Connection from {}", peer);
+        Ok(Reader::new(stream))
+    }
+}
+
+/// Reads the packet stream served at the given address.
+pub fn tcp_client<A: ToSocketAddrs>(addr: A) -> Result<(Reader<TcpStream>, Receiver<Packet>)> {
+    Ok(Reader::new(tcp_connect(addr)?))
+}
+
+/// Pushes a packet stream to the given address.
+pub fn tcp_sender<A: ToSocketAddrs>(addr: A) -> Result<Writer<TcpStream>> {
+    Ok(Writer::new(tcp_connect(addr)?))
+}
+
+fn tcp_connect<A: ToSocketAddrs>(addr: A) -> Result<TcpStream> {
+    let addr = resolve(addr)?;
+    let stream =
+        TcpStream::connect(addr).with_context(|| format!("Could not connect to {}", addr))?;
+    // Packets are written whole: no need to wait for more data
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+fn resolve<A: ToSocketAddrs>(addr: A) -> Result<SocketAddr> {
+    match addr.to_socket_addrs()?.next() {
+        Some(addr) => Ok(addr),
+        None => bail!("Address resolved to nothing"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+
+    use crate::test_utils::{valid_packet, TestResult, VALID_SOURCE};
+
+    #[test]
+    fn udp_datagrams() -> TestResult {
+        let (mut reader, _receiver) = UdpReader::bind("127.0.0.1:0")?;
+        let addr = reader.local_addr()?;
+
+        let sender = UdpSender::connect(addr)?;
+        sender.send(valid_packet())?;
+        assert_eq!(
+            reader.recv()?.map(Packet::into_buffer),
+            Some(VALID_SOURCE.to_vec())
+        );
+
+        // Truncated and padded datagrams
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        socket.send_to(&VALID_SOURCE[..20], addr)?;
+        assert!(reader.recv()?.is_none());
+        socket.send_to(&[&VALID_SOURCE[..], &[0, 0]].concat(), addr)?;
+        assert!(reader.recv()?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tcp_stream() -> TestResult {
+        let server = TcpServer::bind("127.0.0.1:0")?;
+        let addr = server.local_addr()?;
+
+        let client = thread::spawn(move || -> Result<()> {
+            let mut sender = tcp_sender(addr)?;
+            for _ in 0..3 {
+                sender.write(valid_packet())?;
+            }
+            Ok(())
+        });
+
+        let (mut reader, receiver) = server.accept()?;
+        client.join().unwrap()?;
+        reader.run()?;
+        drop(reader);
+
+        let packets: Vec<Packet> = receiver.iter().collect();
+        assert_eq!(packets.len(), 3);
+
+        Ok(())
+    }
+}
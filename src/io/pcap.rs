@@ -0,0 +1,668 @@
+//! Packet captures: legacy pcap and pcapng files, as recorded by Wireshark.
+//!
+//! Space packets are read out of UDP datagrams (over Ethernet, Linux cooked captures or
+//! raw IP), or out of the whole captured data for the link types configured as raw.
+
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+
+use crate::protocol::Packet;
+
+/// Null/loopback encapsulation of BSD.
+pub const LINKTYPE_NULL: u16 = 0;
+pub const LINKTYPE_ETHERNET: u16 = 1;
+/// Raw IPv4 or IPv6 datagrams.
+pub const LINKTYPE_RAW: u16 = 101;
+pub const LINKTYPE_LINUX_SLL: u16 = 113;
+/// First of the link types reserved for private use.
+pub const LINKTYPE_USER0: u16 = 147;
+pub const LINKTYPE_IPV4: u16 = 228;
+pub const LINKTYPE_IPV6: u16 = 229;
+pub const LINKTYPE_LINUX_SLL2: u16 = 276;
+
+const PCAP_MAGIC_US: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NS: u32 = 0xA1B2_3C4D;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const SIMPLE_PACKET_BLOCK: u32 = 3;
+const ENHANCED_PACKET_BLOCK: u32 = 6;
+
+/// Option of the interfaces giving the resolution of their timestamps.
+const IF_TSRESOL: u16 = 9;
+
+/// Max size of a block (or record): larger ones are taken for a corrupted file.
+const MAX_BLOCK_SIZE: usize = 1 << 24;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+#[derive(Clone, Debug, Default)]
+pub struct PcapConfig {
+    /// UDP ports (source or destination) of the datagrams read: any when empty.
+    pub ports: Vec<u16>,
+    /// Link types whose captured data are raw space packets.
+    pub raw_link_types: Vec<u16>,
+}
+
+/// Payload captured, with its timestamp (since the Unix epoch).
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureRecord {
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+struct Interface {
+    link_type: u16,
+    // Timestamp units: 10^-exponent or 2^-exponent seconds
+    base_two: bool,
+    exponent: u8,
+}
+
+enum Format {
+    Pcap {
+        little_endian: bool,
+        interface: Interface,
+    },
+    Pcapng {
+        little_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+/// Reads the payloads of a capture, which can be replayed through `Reader` as a stream.
+pub struct PcapReader<R> {
+    reader: BufReader<R>,
+    config: PcapConfig,
+    format: Format,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Reads the header of the capture, whose format is detected.
+    pub fn new(src: R, config: PcapConfig) -> Result<PcapReader<R>> {
+        let mut reader = BufReader::new(src);
+        let mut magic = [0; 4];
+        reader
+            .read_exact(&mut magic)
+            .context("Could not read the capture header")?;
+
+        let format = match u32::from_be_bytes(magic) {
+            SECTION_HEADER_BLOCK => {
+                let mut len = [0; 4];
+                reader.read_exact(&mut len)?;
+                Format::Pcapng {
+                    little_endian: read_section_header(&mut reader, len)?,
+                    interfaces: Vec::new(),
+                }
+            }
+            _ => {
+                let (little_endian, nanos) =
+                    match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+                        (PCAP_MAGIC_US, _) => (true, false),
+                        (PCAP_MAGIC_NS, _) => (true, true),
+                        (_, PCAP_MAGIC_US) => (false, false),
+                        (_, PCAP_MAGIC_NS) => (false, true),
+                        _ => bail!("Not a pcap or pcapng capture"),
+                    };
+
+                // Versions, time zone, accuracy, snapshot length and link type
+                let mut header = [0; 20];
+                reader.read_exact(&mut header)?;
+                let link_type = read_u32(&header[16..], little_endian) as u16;
+
+                Format::Pcap {
+                    little_endian,
+                    interface: Interface {
+                        link_type,
+                        base_two: false,
+                        exponent: if nanos { 9 } else { 6 },
+                    },
+                }
+            }
+        };
+
+        Ok(PcapReader {
+            reader,
+            config,
+            format,
+            pending: Vec::new(),
+            position: 0,
+        })
+    }
+
+    /// Next payload selected by the configuration, or `None` at the end of the capture.
+    pub fn next_record(&mut self) -> Result<Option<CaptureRecord>> {
+        loop {
+            let (interface, timestamp, data) = match self.next_frame()? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+
+            let payload = if self.config.raw_link_types.contains(&interface.link_type) {
+                Some(&data[..])
+            } else {
+                udp_payload(interface.link_type, &data, &self.config.ports)
+            };
+            if let Some(payload) = payload {
+                return Ok(Some(CaptureRecord {
+                    timestamp: to_duration(timestamp, interface)?,
+                    data: payload.to_vec(),
+                }));
+            }
+        }
+    }
+
+    /// Next captured frame, with its interface and raw timestamp.
+    fn next_frame(&mut self) -> Result<Option<(Interface, u64, Vec<u8>)>> {
+        match &mut self.format {
+            Format::Pcap {
+                little_endian,
+                interface,
+            } => {
+                let mut header = [0; 16];
+                if !read_or_eof(&mut self.reader, &mut header)? {
+                    return Ok(None);
+                }
+                let le = *little_endian;
+                let seconds = read_u32(&header, le) as u64;
+                let fraction = read_u32(&header[4..], le) as u64;
+                let len = read_u32(&header[8..], le) as usize;
+
+                let data = read_vec(&mut self.reader, len)?;
+                let units = 10u64.pow(interface.exponent as u32);
+                Ok(Some((*interface, seconds * units + fraction, data)))
+            }
+            Format::Pcapng {
+                little_endian,
+                interfaces,
+            } => loop {
+                let mut header = [0; 8];
+                if !read_or_eof(&mut self.reader, &mut header)? {
+                    return Ok(None);
+                }
+
+                let block_type = read_u32(&header, *little_endian);
+                if block_type == SECTION_HEADER_BLOCK {
+                    let len = [header[4], header[5], header[6], header[7]];
+                    *little_endian = read_section_header(&mut self.reader, len)?;
+                    interfaces.clear();
+                    continue;
+                }
+
+                let len = read_u32(&header[4..], *little_endian) as usize;
+                if !(12..=MAX_BLOCK_SIZE).contains(&len) || !len.is_multiple_of(4) {
+                    bail!("Block of invalid length `{}`", len);
+                }
+                let body = read_vec(&mut self.reader, len - 8)?;
+                let body = &body[..body.len() - 4]; // trailing block length
+                let le = *little_endian;
+
+                match block_type {
+                    INTERFACE_DESCRIPTION_BLOCK => interfaces.push(read_interface(body, le)?),
+                    ENHANCED_PACKET_BLOCK => {
+                        if body.len() < 20 {
+                            bail!("Enhanced packet block too short");
+                        }
+                        let interface = match interfaces.get(read_u32(body, le) as usize) {
+                            Some(interface) => *interface,
+                            None => bail!("Packet of an undescribed interface"),
+                        };
+                        let timestamp = (read_u32(&body[4..], le) as u64) << 32
+                            | read_u32(&body[8..], le) as u64;
+                        let captured = read_u32(&body[12..], le) as usize;
+                        match body.get(20..20 + captured) {
+                            Some(data) => return Ok(Some((interface, timestamp, data.to_vec()))),
+                            None => bail!("Enhanced packet block truncated"),
+                        }
+                    }
+                    SIMPLE_PACKET_BLOCK => {
+                        // No timestamp, and the data are padded up to the block length
+                        let interface = match interfaces.first() {
+                            Some(interface) => *interface,
+                            None => bail!("Packet of an undescribed interface"),
+                        };
+                        if body.len() < 4 {
+                            bail!("Simple packet block too short");
+                        }
+                        let len = (read_u32(body, le) as usize).min(body.len() - 4);
+                        return Ok(Some((interface, 0, body[4..4 + len].to_vec())));
+                    }
+                    _ => debug!("Skipping block of type `{:#010X}`", block_type),
+                }
+            },
+        }
+    }
+}
+
+impl<R: Read> Read for PcapReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.pending.len() {
+            match self.next_record() {
+                Ok(Some(record)) => {
+                    self.pending = record.data;
+                    self.position = 0;
+                }
+                Ok(None) => return Ok(0),
+                Err(e) => return Err(io::Error::new(ErrorKind::InvalidData, format!("{:#}", e))),
+            }
+        }
+
+        let len = buf.len().min(self.pending.len() - self.position);
+        buf[..len].copy_from_slice(&self.pending[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Rest of a section header block, after its length: returns whether it is little-endian.
+fn read_section_header<R: Read>(reader: &mut R, len: [u8; 4]) -> Result<bool> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    let little_endian = if u32::from_le_bytes(magic) == BYTE_ORDER_MAGIC {
+        true
+    } else if u32::from_be_bytes(magic) == BYTE_ORDER_MAGIC {
+        false
+    } else {
+        bail!("Section header block without byte-order magic");
+    };
+
+    let len = read_u32(&len, little_endian) as usize;
+    if !(28..=MAX_BLOCK_SIZE).contains(&len) {
+        bail!("Section header block of invalid length `{}`", len);
+    }
+    // Versions, section length, options and trailing block length
+    read_vec(reader, len - 12)?;
+
+    Ok(little_endian)
+}
+
+fn read_interface(body: &[u8], le: bool) -> Result<Interface> {
+    if body.len() < 8 {
+        bail!("Interface description block too short");
+    }
+    let mut interface = Interface {
+        link_type: read_u16(body, le),
+        base_two: false,
+        exponent: 6,
+    };
+
+    let mut options = &body[8..];
+    while options.len() >= 4 {
+        let code = read_u16(options, le);
+        let len = read_u16(&options[2..], le) as usize;
+        let value = match options.get(4..4 + len) {
+            Some(value) => value,
+            None => break,
+        };
+        if code == IF_TSRESOL && len == 1 {
+            interface.base_two = value[0] & 0x80 != 0;
+            interface.exponent = value[0] & 0x7F;
+        }
+        options = &options[(4 + len).div_ceil(4) * 4..];
+    }
+    if !interface.base_two && decimal_units(interface.exponent).is_none() {
+        bail!(
+            "Interface of unsupported timestamp resolution `10^-{}`",
+            interface.exponent
+        );
+    }
+
+    Ok(interface)
+}
+
+/// Timestamp units per second of decimal resolutions, if they fit.
+fn decimal_units(exponent: u8) -> Option<u128> {
+    10u128.checked_pow(exponent as u32)
+}
+
+fn to_duration(timestamp: u64, interface: Interface) -> Result<Duration> {
+    let nanos = if interface.base_two {
+        ((timestamp as u128) * 1_000_000_000) >> interface.exponent
+    } else {
+        let units = decimal_units(interface.exponent).with_context(|| {
+            format!(
+                "Unsupported timestamp resolution `10^-{}`",
+                interface.exponent
+            )
+        })?;
+        (timestamp as u128) * 1_000_000_000 / units
+    };
+    Ok(Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    ))
+}
+
+/// Payload of the UDP datagram captured, if any (and if sent from or to the ports).
+fn udp_payload<'a>(link_type: u16, data: &'a [u8], ports: &[u16]) -> Option<&'a [u8]> {
+    let ip = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = u16::from_be_bytes([*data.get(12)?, *data.get(13)?]);
+            let mut start = 14;
+            if ethertype == ETHERTYPE_VLAN {
+                ethertype = u16::from_be_bytes([*data.get(16)?, *data.get(17)?]);
+                start += 4;
+            }
+            match ethertype {
+                ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => data.get(start..)?,
+                _ => return None,
+            }
+        }
+        LINKTYPE_LINUX_SLL => data.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => data.get(20..)?,
+        // Address family in host byte order
+        LINKTYPE_NULL => data.get(4..)?,
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => data,
+        _ => return None,
+    };
+
+    let udp = match ip.first()? >> 4 {
+        4 => {
+            let header_len = ((ip[0] & 0x0F) as usize) * 4;
+            let total_len = u16::from_be_bytes([*ip.get(2)?, *ip.get(3)?]) as usize;
+            let fragment = u16::from_be_bytes([*ip.get(6)?, *ip.get(7)?]) & 0x3FFF;
+            if *ip.get(9)? != IP_PROTOCOL_UDP || fragment != 0 {
+                return None;
+            }
+            ip.get(header_len..total_len)?
+        }
+        6 => {
+            // Extension headers are not supported
+            let payload_len = u16::from_be_bytes([*ip.get(4)?, *ip.get(5)?]) as usize;
+            if *ip.get(6)? != IP_PROTOCOL_UDP {
+                return None;
+            }
+            ip.get(40..40 + payload_len)?
+        }
+        _ => return None,
+    };
+
+    let src_port = u16::from_be_bytes([*udp.first()?, *udp.get(1)?]);
+    let dst_port = u16::from_be_bytes([*udp.get(2)?, *udp.get(3)?]);
+    let len = u16::from_be_bytes([*udp.get(4)?, *udp.get(5)?]) as usize;
+    if !ports.is_empty() && !ports.contains(&src_port) && !ports.contains(&dst_port) {
+        return None;
+    }
+    udp.get(8..len)
+}
+
+fn read_u16(buf: &[u8], little_endian: bool) -> u16 {
+    let bytes = [buf[0], buf[1]];
+    match little_endian {
+        true => u16::from_le_bytes(bytes),
+        false => u16::from_be_bytes(bytes),
+    }
+}
+
+fn read_u32(buf: &[u8], little_endian: bool) -> u32 {
+    let bytes = [buf[0], buf[1], buf[2], buf[3]];
+    match little_endian {
+        true => u32::from_le_bytes(bytes),
+        false => u32::from_be_bytes(bytes),
+    }
+}
+
+fn read_vec<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    if len > MAX_BLOCK_SIZE {
+        bail!("Record of size `{}` is too large", len);
+    }
+    let mut buf = vec![0; len];
+    reader
+        .read_exact(&mut buf)
+        .with_context(|| format!("Could not read the record of size `{}`", len))?;
+    Ok(buf)
+}
+
+/// Fills the buffer, or returns `false` at the end of the stream (before any byte).
+fn read_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) if len == 0 => return Ok(false),
+            Ok(0) => bail!("Capture truncated"),
+            Ok(n) => len += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+/// Link layer of the packets written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkLayer {
+    /// Raw space packets, with the given link type (e.g. `LINKTYPE_USER0`).
+    Raw(u16),
+    /// IPv4/UDP datagrams from and to localhost, on the given port.
+    Udp(u16),
+}
+
+/// Writes packets into a pcapng capture, with a single interface (microsecond timestamps).
+pub struct PcapngWriter<W> {
+    writer: W,
+    link_layer: LinkLayer,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Writes the section header and interface description blocks.
+    pub fn new(dst: W, link_layer: LinkLayer) -> Result<PcapngWriter<W>> {
+        let mut writer = PcapngWriter {
+            writer: dst,
+            link_layer,
+        };
+
+        // Version 1.0, unspecified section length
+        let mut body = vec![];
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        writer.write_block(SECTION_HEADER_BLOCK, &body)?;
+
+        let link_type = match link_layer {
+            LinkLayer::Raw(link_type) => link_type,
+            LinkLayer::Udp(_) => LINKTYPE_IPV4,
+        };
+        let mut body = vec![];
+        body.extend_from_slice(&link_type.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes()); // no snapshot length
+        writer.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, timestamp: Duration, pkt: Packet) -> Result<()> {
+        self.write_payload(timestamp, &pkt.into_buffer())
+    }
+
+    /// Writes any payload (e.g. a frame) in its own enhanced packet block.
+    pub fn write_payload(&mut self, timestamp: Duration, payload: &[u8]) -> Result<()> {
+        let data = match self.link_layer {
+            LinkLayer::Raw(_) => payload.to_vec(),
+            LinkLayer::Udp(port) => udp_datagram(port, payload)?,
+        };
+        let timestamp = timestamp.as_micros() as u64;
+
+        let mut body = Vec::with_capacity(20 + data.len() + 3);
+        body.extend_from_slice(&0u32.to_le_bytes()); // interface
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&data);
+        body.resize(body.len().div_ceil(4) * 4, 0);
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<()> {
+        let len = (body.len() + 12) as u32;
+        let mut buf = Vec::with_capacity(len as usize);
+        buf.extend_from_slice(&block_type.to_le_bytes());
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(body);
+        buf.extend_from_slice(&len.to_le_bytes());
+        self.writer
+            .write_all(&buf)
+            .context("Could not write the capture block")
+    }
+}
+
+/// IPv4/UDP datagram from and to localhost (the UDP checksum is optional over IPv4).
+fn udp_datagram(port: u16, payload: &[u8]) -> Result<Vec<u8>> {
+    let len = 20 + 8 + payload.len();
+    if len > u16::MAX as usize {
+        bail!(
+            "Payload of size `{}` too large for a datagram",
+            payload.len()
+        );
+    }
+
+    let mut buf = Vec::with_capacity(len);
+    buf.extend_from_slice(&[0x45, 0x00]);
+    buf.extend_from_slice(&(len as u16).to_be_bytes());
+    buf.extend_from_slice(&[0x00, 0x00, 0x40, 0x00]); // don't fragment
+    buf.extend_from_slice(&[64, IP_PROTOCOL_UDP, 0x00, 0x00]);
+    buf.extend_from_slice(&[127, 0, 0, 1, 127, 0, 0, 1]);
+    let checksum = !buf
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .fold(0u32, |acc, word| {
+            let sum = acc + word;
+            (sum & 0xFFFF) + (sum >> 16)
+        }) as u16;
+    buf[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    buf.extend_from_slice(&port.to_be_bytes());
+    buf.extend_from_slice(&port.to_be_bytes());
+    buf.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    buf.extend_from_slice(&[0x00, 0x00]);
+    buf.extend_from_slice(payload);
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::io::Reader;
+    use crate::test_utils::{valid_packet, TestResult, VALID_SOURCE};
+
+    #[test]
+    fn pcapng_round_trip() -> TestResult {
+        let mut writer = PcapngWriter::new(Vec::new(), LinkLayer::Udp(5000))?;
+        writer.write(Duration::new(1_600_000_000, 123_456_000), valid_packet())?;
+        writer.write(Duration::new(1_600_000_001, 0), valid_packet())?;
+        let capture = writer.into_inner();
+
+        let config = PcapConfig {
+            ports: vec![5000],
+            ..PcapConfig::default()
+        };
+        let mut reader = PcapReader::new(&capture[..], config)?;
+        let record = reader.next_record()?.unwrap();
+        assert_eq!(record.timestamp, Duration::new(1_600_000_000, 123_456_000));
+        assert_eq!(record.data, VALID_SOURCE);
+
+        // Replaying the whole capture
+        let (mut reader, receiver) =
+            Reader::new(PcapReader::new(&capture[..], PcapConfig::default())?);
+        reader.run()?;
+        drop(reader);
+        assert_eq!(receiver.iter().count(), 2);
+
+        // Other port
+        let config = PcapConfig {
+            ports: vec![5001],
+            ..PcapConfig::default()
+        };
+        assert!(PcapReader::new(&capture[..], config)?
+            .next_record()?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_link_type() -> TestResult {
+        let mut writer = PcapngWriter::new(Vec::new(), LinkLayer::Raw(LINKTYPE_USER0))?;
+        writer.write(Duration::from_secs(1), valid_packet())?;
+        let capture = writer.into_inner();
+
+        assert!(PcapReader::new(&capture[..], PcapConfig::default())?
+            .next_record()?
+            .is_none());
+
+        let config = PcapConfig {
+            raw_link_types: vec![LINKTYPE_USER0],
+            ..PcapConfig::default()
+        };
+        let record = PcapReader::new(&capture[..], config)?
+            .next_record()?
+            .unwrap();
+        assert_eq!(record.data, VALID_SOURCE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn timestamp_resolutions() -> TestResult {
+        let body = |tsresol: u8| {
+            let mut body = vec![1, 0, 0, 0, 0, 0, 0, 0];
+            body.extend_from_slice(&[IF_TSRESOL as u8, 0, 1, 0, tsresol, 0, 0, 0]);
+            body
+        };
+
+        let interface = read_interface(&body(38), true)?;
+        assert_eq!(
+            to_duration(10u64.pow(19), interface)?,
+            Duration::from_nanos(0)
+        );
+        let interface = read_interface(&body(0x80 | 40), true)?;
+        assert_eq!(to_duration(1 << 40, interface)?, Duration::from_secs(1));
+        assert!(read_interface(&body(39), true).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_pcap_over_ethernet() -> TestResult {
+        let datagram = udp_datagram(6000, &VALID_SOURCE)?;
+        let mut frame = vec![0xFF; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend_from_slice(&datagram);
+        frame.extend_from_slice(&[0; 4]); // Ethernet padding
+
+        // Big-endian, nanosecond timestamps
+        let mut capture = PCAP_MAGIC_NS.to_be_bytes().to_vec();
+        capture.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF]);
+        capture.extend_from_slice(&(LINKTYPE_ETHERNET as u32).to_be_bytes());
+        capture.extend_from_slice(&10u32.to_be_bytes());
+        capture.extend_from_slice(&500u32.to_be_bytes());
+        capture.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        capture.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        capture.extend_from_slice(&frame);
+
+        let mut reader = PcapReader::new(&capture[..], PcapConfig::default())?;
+        let record = reader.next_record()?.unwrap();
+        assert_eq!(record.timestamp, Duration::new(10, 500));
+        assert_eq!(record.data, VALID_SOURCE);
+        assert!(reader.next_record()?.is_none());
+
+        Ok(())
+    }
+}
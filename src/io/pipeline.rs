@@ -0,0 +1,239 @@
+//! Multi-threaded decoding: packets are framed in order, decoded by a pool of workers,
+//! then put back in their original order.
+//!
+//! At most `capacity` packets are in flight between the framing and the consumer: the
+//! framing waits for the consumer once they are all taken. A panicking job aborts the
+//! whole pipeline.
+
+use std::collections::BTreeMap;
+use std::io::{BufReader, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{anyhow, bail, Result};
+
+use super::reader::{read_raw, BUFFER_SIZE, HEADER_SIZE};
+use crate::protocol::Packet;
+use crate::pus::ServiceHeader;
+
+/// Packet decoded by the default job of the pipeline.
+#[derive(Debug)]
+pub struct Decoded {
+    pub packet: Packet,
+    /// PUS service header, if the user data field is large enough to carry one.
+    pub service: Option<ServiceHeader>,
+}
+
+/// Verifies the checksum of a raw packet, and decodes its headers.
+pub fn decode(buf: Vec<u8>) -> Result<Decoded> {
+    let packet = Packet::try_from_buffers(&buf[..HEADER_SIZE], &buf[HEADER_SIZE..])?;
+    let service = ServiceHeader::from_packet(&packet).map(|(header, _)| header);
+    Ok(Decoded { packet, service })
+}
+
+type Job<T> = Arc<dyn Fn(Vec<u8>) -> T + Send + Sync>;
+
+pub struct Pipeline<R, T> {
+    reader: BufReader<R>,
+    workers: usize,
+    capacity: usize,
+    job: Job<T>,
+    channel: SyncSender<T>,
+}
+
+impl<R: Read, T: Send + 'static> Pipeline<R, T> {
+    /// Pipeline applying the job to every raw packet (header and data field) of the
+    /// stream, with the given number of workers and packets in flight.
+    pub fn new<F>(src: R, workers: usize, capacity: usize, job: F) -> (Pipeline<R, T>, Receiver<T>)
+    where
+        F: Fn(Vec<u8>) -> T + Send + Sync + 'static,
+    {
+        let capacity = capacity.max(1);
+        // Handing over each packet, so that it is in flight until consumed
+        let (sender, receiver) = mpsc::sync_channel(0);
+
+        (
+            Pipeline {
+                reader: BufReader::with_capacity(BUFFER_SIZE, src),
+                workers: workers.max(1),
+                capacity,
+                job: Arc::new(job),
+                channel: sender,
+            },
+            receiver,
+        )
+    }
+
+    /// Frames the stream on the calling thread until its end, or until the receiving
+    /// end of the channel is dropped.
+    pub fn run(self) -> Result<()> {
+        let Pipeline {
+            mut reader,
+            workers,
+            capacity,
+            job,
+            channel,
+        } = self;
+
+        // One token per packet in flight, given back once the packet is consumed
+        let (token_sender, tokens) = mpsc::sync_channel(capacity);
+        for _ in 0..capacity {
+            token_sender.send(())?;
+        }
+
+        let (job_sender, jobs) = mpsc::sync_channel::<(u64, Vec<u8>)>(capacity);
+        let jobs = Arc::new(Mutex::new(jobs));
+        let (result_sender, results) = mpsc::channel();
+
+        let workers = (0..workers)
+            .map(|_| {
+                let jobs = Arc::clone(&jobs);
+                let job = Arc::clone(&job);
+                let results = result_sender.clone();
+                thread::spawn(move || loop {
+                    let next = jobs.lock().unwrap().recv();
+                    let (seq, buf) = match next {
+                        Ok(next) => next,
+                        Err(_) => return,
+                    };
+                    let res = panic::catch_unwind(AssertUnwindSafe(|| job(buf)));
+                    if results.send((seq, res)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(result_sender);
+
+        let sequencer = thread::spawn(move || -> Result<()> {
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            for (seq, res) in results {
+                // Stopping everything: the framing and the workers once their channels close
+                let res = match res {
+                    Ok(res) => res,
+                    Err(_) => bail!("Job panicked on packet `{}`", seq),
+                };
+                pending.insert(seq, res);
+                while let Some(res) = pending.remove(&next) {
+                    if channel.send(res).is_err() {
+                        return Ok(());
+                    }
+                    // The framing may have stopped already
+                    let _ = token_sender.send(());
+                    next += 1;
+                }
+            }
+            Ok(())
+        });
+
+        let mut seq = 0;
+        let res = loop {
+            // Waiting for a packet to be consumed, unless the consumer is gone
+            if tokens.recv().is_err() {
+                break Ok(());
+            }
+            let buf = match read_raw(&mut reader) {
+                Ok(Some(buf)) => buf,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            };
+            if job_sender.send((seq, buf)).is_err() {
+                break Ok(());
+            }
+            seq += 1;
+        };
+
+        drop(job_sender);
+        drop(tokens);
+        for worker in workers {
+            worker.join().map_err(|_| anyhow!("Worker panicked"))?;
+        }
+        sequencer
+            .join()
+            .map_err(|_| anyhow!("Sequencer panicked"))??;
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use crate::protocol::PrimaryHeader;
+    use crate::test_utils::{TestResult, VALID_SOURCE};
+
+    /// Stream of packets whose sequence counters count from 0.
+    fn stream(count: u16) -> Vec<u8> {
+        (0..count)
+            .flat_map(|seq| {
+                let mut buf = VALID_SOURCE;
+                buf[2] = 0xC0 | (seq >> 8) as u8;
+                buf[3] = seq as u8;
+                let checksum = crate::protocol::hasher::compute(&buf[..20]);
+                buf[20..].copy_from_slice(&checksum.to_be_bytes());
+                buf
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ordered_output() -> TestResult {
+        let (pipeline, receiver) = Pipeline::new(Cursor::new(stream(200)), 4, 16, |buf| {
+            // Later packets are decoded faster
+            let seq = PrimaryHeader::from_buffer(&buf[..HEADER_SIZE]).sequence_counter;
+            thread::sleep(Duration::from_micros(((200 - seq) % 7) as u64 * 100));
+            seq
+        });
+        let framing = thread::spawn(move || pipeline.run());
+
+        let seqs = receiver.iter().collect::<Vec<_>>();
+        framing.join().unwrap()?;
+        assert_eq!(seqs, (0..200).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn decoding_and_backpressure() -> TestResult {
+        let mut src = stream(50);
+        src[10] ^= 0xFF; // wrong checksum of the first packet
+
+        let (pipeline, receiver) = Pipeline::new(Cursor::new(src), 2, 4, decode);
+        let framing = thread::spawn(move || pipeline.run());
+
+        // Nothing consumed yet: the framing waits
+        thread::sleep(Duration::from_millis(20));
+        assert!(!framing.is_finished());
+
+        let results = receiver.iter().collect::<Vec<_>>();
+        framing.join().unwrap()?;
+        assert_eq!(results.len(), 50);
+        assert!(results[0].is_err());
+        let decoded = results[1].as_ref().unwrap();
+        assert_eq!(decoded.packet.pri_header.sequence_counter, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn panicking_job() {
+        let (pipeline, receiver) = Pipeline::new(Cursor::new(stream(50)), 2, 4, |buf| {
+            let seq = PrimaryHeader::from_buffer(&buf[..HEADER_SIZE]).sequence_counter;
+            assert_ne!(seq, 5, "Undecodable packet");
+            seq
+        });
+        let framing = thread::spawn(move || pipeline.run());
+
+        let seqs = receiver.iter().collect::<Vec<_>>();
+        assert!(framing.join().unwrap().is_err());
+        assert_eq!(seqs, (0..seqs.len() as u16).collect::<Vec<_>>());
+        assert!(seqs.len() <= 5);
+    }
+}
@@ -1,13 +1,13 @@
-use std::io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::io::{BufReader, ErrorKind, Read};
 use std::sync::mpsc::{self, Receiver, SyncSender};
 
-use anyhow::{Context, Error, Result};
-use byteorder::{BigEndian, ReadBytesExt};
+use anyhow::{bail, Context, Result};
+use log::warn;
 
-use crate::protocol::Packet;
+use crate::protocol::{Packet, PrimaryHeader};
 
-/// Size of the packet header. Fixed size: 6 bytes.
-pub const HEADER_SIZE: usize = 6;
+/// Size of the packet header, kept here for the users of `io::reader`.
+pub use crate::protocol::HEADER_SIZE;
 
 /// Max size of the data field => variable (depends on data_length field).
 pub const DATA_MAX_SIZE: usize = 65536;
@@ -22,8 +22,6 @@ const CHANNEL_SIZE: usize = 1024;
 /// Custom abstraction of standard `BufReader`
 pub struct Reader<R> {
     reader: BufReader<R>,
-    header_buf: Vec<u8>,
-    data_buf: Vec<u8>,
     channel: SyncSender<Packet>,
 }
 
@@ -35,8 +33,6 @@ impl<R: Read + Unpin> Reader<R> {
         (
             Reader {
                 reader,
-                header_buf: Vec::with_capacity(HEADER_SIZE), // known size
-                data_buf: Vec::new(),                        // variable size
                 channel: sender,
             },
             receiver,
@@ -44,70 +40,55 @@ impl<R: Read + Unpin> Reader<R> {
     }
 
     pub fn run(&mut self) -> Result<()> {
-        loop {
-            let should_stop = self.read()?;
-
-            let pkt = self.parse()?;
-            self.channel.send(pkt)?;
-
-            if should_stop {
-                break;
+        // End of the stream: nothing left to parse
+        while let Some(buf) = read_raw(&mut self.reader)? {
+            // Invalid packets (e.g. wrong checksum) are reported and dropped
+            match Packet::try_from_buffers(&buf[..HEADER_SIZE], &buf[HEADER_SIZE..]) {
+                Ok(pkt) => self.channel.send(pkt)?,
+                Err(e) => warn!("Dropping invalid packet: {:#}", e),
             }
         }
         Ok(())
     }
+}
 
-    fn read(&mut self) -> Result<bool, Error> {
-        // Reading he primary header of the packet (fixed size: 48bits = 6 u8)
-        self.header_buf.resize(HEADER_SIZE, 0); // still needs to be populated
-        let res = self.reader.read_exact(&mut self.header_buf);
-        match res {
-            Ok(_) => Ok(false),
-            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(true),
-            Err(e) => Err(e),
+/// Next raw packet of the stream (header and data field, unchecked), or `None` at its end.
+pub fn read_raw<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut buf = vec![0; HEADER_SIZE];
+    let mut len = 0;
+    while len < HEADER_SIZE {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Could not read the header of size `{}`", HEADER_SIZE)
+                })
+            }
         }
-        .with_context(|| format!("Could not read the header of size `{}`", HEADER_SIZE))?;
-
-        // Parsing the header to get the Packet Data Length
-        // As specified by the protocol: #octets = PKT_DATA_LENGTH + 1
-        let data_len = self.parse_pkt_length() + 1;
-
-        // Reading the data field, which includes the secondary header
-        self.data_buf.resize(data_len, 0);
-        self.reader
-            .read_exact(&mut self.data_buf)
-            .with_context(|| format!("Could not read the body of size `{}`", data_len))?;
-
-        Ok(false)
     }
-
-    fn parse(&self) -> Result<Packet, Error> {
-        let pkt = Packet::from_buffers(&self.header_buf, &self.data_buf);
-        Ok(pkt)
+    match len {
+        0 => return Ok(None),
+        HEADER_SIZE => {}
+        _ => bail!("Truncated header of size `{}`", len),
     }
 
-    /// Since the reading was successfull: this method is not expected to panick!
-    fn parse_pkt_length(&self) -> usize {
-        let mut cursor = Cursor::new(&self.header_buf);
-        cursor
-            .seek(SeekFrom::Start(4))
-            .expect("Fixed size vector: should reach this position");
+    let data_len = PrimaryHeader::from_buffer(&buf).data_field_size();
+    buf.resize(HEADER_SIZE + data_len, 0);
+    reader
+        .read_exact(&mut buf[HEADER_SIZE..])
+        .with_context(|| format!("Could not read the body of size `{}`", data_len))?;
 
-        cursor
-            .read_u16::<BigEndian>()
-            .expect("Reading exactly 16 bits: should parse u16") as usize
-    }
+    Ok(Some(buf))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    type TestResult = Result<(), Box<dyn std::error::Error>>;
+    use crate::test_utils::{TestResult, VALID_SOURCE};
 
-    const VALID_SOURCE: [u8; 22] = [
-        8, 115, 193, 35, 0, 15, 0, 0, 18, 52, 0, 171, 205, 239, 165, 165, 90, 90, 195, 60, 193, 248,
-    ];
     const WRONG_SOURCE: [u8; 8] = [8, 115, 193, 35, 0, 15, 0, 0];
 
     #[test]
@@ -129,10 +110,38 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn drop_invalid_packets() -> TestResult {
+        let mut corrupted = VALID_SOURCE;
+        corrupted[21] ^= 0xFF;
+        let src = [&corrupted[..], &VALID_SOURCE[..]].concat();
+
+        let (mut reader, receiver) = Reader::new(&src[..]);
+        reader.run()?;
+        drop(reader);
+
+        assert_eq!(receiver.iter().count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_packets() -> TestResult {
+        let mut src = &[&VALID_SOURCE[..], &VALID_SOURCE[..3]].concat()[..];
+        assert_eq!(read_raw(&mut src)?, Some(VALID_SOURCE.to_vec()));
+        assert!(read_raw(&mut src).is_err());
+        assert_eq!(read_raw(&mut src)?, None);
+
+        let mut src = &WRONG_SOURCE[..];
+        assert!(read_raw(&mut src).is_err());
+
+        Ok(())
+    }
+
     #[test]
     #[should_panic]
     fn invalid_source() {
         let (mut reader, _) = Reader::new(&WRONG_SOURCE[..]);
-        reader.read().unwrap();
+        reader.run().unwrap();
     }
 }
@@ -0,0 +1,375 @@
+//! Recordings: packets with the time they were received on ground, to be replayed.
+//!
+//! A recording is a header (magic, version and metadata), then records made of a
+//! timestamp (nanoseconds since the Unix epoch), a length and the raw packet.
+
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::warn;
+
+use super::reader::HEADER_SIZE;
+use crate::protocol::Packet;
+
+pub const MAGIC: [u8; 8] = *b"SPKTREC\0";
+pub const VERSION: u16 = 1;
+
+/// Size of a record header: timestamp and length.
+pub const RECORD_HEADER_SIZE: usize = 12;
+
+/// Size of the channel to communicate with the replayer
+const CHANNEL_SIZE: usize = 1024;
+
+/// Packet received at the given time (since the Unix epoch).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+
+pub struct RecordingWriter<W: Write> {
+    writer: BufWriter<W>,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    /// Writes the header of the recording, with free-form metadata (e.g. the source).
+    pub fn new(dst: W, metadata: &BTreeMap<String, String>) -> Result<RecordingWriter<W>> {
+        let mut writer = BufWriter::new(dst);
+        writer.write_all(&MAGIC)?;
+        writer.write_u16::<BigEndian>(VERSION)?;
+
+        if metadata.len() > u16::MAX as usize {
+            bail!("`{}` metadata entries are too many", metadata.len());
+        }
+        writer.write_u16::<BigEndian>(metadata.len() as u16)?;
+        for (key, value) in metadata {
+            write_string(&mut writer, key)?;
+            write_string(&mut writer, value)?;
+        }
+
+        Ok(RecordingWriter { writer })
+    }
+
+    pub fn write(&mut self, timestamp: Duration, pkt: Packet) -> Result<()> {
+        self.write_raw(timestamp, &pkt.into_buffer())
+    }
+
+    /// Records the packet as received now.
+    pub fn write_now(&mut self, pkt: Packet) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        self.write(now, pkt)
+    }
+
+    pub fn write_raw(&mut self, timestamp: Duration, data: &[u8]) -> Result<()> {
+        if data.len() > u32::MAX as usize {
+            bail!("Record of size `{}` is too large", data.len());
+        }
+        self.writer
+            .write_u64::<BigEndian>(timestamp.as_nanos() as u64)?;
+        self.writer.write_u32::<BigEndian>(data.len() as u32)?;
+        self.writer
+            .write_all(data)
+            .context("Could not write the record")
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("Could not flush the recording")
+    }
+
+    pub fn into_inner(self) -> Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context("Could not flush the recording")
+    }
+}
+
+fn write_string<W: Write>(writer: &mut W, val: &str) -> Result<()> {
+    if val.len() > u16::MAX as usize {
+        bail!("Metadata of size `{}` is too large", val.len());
+    }
+    writer.write_u16::<BigEndian>(val.len() as u16)?;
+    writer.write_all(val.as_bytes())?;
+    Ok(())
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let len = reader.read_u16::<BigEndian>()? as usize;
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Gives random access to the records, indexed when the recording is opened.
+pub struct RecordingReader<R> {
+    reader: BufReader<R>,
+    metadata: BTreeMap<String, String>,
+    // Timestamp and offset of every record
+    index: Vec<(Duration, u64)>,
+}
+
+impl<R: Read + Seek> RecordingReader<R> {
+    /// Reads the header and indexes the records. A truncated last record is ignored.
+    pub fn open(src: R) -> Result<RecordingReader<R>> {
+        let mut reader = BufReader::new(src);
+
+        let mut magic = [0; 8];
+        reader
+            .read_exact(&mut magic)
+            .context("Could not read the recording header")?;
+        if magic != MAGIC {
+            bail!("Not a recording");
+        }
+        let version = reader.read_u16::<BigEndian>()?;
+        if version != VERSION {
+            bail!("Unsupported recording version `{}`", version);
+        }
+
+        let mut metadata = BTreeMap::new();
+        for _ in 0..reader.read_u16::<BigEndian>()? {
+            let key = read_string(&mut reader)?;
+            let value = read_string(&mut reader)?;
+            metadata.insert(key, value);
+        }
+
+        let mut offset = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(offset))?;
+
+        let mut index = Vec::new();
+        while offset < end {
+            let (timestamp, len) = match read_record_header(&mut reader) {
+                Ok(header) => header,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+            if offset + (RECORD_HEADER_SIZE + len) as u64 > end {
+                break;
+            }
+            index.push((timestamp, offset));
+            // Skipping the packet, without discarding the buffer
+            reader.seek_relative(len as i64)?;
+            offset += (RECORD_HEADER_SIZE + len) as u64;
+        }
+        if offset < end {
+            warn!("Ignoring the truncated record at offset `{}`", offset);
+        }
+
+        Ok(RecordingReader {
+            reader,
+            metadata,
+            index,
+        })
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn timestamp(&self, idx: usize) -> Option<Duration> {
+        self.index.get(idx).map(|(timestamp, _)| *timestamp)
+    }
+
+    /// Index of the first record received at or after the given time.
+    pub fn find(&self, timestamp: Duration) -> usize {
+        self.index.partition_point(|(t, _)| *t < timestamp)
+    }
+
+    pub fn get(&mut self, idx: usize) -> Result<Record> {
+        let offset = match self.index.get(idx) {
+            Some((_, offset)) => *offset,
+            None => bail!("No record `{}` in a recording of `{}`", idx, self.len()),
+        };
+
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let (timestamp, len) = read_record_header(&mut self.reader)?;
+        let mut data = vec![0; len];
+        self.reader.read_exact(&mut data)?;
+
+        Ok(Record { timestamp, data })
+    }
+}
+
+fn read_record_header<R: Read>(reader: &mut R) -> std::io::Result<(Duration, usize)> {
+    let timestamp = Duration::from_nanos(reader.read_u64::<BigEndian>()?);
+    let len = reader.read_u32::<BigEndian>()? as usize;
+    Ok((timestamp, len))
+}
+
+/// Pacing of a replay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed {
+    /// Same intervals between packets as when recorded.
+    Original,
+    /// Intervals divided by the given factor.
+    Scaled(f64),
+    AsFastAsPossible,
+}
+
+impl Speed {
+    /// Intervals divided by the factor, which must be finite and positive.
+    pub fn scaled(factor: f64) -> Result<Speed> {
+        if !factor.is_finite() || factor <= 0.0 {
+            bail!("Speed factor `{}` is not finite and positive", factor);
+        }
+        Ok(Speed::Scaled(factor))
+    }
+}
+
+/// Re-emits the packets of a recording, paced as when received.
+pub struct Replayer<R> {
+    recording: RecordingReader<R>,
+    speed: Speed,
+    start: usize,
+    channel: SyncSender<Packet>,
+}
+
+impl<R: Read + Seek> Replayer<R> {
+    pub fn new(recording: RecordingReader<R>, speed: Speed) -> (Replayer<R>, Receiver<Packet>) {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_SIZE);
+
+        (
+            Replayer {
+                recording,
+                speed,
+                start: 0,
+                channel: sender,
+            },
+            receiver,
+        )
+    }
+
+    /// Starts the replay at the first record received at or after the given time.
+    pub fn start_at(&mut self, timestamp: Duration) {
+        self.start = self.recording.find(timestamp);
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        let origin = match self.recording.timestamp(self.start) {
+            Some(timestamp) => timestamp,
+            None => return Ok(()),
+        };
+        let started = Instant::now();
+
+        for idx in self.start..self.recording.len() {
+            let record = self.recording.get(idx)?;
+
+            let elapsed = record.timestamp.checked_sub(origin).unwrap_or_default();
+            let due = match self.speed {
+                Speed::Original => Some(elapsed),
+                Speed::Scaled(factor) => Some(
+                    Duration::try_from_secs_f64(elapsed.as_secs_f64() / factor)
+                        .with_context(|| format!("Invalid speed factor `{}`", factor))?,
+                ),
+                Speed::AsFastAsPossible => None,
+            };
+            if let Some(wait) = due.and_then(|due| due.checked_sub(started.elapsed())) {
+                thread::sleep(wait);
+            }
+
+            if record.data.len() < HEADER_SIZE {
+                warn!("Skipping record `{}`: no packet header", idx);
+                continue;
+            }
+            let (header, data) = record.data.split_at(HEADER_SIZE);
+            match Packet::try_from_buffers(header, data) {
+                Ok(pkt) => self.channel.send(pkt)?,
+                Err(e) => warn!("Skipping record `{}`: {:#}", idx, e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::test_utils::{TestResult, VALID_SOURCE};
+
+    fn recording() -> Result<Vec<u8>> {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("source".to_string(), "stdin".to_string());
+
+        let mut writer = RecordingWriter::new(Vec::new(), &metadata)?;
+        for ms in [0, 40, 80, 120].iter() {
+            writer.write_raw(Duration::from_millis(1_000 + ms), &VALID_SOURCE)?;
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn too_many_metadata() {
+        let metadata = (0..=u16::MAX as u32)
+            .map(|idx| (idx.to_string(), String::new()))
+            .collect();
+        assert!(RecordingWriter::new(Vec::new(), &metadata).is_err());
+    }
+
+    #[test]
+    fn indexed_reading() -> TestResult {
+        let mut buf = recording()?;
+        buf.extend_from_slice(&[0; 5]); // interrupted recording
+
+        let mut reader = RecordingReader::open(Cursor::new(buf))?;
+        assert_eq!(reader.metadata()["source"], "stdin");
+        assert_eq!(reader.len(), 4);
+        assert_eq!(reader.find(Duration::from_millis(1_050)), 2);
+
+        let record = reader.get(3)?;
+        assert_eq!(record.timestamp, Duration::from_millis(1_120));
+        assert_eq!(record.data, VALID_SOURCE);
+        assert!(reader.get(4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_speeds() -> TestResult {
+        let replay = |speed, start| -> Result<(usize, Duration)> {
+            let reader = RecordingReader::open(Cursor::new(recording()?))?;
+            let (mut replayer, receiver) = Replayer::new(reader, speed);
+            replayer.start_at(start);
+
+            let started = Instant::now();
+            replayer.run()?;
+            drop(replayer);
+            Ok((receiver.iter().count(), started.elapsed()))
+        };
+
+        let (count, elapsed) = replay(Speed::Original, Duration::from_millis(1_040))?;
+        assert_eq!(count, 3);
+        assert!(elapsed >= Duration::from_millis(80));
+
+        let (count, elapsed) = replay(Speed::scaled(4.0)?, Duration::ZERO)?;
+        assert_eq!(count, 4);
+        assert!(elapsed >= Duration::from_millis(30));
+
+        let (count, elapsed) = replay(Speed::AsFastAsPossible, Duration::ZERO)?;
+        assert_eq!(count, 4);
+        assert!(elapsed < Duration::from_millis(100));
+
+        for factor in [0.0, -1.0, f64::NAN, f64::INFINITY].iter() {
+            assert!(Speed::scaled(*factor).is_err());
+        }
+        assert!(replay(Speed::Scaled(0.0), Duration::ZERO).is_err());
+
+        Ok(())
+    }
+}
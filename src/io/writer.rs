@@ -0,0 +1,58 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+use crate::protocol::Packet;
+
+/// Counterpart of `Reader`: writes packets back to back in a byte stream.
+pub struct Writer<W> {
+    writer: W,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(dst: W) -> Writer<W> {
+        Writer { writer: dst }
+    }
+
+    /// Writes the whole packet at once, so that it is never split by a buffered stream.
+    pub fn write(&mut self, pkt: Packet) -> Result<()> {
+        let buf = pkt.into_buffer();
+        self.writer
+            .write_all(&buf)
+            .with_context(|| format!("Could not write the packet of size `{}`", buf.len()))?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("Could not flush the packets")
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::io::Reader;
+    use crate::test_utils::{valid_packet, TestResult, VALID_SOURCE};
+
+    #[test]
+    fn write_then_read() -> TestResult {
+        let mut writer = Writer::new(Vec::new());
+        for _ in 0..2 {
+            writer.write(valid_packet())?;
+        }
+        let stream = writer.into_inner();
+        assert_eq!(stream, [VALID_SOURCE, VALID_SOURCE].concat());
+
+        let (mut reader, receiver) = Reader::new(&stream[..]);
+        reader.run()?;
+        drop(reader);
+        assert_eq!(receiver.iter().count(), 2);
+
+        Ok(())
+    }
+}
@@ -1,7 +1,15 @@
 // Reachable modules
+pub mod decom;
+pub mod export;
+pub mod filter;
+pub mod frames;
 pub mod io;
 pub mod protocol;
+pub mod pus;
+#[cfg(test)]
+mod test_utils;
 
 // Re-exporting
+pub use filter::Filter;
 pub use io::Reader;
 pub use protocol::Packet;
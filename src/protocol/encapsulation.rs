@@ -0,0 +1,231 @@
+//! Encapsulation Packets (CCSDS 133.1-B), carried alongside space packets.
+
+use std::io::Cursor;
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+/// Packet version number of encapsulation packets: `111`.
+pub const VERSION_NUMBER: u8 = 7;
+
+/// Protocol ID of idle packets (fill).
+pub const PROTOCOL_ID_IDLE: u8 = 0;
+
+/// Protocol ID announcing the protocol in the Protocol ID Extension field.
+pub const PROTOCOL_ID_EXTENDED: u8 = 6;
+
+/// Size of the header, depending on its length of length.
+pub fn header_size(length_of_length: u8) -> usize {
+    match length_of_length & 0x03 {
+        0 => 1,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncapsulationPacket {
+    pub protocol_id: u8,
+    /// Size of the Packet Length field: 0, 1, 2 or 4 bytes (encoded as 0 to 3).
+    pub length_of_length: u8,
+    /// User Defined field, with a length of length of 2 or 3.
+    pub user_defined: u8,
+    /// Protocol ID Extension field, with a length of length of 2 or 3.
+    pub protocol_id_extension: u8,
+    /// CCSDS Defined field, with a length of length of 3.
+    pub ccsds_defined: u16,
+    pub data: Vec<u8>,
+}
+
+impl EncapsulationPacket {
+    /// Packet with the shortest header able to carry the data.
+    pub fn new(protocol_id: u8, data: Vec<u8>) -> EncapsulationPacket {
+        let length_of_length = match data.len() {
+            len if len + header_size(1) <= u8::MAX as usize => 1,
+            len if len + header_size(2) <= u16::MAX as usize => 2,
+            _ => 3,
+        };
+
+        EncapsulationPacket {
+            protocol_id,
+            length_of_length,
+            user_defined: 0,
+            protocol_id_extension: 0,
+            ccsds_defined: 0,
+            data,
+        }
+    }
+
+    /// One-byte idle packet.
+    pub fn idle() -> EncapsulationPacket {
+        EncapsulationPacket {
+            protocol_id: PROTOCOL_ID_IDLE,
+            length_of_length: 0,
+            user_defined: 0,
+            protocol_id_extension: 0,
+            ccsds_defined: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.protocol_id == PROTOCOL_ID_IDLE
+    }
+
+    /// Total length of the packet, as announced by its header (which must be whole).
+    pub fn packet_length(header: &[u8]) -> Result<usize> {
+        let len = match header.first() {
+            Some(first) => header_size(*first),
+            None => bail!("Empty header"),
+        };
+        if header.len() < len {
+            bail!("Header of size `{}` instead of `{}`", header.len(), len);
+        }
+
+        // The Packet Length field is the second half of the header
+        let mut cursor = Cursor::new(&header[len / 2..]);
+        let packet_length = match len {
+            1 => 1,
+            2 => cursor.read_u8()? as usize,
+            4 => cursor.read_u16::<BigEndian>()? as usize,
+            _ => cursor.read_u32::<BigEndian>()? as usize,
+        };
+        if packet_length < len {
+            bail!("Packet length `{}` shorter than its header", packet_length);
+        }
+
+        Ok(packet_length)
+    }
+
+    /// Decodes a whole packet: its length is the length of the given buffer.
+    pub fn from_buffer(buf: &[u8]) -> Result<EncapsulationPacket> {
+        let packet_length = EncapsulationPacket::packet_length(buf)?;
+        if packet_length != buf.len() {
+            bail!(
+                "Packet length `{}` does not match the buffer of size `{}`",
+                packet_length,
+                buf.len()
+            );
+        }
+
+        let first = buf[0];
+        if first >> 5 != VERSION_NUMBER {
+            bail!(
+                "Packet version number `{}` instead of `{}`",
+                first >> 5,
+                VERSION_NUMBER
+            );
+        }
+        let protocol_id = (first >> 2) & 0x07;
+        let length_of_length = first & 0x03;
+
+        let (user_defined, protocol_id_extension) = match length_of_length {
+            2 | 3 => (buf[1] >> 4, buf[1] & 0x0F),
+            _ => (0, 0),
+        };
+        let ccsds_defined = match length_of_length {
+            3 => u16::from_be_bytes([buf[2], buf[3]]),
+            _ => 0,
+        };
+
+        Ok(EncapsulationPacket {
+            protocol_id,
+            length_of_length,
+            user_defined,
+            protocol_id_extension,
+            ccsds_defined,
+            data: buf[header_size(length_of_length)..].to_vec(),
+        })
+    }
+
+    /// Encodes the packet, setting its packet length.
+    pub fn get_buffer(&self) -> Result<Vec<u8>> {
+        let header_len = header_size(self.length_of_length);
+        let len = header_len + self.data.len();
+
+        let mut buf = Vec::with_capacity(len);
+        let mut cursor = Cursor::new(&mut buf);
+
+        cursor.write_u8(
+            (VERSION_NUMBER << 5)
+                | ((self.protocol_id & 0x07) << 2)
+                | (self.length_of_length & 0x03),
+        )?;
+        match header_len {
+            1 if !self.data.is_empty() => {
+                bail!("Packet without length of length cannot carry data")
+            }
+            1 => {}
+            2 if len <= u8::MAX as usize => cursor.write_u8(len as u8)?,
+            4 if len <= u16::MAX as usize => {
+                cursor.write_u8((self.user_defined << 4) | (self.protocol_id_extension & 0x0F))?;
+                cursor.write_u16::<BigEndian>(len as u16)?;
+            }
+            8 if len <= u32::MAX as usize => {
+                cursor.write_u8((self.user_defined << 4) | (self.protocol_id_extension & 0x0F))?;
+                cursor.write_u16::<BigEndian>(self.ccsds_defined)?;
+                cursor.write_u32::<BigEndian>(len as u32)?;
+            }
+            _ => bail!(
+                "Packet of length `{}` exceeds its length of length `{}`",
+                len,
+                self.length_of_length
+            ),
+        }
+
+        buf.extend_from_slice(&self.data);
+        Ok(buf)
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::TestResult;
+
+    #[test]
+    fn headers_of_every_length() -> TestResult {
+        let buf = EncapsulationPacket::idle().get_buffer()?;
+        assert_eq!(buf, [0xE0]);
+        assert!(EncapsulationPacket::from_buffer(&buf)?.is_idle());
+
+        let pkt = EncapsulationPacket::new(2, vec![0xAA; 3]);
+        let buf = pkt.get_buffer()?;
+        assert_eq!(buf, [0xE9, 0x05, 0xAA, 0xAA, 0xAA]);
+        assert_eq!(EncapsulationPacket::from_buffer(&buf)?, pkt);
+
+        let mut pkt = EncapsulationPacket::new(PROTOCOL_ID_EXTENDED, vec![0xBB; 300]);
+        pkt.protocol_id_extension = 0x3;
+        assert_eq!(pkt.length_of_length, 2);
+        let buf = pkt.get_buffer()?;
+        assert_eq!(buf[..4], [0xFA, 0x03, 0x01, 0x30]);
+        assert_eq!(EncapsulationPacket::packet_length(&buf[..4])?, 304);
+        assert_eq!(EncapsulationPacket::from_buffer(&buf)?, pkt);
+
+        pkt.length_of_length = 3;
+        pkt.ccsds_defined = 0x1234;
+        let buf = pkt.get_buffer()?;
+        assert_eq!(buf[..8], [0xFB, 0x03, 0x12, 0x34, 0x00, 0x00, 0x01, 0x34]);
+        assert_eq!(EncapsulationPacket::from_buffer(&buf)?, pkt);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_packets() {
+        // Data too long for its length of length
+        let mut pkt = EncapsulationPacket::new(2, vec![0; 300]);
+        pkt.length_of_length = 1;
+        assert!(pkt.get_buffer().is_err());
+
+        // Truncated, or a space packet
+        assert!(EncapsulationPacket::from_buffer(&[0xE9, 0x05, 0xAA]).is_err());
+        assert!(EncapsulationPacket::from_buffer(&[0x09, 0x02]).is_err());
+    }
+}
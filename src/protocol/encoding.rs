@@ -0,0 +1,262 @@
+//! Serialized form of packets, as read and written by serde (`serde` feature).
+//!
+//! A packet is a flat object, with stable field names:
+//!
+//! ```json
+//! {"version":0,"type":"tm","apid":115,"sequence_flags":3,"sequence_counter":291,
+//!  "data_length":15,"secondary_header":{"time_week":4660,"time_ms":11259375},
+//!  "user_data":"a5a55a5ac33c","checksum":49656}
+//! ```
+//!
+//! The secondary header is absent when the packet has none, and the user data field is a
+//! hex string (or a base64 `user_data_base64` string, see [`Encoding`]). When
+//! deserializing, only `type` and `apid` are required: the data length and the checksum
+//! are computed, and checked if given.
+
+use std::convert::TryFrom;
+
+use anyhow::{bail, Error};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::de::{self, Deserializer};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+use super::packet::Packet;
+use super::primary_header::{PktType, PrimaryHeader};
+use super::secondary_header::SecondaryHeader;
+use super::user_data_field::UserDataField;
+
+/// Sequence flags of a packet which is not part of a group ("standalone").
+const STANDALONE: u8 = 0x03;
+/// Size of the secondary header, in bytes.
+const SEC_HEADER_SIZE: usize = 8;
+/// Size of the checksum, in bytes.
+const CHECKSUM_SIZE: usize = 2;
+
+/// Encoding of the user data field when serializing. Both are deserialized.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Encoding {
+    /// Lowercase hex string, e.g. `a5a55a`, as `user_data`.
+    #[default]
+    Hex,
+    /// Standard base64 string, with padding, e.g. `paVa`, as `user_data_base64`.
+    Base64,
+}
+
+impl Encoding {
+    pub fn encode(self, data: &[u8]) -> String {
+        match self {
+            Encoding::Hex => hex::encode(data),
+            Encoding::Base64 => BASE64.encode(data),
+        }
+    }
+
+    /// Name of the field of the user data field.
+    pub fn field(self) -> &'static str {
+        match self {
+            Encoding::Hex => "user_data",
+            Encoding::Base64 => "user_data_base64",
+        }
+    }
+}
+
+/// Packet serialized with the given encoding of its user data field.
+pub struct Encoded<'a> {
+    pub packet: &'a Packet,
+    pub encoding: Encoding,
+}
+
+impl Serialize for Encoded<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let pkt = self.packet;
+        let header = &pkt.pri_header;
+        let user_data = pkt.user_data.as_ref().map_or(&[][..], |field| &field.data);
+
+        let mut state = serializer.serialize_struct("Packet", 9)?;
+        state.serialize_field("version", &header.version_number)?;
+        state.serialize_field("type", &header.packet_type)?;
+        state.serialize_field("apid", &header.apid)?;
+        state.serialize_field("sequence_flags", &header.sequence_flags)?;
+        state.serialize_field("sequence_counter", &header.sequence_counter)?;
+        state.serialize_field("data_length", &header.data_length)?;
+        match &pkt.sec_header {
+            Some(sec_header) => state.serialize_field("secondary_header", sec_header)?,
+            None => state.skip_field("secondary_header")?,
+        }
+        state.serialize_field(self.encoding.field(), &self.encoding.encode(user_data))?;
+        state.serialize_field("checksum", &pkt.checksum)?;
+        state.end()
+    }
+}
+
+impl Serialize for Packet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Encoded {
+            packet: self,
+            encoding: Encoding::Hex,
+        }
+        .serialize(serializer)
+    }
+}
+
+fn standalone() -> u8 {
+    STANDALONE
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    BASE64.decode(text).map(Some).map_err(de::Error::custom)
+}
+
+/// Deserialized form of a packet, before the checks.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PacketRepr {
+    #[serde(default)]
+    version: u8,
+    #[serde(rename = "type")]
+    packet_type: PktType,
+    apid: u16,
+    #[serde(default = "standalone")]
+    sequence_flags: u8,
+    #[serde(default)]
+    sequence_counter: u16,
+    #[serde(default)]
+    data_length: Option<u16>,
+    #[serde(default)]
+    secondary_header: Option<SecondaryHeader>,
+    #[serde(default, with = "hex")]
+    user_data: Vec<u8>,
+    #[serde(default, deserialize_with = "from_base64")]
+    user_data_base64: Option<Vec<u8>>,
+    #[serde(default)]
+    checksum: Option<u16>,
+}
+
+impl TryFrom<PacketRepr> for Packet {
+    type Error = Error;
+
+    fn try_from(mut repr: PacketRepr) -> Result<Packet, Error> {
+        if let Some(data) = repr.user_data_base64.take() {
+            if !repr.user_data.is_empty() {
+                bail!("Both `user_data` and `user_data_base64` are given");
+            }
+            repr.user_data = data;
+        }
+        let sec_header_len = repr
+            .secondary_header
+            .as_ref()
+            .map_or(0, |_| SEC_HEADER_SIZE);
+        let len = sec_header_len + repr.user_data.len() + CHECKSUM_SIZE;
+        let data_length = match PrimaryHeader::data_length_for(len) {
+            Some(data_length) => data_length,
+            None => bail!("Data field of size `{}` is too large", len),
+        };
+        if repr.data_length.is_some_and(|given| given != data_length) {
+            bail!(
+                "Data length `{:?}` instead of `{}`",
+                repr.data_length,
+                data_length
+            );
+        }
+        if repr.version > 0x07
+            || repr.apid > 0x07FF
+            || repr.sequence_flags > 0x03
+            || repr.sequence_counter > 0x3FFF
+        {
+            bail!("Version, APID, sequence flags or sequence counter out of range");
+        }
+
+        let pri_header = PrimaryHeader {
+            version_number: repr.version,
+            packet_type: repr.packet_type,
+            secondary_header_flag: repr.secondary_header.is_some(),
+            apid: repr.apid,
+            sequence_flags: repr.sequence_flags,
+            sequence_counter: repr.sequence_counter,
+            data_length,
+        };
+        let user_data = if repr.user_data.is_empty() {
+            None
+        } else {
+            Some(UserDataField {
+                data: repr.user_data,
+            })
+        };
+
+        let pkt = Packet::new(pri_header, repr.secondary_header, user_data);
+        if repr.checksum.is_some_and(|given| given != pkt.checksum) {
+            bail!(
+                "Checksum `{:?}` instead of `{:#06X}`",
+                repr.checksum,
+                pkt.checksum
+            );
+        }
+        Ok(pkt)
+    }
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::{valid_packet, TestResult, VALID_SOURCE};
+
+    fn from_json(json: &str) -> serde_json::Result<Packet> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn packet_json() -> TestResult {
+        let pkt = valid_packet();
+        let json = serde_json::to_value(&pkt)?;
+        assert_eq!(json["type"], "tm");
+        assert_eq!(json["apid"], 0x73);
+        assert_eq!(json["sequence_counter"], 0x123);
+        assert_eq!(json["secondary_header"]["time_week"], 0x1234);
+        assert_eq!(json["user_data"], "a5a55a5ac33c");
+
+        // The headers have the field names of the flat form
+        let header = serde_json::to_value(&pkt.pri_header)?;
+        for (name, value) in header.as_object().unwrap() {
+            assert_eq!(&json[name], value, "{}", name);
+        }
+        assert!(header.get("secondary_header_flag").is_none());
+
+        let encoded = Encoded {
+            packet: &pkt,
+            encoding: Encoding::Base64,
+        };
+        let base64 = serde_json::to_value(&encoded)?;
+        assert_eq!(base64["user_data_base64"], "paVaWsM8");
+        assert!(base64.get("user_data").is_none());
+        assert_eq!(from_json(&base64.to_string())?.into_buffer(), VALID_SOURCE);
+
+        let parsed = from_json(&json.to_string())?;
+        assert_eq!(parsed.into_buffer(), VALID_SOURCE);
+
+        // Minimal description, without secondary header
+        let pkt = from_json(r#"{"type": "tc", "apid": 16, "user_data": "0102"}"#)?;
+        assert_eq!(pkt.pri_header.packet_type, PktType::Telecommand);
+        assert_eq!(pkt.pri_header.sequence_flags, STANDALONE);
+        assert_eq!(pkt.pri_header.data_length, 3);
+        assert!(pkt.sec_header.is_none());
+        let json = serde_json::to_value(&pkt)?;
+        assert!(json.get("secondary_header").is_none());
+
+        // Inconsistent descriptions
+        assert!(from_json(r#"{"type": "tm", "apid": 1, "data_length": 9}"#).is_err());
+        assert!(from_json(r#"{"type": "tm", "apid": 1, "checksum": 0}"#).is_err());
+        assert!(from_json(r#"{"type": "tm", "apid": 4096}"#).is_err());
+        assert!(from_json(r#"{"type": "tm", "apid": 1, "user_data": "xyz"}"#).is_err());
+        let both = r#"{"type": "tm", "apid": 1, "user_data": "01", "user_data_base64": "AQ=="}"#;
+        assert!(from_json(both).is_err());
+
+        Ok(())
+    }
+}
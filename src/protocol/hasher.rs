@@ -46,7 +46,7 @@ pub fn compute_partial(initial_value: u16, bytes: &[u8]) -> u16 {
 
 pub fn append_checksum(buf: &mut Vec<u8>) {
     // Checksum => ATTENTION TO ENDIANNESS <= (Big Endian)
-    let checksum = compute(&buf);
+    let checksum = compute(buf);
     let high = (checksum >> 8) as u8;
     let low = checksum as u8;
     buf.push(high);
@@ -55,7 +55,7 @@ pub fn append_checksum(buf: &mut Vec<u8>) {
 
 pub fn append_partial_checksum(initial_value: u16, buf: &mut Vec<u8>) {
     // Checksum => ATTENTION TO ENDIANNESS <= (Big Endian)
-    let checksum = compute_partial(initial_value, &buf);
+    let checksum = compute_partial(initial_value, buf);
     let high = (checksum >> 8) as u8;
     let low = checksum as u8;
     buf.push(high);
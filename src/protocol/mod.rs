@@ -1,13 +1,19 @@
 // Reachable modules
-mod hasher;
+pub mod encapsulation;
+#[cfg(feature = "serde")]
+pub mod encoding;
+pub(crate) mod hasher;
 mod packet;
 mod primary_header;
 mod secondary_header;
 mod user_data_field;
 
 // Re-exporting
-pub use packet::Packet;
-pub use primary_header::PktType;
+pub use encapsulation::EncapsulationPacket;
+#[cfg(feature = "serde")]
+pub use encoding::Encoding;
+pub use packet::{Packet, HEADER_SIZE, VERSION_NUMBER};
+pub use primary_header::{PktType, IDLE_APID};
 
 pub use primary_header::PrimaryHeader;
 pub use secondary_header::SecondaryHeader;
@@ -1,5 +1,6 @@
 use std::io::{Cursor, Seek, SeekFrom};
 
+use anyhow::{bail, Result};
 use byteorder::{BigEndian, ReadBytesExt};
 
 use super::primary_header::PrimaryHeader;
@@ -8,7 +9,20 @@ use super::user_data_field::UserDataField;
 
 use super::hasher::{self, INITIAL_VALUE};
 
+/// Size of the packet primary header. Fixed size: 6 bytes.
+pub const HEADER_SIZE: usize = 6;
+
+/// Packet version number of space packets: `000`.
+pub const VERSION_NUMBER: u8 = 0;
+
+/// Serialized with the fields of its headers, and deserialized checking the data length
+/// and checksum if given (see [`encoding`](super::encoding)).
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(try_from = "super::encoding::PacketRepr")
+)]
 pub struct Packet {
     pub pri_header: PrimaryHeader,
     pub sec_header: Option<SecondaryHeader>,
@@ -80,8 +94,8 @@ impl Packet {
         };
 
         // Validating the given buffers (using checksum)
-        let checksum = hasher::compute_partial(INITIAL_VALUE, &header_buf);
-        let checksum = hasher::compute_partial(checksum, &data_buf);
+        let checksum = hasher::compute_partial(INITIAL_VALUE, header_buf);
+        let checksum = hasher::compute_partial(checksum, data_buf);
         assert_eq!(checksum, 0);
 
         let mut cursor = Cursor::new(data_buf);
@@ -96,6 +110,34 @@ impl Packet {
         }
     }
 
+    /// Same as `from_buffers`, but reports invalid buffers instead of panicking.
+    pub fn try_from_buffers(header_buf: &[u8], data_buf: &[u8]) -> Result<Packet> {
+        if header_buf.len() != HEADER_SIZE {
+            bail!(
+                "Header of size `{}` instead of `{}`",
+                header_buf.len(),
+                HEADER_SIZE
+            );
+        }
+        if data_buf.len() < 2 {
+            bail!("Data field of size `{}` has no checksum", data_buf.len());
+        }
+        if PrimaryHeader::from_buffer(header_buf).secondary_header_flag && data_buf.len() < 10 {
+            bail!(
+                "Data field of size `{}` has no secondary header",
+                data_buf.len()
+            );
+        }
+
+        let checksum = hasher::compute_partial(INITIAL_VALUE, header_buf);
+        let checksum = hasher::compute_partial(checksum, data_buf);
+        if checksum != 0 {
+            bail!("Invalid checksum: residue `{:#06X}`", checksum);
+        }
+
+        Ok(Packet::from_buffers(header_buf, data_buf))
+    }
+
     pub fn into_buffer(self) -> Vec<u8> {
         // Primary Header
         let mut buf = self.pri_header.get_buffer();
@@ -165,7 +207,7 @@ mod test {
 
         assert_eq!(pkt.pri_header.version_number, 0);
         assert_eq!(pkt.pri_header.packet_type, PktType::Telemetry);
-        assert_eq!(pkt.pri_header.secondary_header_flag, true);
+        assert!(pkt.pri_header.secondary_header_flag);
         assert_eq!(pkt.pri_header.apid, 0x0073);
         assert_eq!(pkt.pri_header.sequence_flags, 0x03);
         assert_eq!(pkt.pri_header.sequence_counter, 0x0123);
@@ -187,7 +229,7 @@ mod test {
 
         assert_eq!(pkt.pri_header.version_number, 0);
         assert_eq!(pkt.pri_header.packet_type, PktType::Telecommand);
-        assert_eq!(pkt.pri_header.secondary_header_flag, false);
+        assert!(!pkt.pri_header.secondary_header_flag);
         assert_eq!(pkt.pri_header.apid, 0x0754);
         assert_eq!(pkt.pri_header.sequence_flags, 0x03);
         assert_eq!(pkt.pri_header.sequence_counter, 0x0682);
@@ -200,4 +242,16 @@ mod test {
 
         assert_eq!(pkt.checksum, 0x2DDD);
     }
+
+    #[test]
+    fn test_try_from_buffers() {
+        assert!(Packet::try_from_buffers(&SP1_HEADER, &SP1_BODY).is_ok());
+        assert!(Packet::try_from_buffers(&SP2_HEADER, &SP2_BODY).is_ok());
+
+        let mut corrupted = SP1_BODY;
+        corrupted[3] ^= 0x10;
+        assert!(Packet::try_from_buffers(&SP1_HEADER, &corrupted).is_err());
+        assert!(Packet::try_from_buffers(&SP1_HEADER, &SP1_BODY[..1]).is_err());
+        assert!(Packet::try_from_buffers(&SP1_HEADER[..4], &SP1_BODY).is_err());
+    }
 }
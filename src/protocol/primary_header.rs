@@ -1,18 +1,33 @@
 use std::cmp::PartialEq;
+use std::convert::TryFrom;
 use std::io::Cursor;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// APID reserved for idle packets.
+pub const IDLE_APID: u16 = 0x07FF;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PktType {
+    #[cfg_attr(feature = "serde", serde(rename = "tm"))]
     Telemetry = 0,
+    #[cfg_attr(feature = "serde", serde(rename = "tc"))]
     Telecommand = 1,
 }
 
+/// Serialized with the field names of the flat `Packet` form, in which the secondary
+/// header flag is implied by the presence of the secondary header.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PrimaryHeader {
+    #[cfg_attr(feature = "serde", serde(rename = "version"))]
     pub version_number: u8,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
     pub packet_type: PktType,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub secondary_header_flag: bool,
     pub apid: u16,
     pub sequence_flags: u8,
@@ -58,12 +73,12 @@ impl PrimaryHeader {
         val = (self.version_number as u16) << 13;
         val |= (self.packet_type as u16) << 12;
         val |= (self.secondary_header_flag as u16) << 11;
-        val |= self.apid as u16;
+        val |= self.apid;
         cursor.write_u16::<BigEndian>(val).unwrap();
 
         // Next 2 bytes
         val = (self.sequence_flags as u16) << 14;
-        val |= self.sequence_counter as u16;
+        val |= self.sequence_counter;
         cursor.write_u16::<BigEndian>(val).unwrap();
 
         // Final 2 bytes
@@ -71,6 +86,17 @@ impl PrimaryHeader {
 
         buf
     }
+
+    /// Size of the packet data field (secondary header, user data and checksum).
+    pub fn data_field_size(&self) -> usize {
+        // As specified by the protocol: #octets = PKT_DATA_LENGTH + 1
+        self.data_length as usize + 1
+    }
+
+    /// `data_length` of a packet data field of the given size, if it can describe it.
+    pub fn data_length_for(data_field_size: usize) -> Option<u16> {
+        u16::try_from(data_field_size.checked_sub(1)?).ok()
+    }
 }
 
 /// Masks to filter the desired fields in the provided buffer
@@ -137,7 +163,7 @@ mod test {
 
         assert_eq!(pkt.version_number, 0);
         assert_eq!(pkt.packet_type, PktType::Telemetry);
-        assert_eq!(pkt.secondary_header_flag, true);
+        assert!(pkt.secondary_header_flag);
         assert_eq!(pkt.apid, 0x0073);
         assert_eq!(pkt.sequence_flags, 0x03);
         assert_eq!(pkt.sequence_counter, 0x0123);
@@ -162,7 +188,7 @@ mod test {
 
         assert_eq!(pkt.version_number, 0);
         assert_eq!(pkt.packet_type, PktType::Telecommand);
-        assert_eq!(pkt.secondary_header_flag, false);
+        assert!(!pkt.secondary_header_flag);
         assert_eq!(pkt.apid, 0x0754);
         assert_eq!(pkt.sequence_flags, 0x03);
         assert_eq!(pkt.sequence_counter, 0x0682);
@@ -180,4 +206,15 @@ mod test {
         let buf = pkt.get_buffer();
         assert_eq!(buf, SP2_HEADER);
     }
+
+    #[test]
+    fn test_data_field_size() {
+        let pkt = PrimaryHeader::from_buffer(&SP1_HEADER);
+        assert_eq!(pkt.data_field_size(), 16);
+        assert_eq!(PrimaryHeader::data_length_for(16), Some(0x000F));
+
+        assert_eq!(PrimaryHeader::data_length_for(0x10000), Some(0xFFFF));
+        assert_eq!(PrimaryHeader::data_length_for(0x10001), None);
+        assert_eq!(PrimaryHeader::data_length_for(0), None);
+    }
 }
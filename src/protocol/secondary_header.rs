@@ -1,8 +1,12 @@
 use std::io::{Cursor, Seek, SeekFrom};
+use std::time::Duration;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SecondaryHeader {
     pub time_week: u32,
     pub time_ms: u32,
@@ -28,4 +32,10 @@ impl SecondaryHeader {
 
         buf
     }
+
+    /// Time since the epoch of the time system (weeks and milliseconds of the week).
+    pub fn time(&self) -> Duration {
+        Duration::from_secs(self.time_week as u64 * 7 * 86_400)
+            + Duration::from_millis(self.time_ms as u64)
+    }
 }
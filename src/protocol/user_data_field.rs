@@ -1,5 +1,11 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Serialized as a hex string.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
 pub struct UserDataField {
+    #[cfg_attr(feature = "serde", serde(with = "hex"))]
     pub data: Vec<u8>,
 }
 
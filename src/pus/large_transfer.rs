@@ -0,0 +1,322 @@
+//! PUS Service 13: large data transfer.
+//!
+//! Every part carries the large message transaction identifier (u16) and the part
+//! sequence number (u16, starting at 1) ahead of the part data.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use anyhow::{bail, Context, Result};
+use byteorder::{BigEndian, ReadBytesExt};
+
+use super::{build_packet, ServiceHeader};
+use crate::protocol::{Packet, PktType};
+
+/// Service type of the large data transfer.
+pub const SERVICE: u8 = 13;
+
+/// Size of the part header (transaction identifier + part sequence number).
+pub const PART_HEADER_SIZE: usize = 4;
+
+/// Subservices of the large data transfer service.
+pub mod subservice {
+    pub const FIRST_DOWNLINK_PART: u8 = 1;
+    pub const INTERMEDIATE_DOWNLINK_PART: u8 = 2;
+    pub const LAST_DOWNLINK_PART: u8 = 3;
+    pub const DOWNLINK_ABORT: u8 = 4;
+
+    pub const FIRST_UPLINK_PART: u8 = 9;
+    pub const INTERMEDIATE_UPLINK_PART: u8 = 10;
+    pub const LAST_UPLINK_PART: u8 = 11;
+}
+
+/// Large message, fully reassembled from its downlink parts.
+#[derive(Debug, PartialEq)]
+pub struct CompletedTransfer {
+    pub transaction_id: u16,
+    pub data: Vec<u8>,
+}
+
+/// Ongoing transfer: last received part and data received so far.
+struct Transfer {
+    last_part: u16,
+    data: Vec<u8>,
+}
+
+/// Reassembles downlink part reports into large messages, keyed by transaction ID.
+#[derive(Default)]
+pub struct Reassembler {
+    transfers: HashMap<u16, Transfer>,
+}
+
+impl Reassembler {
+    pub fn new() -> Reassembler {
+        Reassembler::default()
+    }
+
+    /// Number of transfers which have started but not yet finished.
+    pub fn pending(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// Consumes a downlink part report, returning the message once its last part arrives.
+    pub fn push(&mut self, pkt: &Packet) -> Result<Option<CompletedTransfer>> {
+        let (header, app_data) =
+            ServiceHeader::from_packet(pkt).context("Packet without PUS service header")?;
+        if header.service != SERVICE {
+            bail!(
+                "Not a large data transfer packet: service `{}`",
+                header.service
+            );
+        }
+
+        let (transaction_id, part, part_data) = parse_part(app_data)?;
+
+        match header.subservice {
+            subservice::FIRST_DOWNLINK_PART => {
+                if part != 1 {
+                    bail!(
+                        "First part of transaction `{}` numbered `{}`",
+                        transaction_id,
+                        part
+                    );
+                }
+                if self.transfers.contains_key(&transaction_id) {
+                    bail!(
+                        "Transaction `{}` restarted before completion",
+                        transaction_id
+                    );
+                }
+                let transfer = Transfer {
+                    last_part: part,
+                    data: part_data.to_vec(),
+                };
+                self.transfers.insert(transaction_id, transfer);
+                Ok(None)
+            }
+            subservice::INTERMEDIATE_DOWNLINK_PART => {
+                self.append(transaction_id, part, part_data)?;
+                Ok(None)
+            }
+            subservice::LAST_DOWNLINK_PART => {
+                self.append(transaction_id, part, part_data)?;
+                let transfer = self
+                    .transfers
+                    .remove(&transaction_id)
+                    .expect("Appending succeeded: transfer should exist");
+                Ok(Some(CompletedTransfer {
+                    transaction_id,
+                    data: transfer.data,
+                }))
+            }
+            subservice::DOWNLINK_ABORT => {
+                self.transfers.remove(&transaction_id);
+                Ok(None)
+            }
+            other => bail!("Unexpected large data transfer subservice `{}`", other),
+        }
+    }
+
+    fn append(&mut self, transaction_id: u16, part: u16, part_data: &[u8]) -> Result<()> {
+        let transfer = match self.transfers.get_mut(&transaction_id) {
+            Some(transfer) => transfer,
+            None => bail!(
+                "Part `{}` of unknown transaction `{}`",
+                part,
+                transaction_id
+            ),
+        };
+
+        let expected = transfer.last_part.wrapping_add(1);
+        if part != expected {
+            self.transfers.remove(&transaction_id);
+            bail!(
+                "Transaction `{}` aborted: expected part `{}`, received `{}`",
+                transaction_id,
+                expected,
+                part
+            );
+        }
+
+        transfer.last_part = part;
+        transfer.data.extend_from_slice(part_data);
+        Ok(())
+    }
+}
+
+fn parse_part(app_data: &[u8]) -> Result<(u16, u16, &[u8])> {
+    if app_data.len() < PART_HEADER_SIZE {
+        bail!("Part of size `{}` is missing its header", app_data.len());
+    }
+
+    let mut cursor = Cursor::new(app_data);
+    let transaction_id = cursor.read_u16::<BigEndian>()?;
+    let part = cursor.read_u16::<BigEndian>()?;
+
+    Ok((transaction_id, part, &app_data[PART_HEADER_SIZE..]))
+}
+
+/// Splits an image in uplink part telecommands of at most `part_size` data octets.
+///
+/// The image must need at least two parts (first and last), otherwise a regular
+/// telecommand should be used instead.
+pub fn split_uplink(
+    apid: u16,
+    transaction_id: u16,
+    sequence_counter: u16,
+    image: &[u8],
+    part_size: usize,
+) -> Result<Vec<Packet>> {
+    if part_size == 0 {
+        bail!("Part size should be positive");
+    }
+    if image.len() <= part_size {
+        bail!(
+            "Image of size `{}` fits in a single part of size `{}`",
+            image.len(),
+            part_size
+        );
+    }
+
+    let chunks: Vec<&[u8]> = image.chunks(part_size).collect();
+    if chunks.len() > u16::MAX as usize {
+        bail!(
+            "Image needs `{}` parts, more than the maximum",
+            chunks.len()
+        );
+    }
+
+    let last = chunks.len() - 1;
+    let packets = chunks
+        .iter()
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let subservice = match idx {
+                0 => subservice::FIRST_UPLINK_PART,
+                idx if idx == last => subservice::LAST_UPLINK_PART,
+                _ => subservice::INTERMEDIATE_UPLINK_PART,
+            };
+
+            let part = (idx + 1) as u16;
+            let mut app_data = Vec::with_capacity(PART_HEADER_SIZE + chunk.len());
+            app_data.extend_from_slice(&transaction_id.to_be_bytes());
+            app_data.extend_from_slice(&part.to_be_bytes());
+            app_data.extend_from_slice(chunk);
+
+            build_packet(
+                PktType::Telecommand,
+                apid,
+                sequence_counter.wrapping_add(idx as u16),
+                None,
+                ServiceHeader::new(SERVICE, subservice),
+                &app_data,
+            )
+            .with_context(|| format!("Part `{}` does not fit in a packet", part))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(packets)
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_utils::TestResult;
+
+    fn downlink_part(subservice: u8, transaction_id: u16, part: u16, data: &[u8]) -> Packet {
+        let mut app_data = transaction_id.to_be_bytes().to_vec();
+        app_data.extend_from_slice(&part.to_be_bytes());
+        app_data.extend_from_slice(data);
+
+        let header = ServiceHeader::new(SERVICE, subservice);
+        build_packet(PktType::Telemetry, 0x0073, part, None, header, &app_data).unwrap()
+    }
+
+    #[test]
+    fn reassemble_interleaved_transfers() -> TestResult {
+        let mut reassembler = Reassembler::new();
+
+        let first_a = downlink_part(subservice::FIRST_DOWNLINK_PART, 7, 1, &[1, 2]);
+        let first_b = downlink_part(subservice::FIRST_DOWNLINK_PART, 8, 1, &[9]);
+        let middle_a = downlink_part(subservice::INTERMEDIATE_DOWNLINK_PART, 7, 2, &[3]);
+        let last_a = downlink_part(subservice::LAST_DOWNLINK_PART, 7, 3, &[4, 5]);
+
+        assert_eq!(reassembler.push(&first_a)?, None);
+        assert_eq!(reassembler.push(&first_b)?, None);
+        assert_eq!(reassembler.push(&middle_a)?, None);
+        assert_eq!(reassembler.pending(), 2);
+
+        let done = reassembler.push(&last_a)?.unwrap();
+        assert_eq!(done.transaction_id, 7);
+        assert_eq!(done.data, [1, 2, 3, 4, 5]);
+        assert_eq!(reassembler.pending(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_part_aborts_transfer() {
+        let mut reassembler = Reassembler::new();
+
+        let first = downlink_part(subservice::FIRST_DOWNLINK_PART, 7, 1, &[1]);
+        let last = downlink_part(subservice::LAST_DOWNLINK_PART, 7, 3, &[3]);
+
+        reassembler.push(&first).unwrap();
+        assert!(reassembler.push(&last).is_err());
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn restarted_transfer_is_rejected() -> TestResult {
+        let mut reassembler = Reassembler::new();
+
+        let first = downlink_part(subservice::FIRST_DOWNLINK_PART, 7, 1, &[1]);
+        let restart = downlink_part(subservice::FIRST_DOWNLINK_PART, 7, 1, &[9]);
+        let last = downlink_part(subservice::LAST_DOWNLINK_PART, 7, 2, &[2]);
+
+        reassembler.push(&first)?;
+        assert!(reassembler.push(&restart).is_err());
+
+        // The pending transfer is left untouched
+        let done = reassembler.push(&last)?.unwrap();
+        assert_eq!(done.data, [1, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn split_image_in_uplink_parts() -> TestResult {
+        let image: Vec<u8> = (0..10).collect();
+        let packets = split_uplink(0x0754, 3, 0x3FFF, &image, 4)?;
+        assert_eq!(packets.len(), 3);
+
+        let subservices: Vec<u8> = packets
+            .iter()
+            .map(|pkt| ServiceHeader::from_packet(pkt).unwrap().0.subservice)
+            .collect();
+        assert_eq!(subservices, [9, 10, 11]);
+        assert_eq!(packets[1].pri_header.sequence_counter, 0x0000);
+
+        let mut data = Vec::new();
+        for (idx, pkt) in packets.iter().enumerate() {
+            let (_, app_data) = ServiceHeader::from_packet(pkt).unwrap();
+            let (transaction_id, part, part_data) = parse_part(app_data)?;
+            assert_eq!(transaction_id, 3);
+            assert_eq!(part as usize, idx + 1);
+            data.extend_from_slice(part_data);
+        }
+        assert_eq!(data, image);
+
+        assert!(split_uplink(0x0754, 3, 0, &image, 10).is_err());
+        // Parts larger than a packet
+        let image = vec![0; 0x20000];
+        assert!(split_uplink(0x0754, 3, 0, &image, 0x10000).is_err());
+
+        Ok(())
+    }
+}
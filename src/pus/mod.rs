@@ -0,0 +1,140 @@
+//! Packet Utilisation Standard (ECSS-E-70-41A) services built on top of `Packet`.
+//!
+//! The time of the packet is carried by the `SecondaryHeader`, so the PUS service
+//! type and subtype are the first two octets of the `UserDataField`.
+
+// Reachable modules
+pub mod large_transfer;
+
+// Re-exporting
+pub use large_transfer::{split_uplink, CompletedTransfer, Reassembler};
+
+use anyhow::{bail, Result};
+
+use crate::protocol::{Packet, PktType, PrimaryHeader, SecondaryHeader, UserDataField};
+
+/// Size of the service header (service type + service subtype).
+pub const SERVICE_HEADER_SIZE: usize = 2;
+
+/// Size of the packet checksum (Packet Error Control).
+const CHECKSUM_SIZE: usize = 2;
+
+/// Sequence flags of a packet which is not part of a group ("standalone").
+const STANDALONE: u8 = 0x03;
+
+/// Max value of the packet sequence counter (14 bits).
+pub const SEQUENCE_COUNTER_MASK: u16 = 0x3FFF;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServiceHeader {
+    pub service: u8,
+    pub subservice: u8,
+}
+
+impl ServiceHeader {
+    pub fn new(service: u8, subservice: u8) -> ServiceHeader {
+        ServiceHeader {
+            service,
+            subservice,
+        }
+    }
+
+    /// Splits the user data field of the packet in service header and application data.
+    pub fn from_packet(pkt: &Packet) -> Option<(ServiceHeader, &[u8])> {
+        let data = &pkt.user_data.as_ref()?.data;
+        if data.len() < SERVICE_HEADER_SIZE {
+            return None;
+        }
+
+        let header = ServiceHeader::new(data[0], data[1]);
+        Some((header, &data[SERVICE_HEADER_SIZE..]))
+    }
+
+    pub fn get_buffer(&self) -> Vec<u8> {
+        vec![self.service, self.subservice]
+    }
+}
+
+/// Builds a standalone PUS packet, computing the `data_length` of the primary header.
+/// Fails if the packet data field would be larger than `data_length` can describe.
+pub fn build_packet(
+    packet_type: PktType,
+    apid: u16,
+    sequence_counter: u16,
+    sec_header: Option<SecondaryHeader>,
+    service_header: ServiceHeader,
+    app_data: &[u8],
+) -> Result<Packet> {
+    let mut data = service_header.get_buffer();
+    data.extend_from_slice(app_data);
+
+    let sec_header_len = if sec_header.is_some() { 8 } else { 0 };
+    let data_field_size = sec_header_len + data.len() + CHECKSUM_SIZE;
+    let data_length = match PrimaryHeader::data_length_for(data_field_size) {
+        Some(data_length) => data_length,
+        None => bail!(
+            "Packet data field of size `{}` is too large",
+            data_field_size
+        ),
+    };
+
+    let pri_header = PrimaryHeader {
+        version_number: 0,
+        packet_type,
+        secondary_header_flag: sec_header.is_some(),
+        apid,
+        sequence_flags: STANDALONE,
+        sequence_counter: sequence_counter & SEQUENCE_COUNTER_MASK,
+        data_length,
+    };
+
+    Ok(Packet::new(
+        pri_header,
+        sec_header,
+        Some(UserDataField { data }),
+    ))
+}
+
+//
+// UNIT TESTS
+//
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::io::reader::DATA_MAX_SIZE;
+    use crate::test_utils::TestResult;
+
+    #[test]
+    fn build_and_parse_service_header() -> TestResult {
+        let header = ServiceHeader::new(13, 9);
+        let pkt = build_packet(PktType::Telecommand, 0x0754, 0x4001, None, header, &[1, 2])?;
+
+        assert_eq!(pkt.pri_header.sequence_counter, 0x0001);
+        assert_eq!(pkt.pri_header.data_length, 5);
+
+        let (parsed, app_data) = ServiceHeader::from_packet(&pkt).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(app_data, [1, 2]);
+
+        let (header_buf, data_buf) = pkt.into_buffers();
+        let pkt = Packet::from_buffers(&header_buf, &data_buf);
+        assert_eq!(ServiceHeader::from_packet(&pkt).unwrap().0, header);
+
+        Ok(())
+    }
+
+    #[test]
+    fn data_field_too_large() {
+        let header = ServiceHeader::new(13, 9);
+        let app_data = vec![0; DATA_MAX_SIZE - SERVICE_HEADER_SIZE - CHECKSUM_SIZE];
+        assert!(build_packet(PktType::Telecommand, 1, 0, None, header, &app_data).is_ok());
+
+        let time = SecondaryHeader {
+            time_week: 0,
+            time_ms: 0,
+        };
+        assert!(build_packet(PktType::Telecommand, 1, 0, Some(time), header, &app_data).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! Fixtures shared by the unit tests.
+
+use crate::protocol::{
+    Packet, PktType, PrimaryHeader, SecondaryHeader, UserDataField, HEADER_SIZE,
+};
+
+pub type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Telemetry packet of APID 0x73, with a secondary header and a valid checksum.
+pub const VALID_SOURCE: [u8; 22] = [
+    8, 115, 193, 35, 0, 15, 0, 0, 18, 52, 0, 171, 205, 239, 165, 165, 90, 90, 195, 60, 193, 248,
+];
+
+/// Telecommand packet of APID 0x754, without secondary header, with a valid checksum.
+pub const VALID_TC_SOURCE: [u8; 11] = [23, 84, 198, 130, 0, 4, 1, 2, 0, 45, 221];
+
+/// Packet of `VALID_SOURCE`.
+pub fn valid_packet() -> Packet {
+    Packet::from_buffers(&VALID_SOURCE[..HEADER_SIZE], &VALID_SOURCE[HEADER_SIZE..])
+}
+
+/// Standalone packet, without secondary header nor user data field unless given.
+pub struct PacketBuilder {
+    packet_type: PktType,
+    apid: u16,
+    sequence_counter: u16,
+    sec_header: Option<SecondaryHeader>,
+    data: Vec<u8>,
+}
+
+impl PacketBuilder {
+    pub fn new(packet_type: PktType, apid: u16) -> PacketBuilder {
+        PacketBuilder {
+            packet_type,
+            apid,
+            sequence_counter: 0,
+            sec_header: None,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn sequence_counter(mut self, sequence_counter: u16) -> PacketBuilder {
+        self.sequence_counter = sequence_counter;
+        self
+    }
+
+    pub fn time(mut self, time_week: u32, time_ms: u32) -> PacketBuilder {
+        self.sec_header = Some(SecondaryHeader { time_week, time_ms });
+        self
+    }
+
+    pub fn data(mut self, data: &[u8]) -> PacketBuilder {
+        self.data = data.to_vec();
+        self
+    }
+
+    pub fn build(self) -> Packet {
+        // Secondary header, user data field and checksum
+        let sec_header_len = if self.sec_header.is_some() { 8 } else { 0 };
+        let data_field_size = sec_header_len + self.data.len() + 2;
+
+        let pri_header = PrimaryHeader {
+            version_number: 0,
+            packet_type: self.packet_type,
+            secondary_header_flag: self.sec_header.is_some(),
+            apid: self.apid,
+            sequence_flags: 0x03,
+            sequence_counter: self.sequence_counter,
+            data_length: PrimaryHeader::data_length_for(data_field_size).unwrap(),
+        };
+        let user_data = if self.data.is_empty() {
+            None
+        } else {
+            Some(UserDataField { data: self.data })
+        };
+        Packet::new(pri_header, self.sec_header, user_data)
+    }
+}